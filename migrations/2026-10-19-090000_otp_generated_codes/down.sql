@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DELETE FROM otp WHERE length(code) > 6;
+ALTER TABLE otp ALTER COLUMN code TYPE VARCHAR(6);
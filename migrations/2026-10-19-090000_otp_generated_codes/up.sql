@@ -0,0 +1,5 @@
+-- Your SQL goes here
+ALTER TABLE otp ALTER COLUMN code TYPE VARCHAR(128);
+
+-- codes are compared in their normalized form (alphanumeric only, upper case)
+UPDATE otp SET code = upper(regexp_replace(code, '[^a-zA-Z0-9]', '', 'g'));
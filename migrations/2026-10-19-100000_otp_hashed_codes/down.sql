@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+-- hashed codes can not be restored into plaintext
+DELETE FROM otp WHERE legacy_code IS NULL;
+ALTER TABLE otp DROP COLUMN code_prefix;
+ALTER TABLE otp DROP COLUMN code_hash;
+ALTER TABLE otp ALTER COLUMN legacy_code SET NOT NULL;
+ALTER TABLE otp RENAME COLUMN legacy_code TO code;
@@ -0,0 +1,9 @@
+-- Your SQL goes here
+-- codes are only kept as keyed hash, the hash is computed by the application as it holds the secret.
+-- existing plaintext codes are moved into `legacy_code` and get hashed (and cleared) on the next startup
+ALTER TABLE otp RENAME COLUMN code TO legacy_code;
+ALTER TABLE otp ALTER COLUMN legacy_code DROP NOT NULL;
+ALTER TABLE otp ADD COLUMN code_hash VARCHAR(64) UNIQUE;
+ALTER TABLE otp ADD COLUMN code_prefix VARCHAR(8) NOT NULL DEFAULT '';
+
+UPDATE otp SET code_prefix = left(legacy_code, 3);
@@ -0,0 +1,10 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE otp DROP COLUMN account_expires_at;
+ALTER TABLE otp DROP COLUMN groups;
+ALTER TABLE otp DROP COLUMN restricted_to;
+ALTER TABLE otp DROP COLUMN grant_admin;
+
+DROP TABLE group_members;
+DROP TABLE groups;
+
+ALTER TABLE users DROP COLUMN expires_at;
@@ -0,0 +1,19 @@
+-- Your SQL goes here
+ALTER TABLE users ADD COLUMN expires_at TIMESTAMPTZ;
+
+CREATE TABLE IF NOT EXISTS groups (
+  group_id UUID NOT NULL PRIMARY KEY,
+  name VARCHAR(64) NOT NULL UNIQUE
+);
+
+CREATE TABLE IF NOT EXISTS group_members (
+  group_id UUID NOT NULL REFERENCES groups (group_id) ON DELETE CASCADE,
+  user_id UUID NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+  PRIMARY KEY (group_id, user_id)
+);
+
+-- payload of REGISTER codes, applied to the user that redeems the code
+ALTER TABLE otp ADD COLUMN grant_admin BOOL NOT NULL DEFAULT false;
+ALTER TABLE otp ADD COLUMN restricted_to VARCHAR(254);
+ALTER TABLE otp ADD COLUMN groups TEXT[] NOT NULL DEFAULT '{}';
+ALTER TABLE otp ADD COLUMN account_expires_at TIMESTAMPTZ;
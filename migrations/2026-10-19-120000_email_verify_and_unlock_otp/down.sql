@@ -0,0 +1,10 @@
+-- This file should undo anything in `up.sql`
+-- values of an enum can not be dropped, so only codes of the new types are removed
+DELETE FROM otp WHERE code_type IN ('EMAIL_VERIFY', 'UNLOCK');
+
+ALTER TABLE users DROP COLUMN locked_at;
+ALTER TABLE users DROP COLUMN failed_login_attempts;
+ALTER TABLE users DROP COLUMN email_verified_at;
+ALTER TABLE users DROP COLUMN email;
+
+ALTER TABLE otp DROP COLUMN email;
@@ -0,0 +1,13 @@
+-- Your SQL goes here
+ALTER TYPE otp_type ADD VALUE IF NOT EXISTS 'EMAIL_VERIFY';
+ALTER TYPE otp_type ADD VALUE IF NOT EXISTS 'UNLOCK';
+
+-- address an EMAIL_VERIFY code confirms
+ALTER TABLE otp ADD COLUMN email VARCHAR(254);
+
+ALTER TABLE users ADD COLUMN email VARCHAR(254);
+ALTER TABLE users ADD COLUMN email_verified_at TIMESTAMPTZ;
+
+-- brute-force lockout, separate from the admin controlled `blocked` flag
+ALTER TABLE users ADD COLUMN failed_login_attempts INT NOT NULL DEFAULT 0;
+ALTER TABLE users ADD COLUMN locked_at TIMESTAMPTZ;
@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX otp_batch_id_idx;
+
+ALTER TABLE otp DROP COLUMN expires_at;
+ALTER TABLE otp DROP COLUMN batch_id;
+
+DROP TABLE otp_batches;
@@ -0,0 +1,14 @@
+-- Your SQL goes here
+CREATE TABLE IF NOT EXISTS otp_batches (
+  batch_id UUID NOT NULL PRIMARY KEY,
+  label VARCHAR(128) NOT NULL,
+  created_by UUID REFERENCES users (user_id) ON DELETE SET NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  expires_at TIMESTAMPTZ,
+  revoked_at TIMESTAMPTZ
+);
+
+ALTER TABLE otp ADD COLUMN batch_id UUID REFERENCES otp_batches (batch_id) ON DELETE CASCADE;
+ALTER TABLE otp ADD COLUMN expires_at TIMESTAMPTZ;
+
+CREATE INDEX otp_batch_id_idx ON otp (batch_id);
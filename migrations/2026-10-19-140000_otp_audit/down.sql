@@ -0,0 +1,10 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX otp_user_idx;
+DROP INDEX otp_code_type_idx;
+
+DELETE FROM otp WHERE redeemed_at IS NOT NULL;
+
+ALTER TABLE otp DROP COLUMN redeemed_at;
+ALTER TABLE otp DROP COLUMN redeemed_by;
+ALTER TABLE otp DROP COLUMN created_at;
+ALTER TABLE otp DROP COLUMN created_by;
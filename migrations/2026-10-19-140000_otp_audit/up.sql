@@ -0,0 +1,9 @@
+-- Your SQL goes here
+ALTER TABLE otp ADD COLUMN created_by UUID REFERENCES users (user_id) ON DELETE SET NULL;
+ALTER TABLE otp ADD COLUMN created_at TIMESTAMPTZ NOT NULL DEFAULT now();
+-- redeemed codes are kept as history instead of being deleted
+ALTER TABLE otp ADD COLUMN redeemed_by UUID REFERENCES users (user_id) ON DELETE SET NULL;
+ALTER TABLE otp ADD COLUMN redeemed_at TIMESTAMPTZ;
+
+CREATE INDEX otp_code_type_idx ON otp (code_type);
+CREATE INDEX otp_user_idx ON otp ("user");
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX users_username_lower_idx;
+DROP INDEX users_created_at_idx;
+
+ALTER TABLE users DROP COLUMN created_at;
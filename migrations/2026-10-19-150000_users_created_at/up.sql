@@ -0,0 +1,6 @@
+-- Your SQL goes here
+-- existing accounts get the time of the migration, their real creation date is unknown
+ALTER TABLE users ADD COLUMN created_at TIMESTAMPTZ NOT NULL DEFAULT now();
+
+CREATE INDEX users_created_at_idx ON users (created_at);
+CREATE INDEX users_username_lower_idx ON users (lower(username) text_pattern_ops);
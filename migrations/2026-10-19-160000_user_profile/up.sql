@@ -0,0 +1,6 @@
+-- Your SQL goes here
+ALTER TABLE users ADD COLUMN display_name VARCHAR(128);
+-- BCP 47 language tag, e.g. `de-AT`
+ALTER TABLE users ADD COLUMN locale VARCHAR(35);
+-- application specific attributes, validated against USER_ATTRIBUTES_SCHEMA
+ALTER TABLE users ADD COLUMN attributes JSONB NOT NULL DEFAULT '{}';
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+-- an email address can only belong to a single account, regardless of its casing
+CREATE UNIQUE INDEX users_email_lower_key ON users (lower(email));
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE username_history;
+DROP TABLE audit_events;
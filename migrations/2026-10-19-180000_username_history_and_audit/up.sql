@@ -0,0 +1,27 @@
+-- Your SQL goes here
+CREATE TABLE audit_events (
+  id BIGSERIAL PRIMARY KEY,
+  -- user that performed the action, NULL for the system or once the user has been deleted
+  actor UUID REFERENCES users (user_id) ON DELETE SET NULL,
+  -- user the action has been performed on
+  subject UUID REFERENCES users (user_id) ON DELETE SET NULL,
+  action VARCHAR(64) NOT NULL,
+  details JSONB NOT NULL DEFAULT '{}',
+  created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX audit_events_subject_idx ON audit_events (subject);
+
+-- released usernames, they stay reserved for the previous owner until reserved_until
+CREATE TABLE username_history (
+  id SERIAL PRIMARY KEY,
+  user_id UUID REFERENCES users (user_id) ON DELETE SET NULL,
+  old_username VARCHAR(64) NOT NULL,
+  new_username VARCHAR(64) NOT NULL,
+  changed_by UUID REFERENCES users (user_id) ON DELETE SET NULL,
+  changed_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  reserved_until TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX username_history_old_username_idx ON username_history (old_username);
+CREATE INDEX username_history_user_id_idx ON username_history (user_id);
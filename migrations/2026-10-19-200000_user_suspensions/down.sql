@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX users_blocked_until_idx;
+
+ALTER TABLE users DROP COLUMN blocked_by;
+ALTER TABLE users DROP COLUMN blocked_until;
+ALTER TABLE users DROP COLUMN blocked_reason;
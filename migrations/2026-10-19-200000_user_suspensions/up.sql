@@ -0,0 +1,6 @@
+-- Your SQL goes here
+ALTER TABLE users ADD COLUMN blocked_reason VARCHAR;
+ALTER TABLE users ADD COLUMN blocked_until TIMESTAMPTZ;
+ALTER TABLE users ADD COLUMN blocked_by UUID REFERENCES users (user_id) ON DELETE SET NULL;
+
+CREATE INDEX users_blocked_until_idx ON users (blocked_until) WHERE blocked_until IS NOT NULL;
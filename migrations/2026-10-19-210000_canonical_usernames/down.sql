@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX users_username_canonical_pattern_idx;
+CREATE INDEX users_username_lower_idx ON users (lower(username) text_pattern_ops);
+
+DROP INDEX users_username_canonical_key;
+ALTER TABLE users DROP COLUMN username_canonical;
@@ -0,0 +1,24 @@
+-- Your SQL goes here
+ALTER TABLE users ADD COLUMN username_canonical VARCHAR(64);
+
+-- approximates the canonical form, the server recomputes it with Unicode normalization and case folding on startup
+UPDATE users SET username_canonical = lower(username);
+
+DO $$
+DECLARE
+  conflicts TEXT;
+BEGIN
+  SELECT string_agg(names, '; ') INTO conflicts FROM (
+    SELECT string_agg(username, ', ') AS names FROM users GROUP BY username_canonical HAVING count(*) > 1
+  ) duplicates;
+
+  IF conflicts IS NOT NULL THEN
+    RAISE EXCEPTION 'These usernames only differ in case or Unicode spelling, rename all but one of each group first: %', conflicts;
+  END IF;
+END $$;
+
+ALTER TABLE users ALTER COLUMN username_canonical SET NOT NULL;
+CREATE UNIQUE INDEX users_username_canonical_key ON users (username_canonical);
+
+DROP INDEX users_username_lower_idx;
+CREATE INDEX users_username_canonical_pattern_idx ON users (username_canonical text_pattern_ops);
@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX users_last_login_at_idx;
+DROP TRIGGER set_updated_at ON users;
+
+ALTER TABLE users DROP COLUMN last_active_at;
+ALTER TABLE users DROP COLUMN last_login_at;
+ALTER TABLE users DROP COLUMN updated_at;
@@ -0,0 +1,8 @@
+-- Your SQL goes here
+ALTER TABLE users ADD COLUMN updated_at TIMESTAMPTZ NOT NULL DEFAULT now();
+ALTER TABLE users ADD COLUMN last_login_at TIMESTAMPTZ;
+ALTER TABLE users ADD COLUMN last_active_at TIMESTAMPTZ;
+
+SELECT diesel_manage_updated_at('users');
+
+CREATE INDEX users_last_login_at_idx ON users (last_login_at);
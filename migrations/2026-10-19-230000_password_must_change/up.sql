@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE users ADD COLUMN password_must_change BOOL NOT NULL DEFAULT false;
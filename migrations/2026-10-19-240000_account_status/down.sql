@@ -0,0 +1,10 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX users_status_idx;
+
+ALTER TABLE users ALTER COLUMN admin DROP NOT NULL;
+
+ALTER TABLE users ADD COLUMN blocked bool DEFAULT false;
+UPDATE users SET blocked = (status = 'SUSPENDED');
+
+ALTER TABLE users DROP COLUMN status;
+DROP TYPE account_status;
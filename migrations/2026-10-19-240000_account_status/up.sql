@@ -0,0 +1,24 @@
+-- Your SQL goes here
+CREATE TYPE account_status AS ENUM ('PENDING_APPROVAL', 'ACTIVE', 'SUSPENDED', 'LOCKED', 'DELETED');
+
+ALTER TABLE users ADD COLUMN status account_status NOT NULL DEFAULT 'ACTIVE';
+
+-- a deletion outranks an admin block, which outranks a lockout due to failed logins
+UPDATE users SET status = CASE
+  WHEN deleted_at IS NOT NULL THEN 'DELETED'
+  WHEN blocked AND (blocked_until IS NULL OR blocked_until > now()) THEN 'SUSPENDED'
+  WHEN locked_at IS NOT NULL THEN 'LOCKED'
+  ELSE 'ACTIVE'
+END::account_status;
+
+-- suspensions that have run out leave no details behind, a suspension supersedes a lockout
+UPDATE users SET blocked_reason = NULL, blocked_until = NULL, blocked_by = NULL WHERE status IN ('ACTIVE', 'LOCKED');
+UPDATE users SET failed_login_attempts = 0, locked_at = NULL WHERE status = 'SUSPENDED';
+
+ALTER TABLE users DROP COLUMN blocked;
+
+UPDATE users SET admin = false WHERE admin IS NULL;
+ALTER TABLE users ALTER COLUMN admin SET DEFAULT false;
+ALTER TABLE users ALTER COLUMN admin SET NOT NULL;
+
+CREATE INDEX users_status_idx ON users (status);
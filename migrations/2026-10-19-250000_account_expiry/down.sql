@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX users_expires_at_idx;
+
+ALTER TABLE users DROP COLUMN expiry_processed_at;
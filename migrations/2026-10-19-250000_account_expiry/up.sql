@@ -0,0 +1,4 @@
+-- Your SQL goes here
+ALTER TABLE users ADD COLUMN expiry_processed_at TIMESTAMPTZ;
+
+CREATE INDEX users_expires_at_idx ON users (expires_at) WHERE expires_at IS NOT NULL;
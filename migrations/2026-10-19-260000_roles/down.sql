@@ -0,0 +1,11 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER IF EXISTS user_roles_admin_changed ON user_roles;
+DROP TRIGGER IF EXISTS users_admin_updated ON users;
+DROP TRIGGER IF EXISTS users_admin_inserted ON users;
+DROP FUNCTION IF EXISTS sync_admin_flag_from_role();
+DROP FUNCTION IF EXISTS sync_admin_role_from_flag();
+
+DROP TABLE IF EXISTS user_roles;
+DROP TABLE IF EXISTS role_permissions;
+DROP TABLE IF EXISTS roles;
+DROP TABLE IF EXISTS permissions;
@@ -0,0 +1,94 @@
+-- Your SQL goes here
+CREATE TABLE permissions (
+  name TEXT NOT NULL PRIMARY KEY,
+  description TEXT NOT NULL
+);
+
+INSERT INTO permissions (name, description) VALUES
+  ('users.read', 'View users, pending registrations, expiring accounts and username histories'),
+  ('users.update', 'Edit the profiles of users'),
+  ('users.lock', 'Lock, suspend and unlock users'),
+  ('users.delete', 'Delete and restore users'),
+  ('users.approve', 'Approve and reject registrations'),
+  ('users.password', 'Reset the passwords of users'),
+  ('users.sessions', 'End the sessions of users'),
+  ('roles.read', 'View roles and the roles of users'),
+  ('roles.manage', 'Create, edit and delete roles'),
+  ('roles.assign', 'Grant and revoke roles, including the admin role'),
+  ('otp.read', 'View codes and code batches'),
+  ('otp.create', 'Create codes and code batches'),
+  ('otp.revoke', 'Delete codes and revoke code batches');
+
+CREATE TABLE roles (
+  role_id UUID NOT NULL PRIMARY KEY DEFAULT gen_random_uuid(),
+  name TEXT NOT NULL UNIQUE,
+  description TEXT,
+  -- system roles are maintained by migrations and cannot be edited or deleted
+  system BOOLEAN NOT NULL DEFAULT false,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE role_permissions (
+  role_id UUID NOT NULL REFERENCES roles (role_id) ON DELETE CASCADE,
+  permission TEXT NOT NULL REFERENCES permissions (name) ON DELETE CASCADE,
+  PRIMARY KEY (role_id, permission)
+);
+
+CREATE TABLE user_roles (
+  user_id UUID NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+  role_id UUID NOT NULL REFERENCES roles (role_id) ON DELETE CASCADE,
+  granted_by UUID REFERENCES users (user_id) ON DELETE SET NULL,
+  granted_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (user_id, role_id)
+);
+
+CREATE INDEX user_roles_role_id_idx ON user_roles (role_id);
+
+-- the admin role holds every permission, permissions added later have to be granted to it by their migration
+INSERT INTO roles (name, description, system) VALUES ('admin', 'Full access to the administration of users and codes', true);
+
+INSERT INTO role_permissions (role_id, permission)
+SELECT roles.role_id, permissions.name FROM roles CROSS JOIN permissions WHERE roles.name = 'admin';
+
+INSERT INTO user_roles (user_id, role_id)
+SELECT users.user_id, roles.role_id FROM users CROSS JOIN roles WHERE users.admin AND roles.name = 'admin';
+
+-- `users.admin` stays as flag of holding the admin role, the last admin safeguard and the registration codes rely on it.
+-- Both sides are kept in sync, each trigger only writes if the other side differs, so they do not recurse.
+CREATE FUNCTION sync_admin_role_from_flag() RETURNS TRIGGER AS $$
+BEGIN
+  IF NEW.admin THEN
+    INSERT INTO user_roles (user_id, role_id)
+    SELECT NEW.user_id, role_id FROM roles WHERE name = 'admin' AND system
+    ON CONFLICT DO NOTHING;
+  ELSE
+    DELETE FROM user_roles
+    WHERE user_id = NEW.user_id AND role_id IN (SELECT role_id FROM roles WHERE name = 'admin' AND system);
+  END IF;
+  RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER users_admin_inserted AFTER INSERT ON users
+FOR EACH ROW WHEN (NEW.admin) EXECUTE FUNCTION sync_admin_role_from_flag();
+
+CREATE TRIGGER users_admin_updated AFTER UPDATE OF admin ON users
+FOR EACH ROW WHEN (OLD.admin IS DISTINCT FROM NEW.admin) EXECUTE FUNCTION sync_admin_role_from_flag();
+
+CREATE FUNCTION sync_admin_flag_from_role() RETURNS TRIGGER AS $$
+BEGIN
+  IF TG_OP = 'INSERT' THEN
+    UPDATE users SET admin = true
+    WHERE user_id = NEW.user_id AND NOT admin
+      AND NEW.role_id IN (SELECT role_id FROM roles WHERE name = 'admin' AND system);
+  ELSE
+    UPDATE users SET admin = false
+    WHERE user_id = OLD.user_id AND admin
+      AND OLD.role_id IN (SELECT role_id FROM roles WHERE name = 'admin' AND system);
+  END IF;
+  RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER user_roles_admin_changed AFTER INSERT OR DELETE ON user_roles
+FOR EACH ROW EXECUTE FUNCTION sync_admin_flag_from_role();
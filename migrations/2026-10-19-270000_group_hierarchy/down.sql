@@ -0,0 +1,14 @@
+-- This file should undo anything in `up.sql`
+DELETE FROM permissions WHERE name IN ('groups.read', 'groups.manage', 'groups.members');
+
+DROP TABLE IF EXISTS group_roles;
+
+DROP INDEX IF EXISTS group_members_user_id_idx;
+ALTER TABLE group_members DROP COLUMN added_at;
+ALTER TABLE group_members DROP COLUMN added_by;
+
+DROP INDEX IF EXISTS groups_parent_id_idx;
+ALTER TABLE groups DROP CONSTRAINT groups_parent_not_self;
+ALTER TABLE groups DROP COLUMN created_at;
+ALTER TABLE groups DROP COLUMN parent_id;
+ALTER TABLE groups DROP COLUMN description;
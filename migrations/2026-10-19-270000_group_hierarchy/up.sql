@@ -0,0 +1,33 @@
+-- Your SQL goes here
+-- groups nest below a parent group, members of a group count as members of all groups above it
+ALTER TABLE groups ADD COLUMN description TEXT;
+ALTER TABLE groups ADD COLUMN parent_id UUID REFERENCES groups (group_id);
+ALTER TABLE groups ADD COLUMN created_at TIMESTAMPTZ NOT NULL DEFAULT NOW();
+ALTER TABLE groups ADD CONSTRAINT groups_parent_not_self CHECK (parent_id <> group_id);
+
+CREATE INDEX groups_parent_id_idx ON groups (parent_id);
+
+ALTER TABLE group_members ADD COLUMN added_by UUID REFERENCES users (user_id) ON DELETE SET NULL;
+ALTER TABLE group_members ADD COLUMN added_at TIMESTAMPTZ NOT NULL DEFAULT NOW();
+
+CREATE INDEX group_members_user_id_idx ON group_members (user_id);
+
+-- roles granted to a group apply to its members and the members of all groups below it
+CREATE TABLE group_roles (
+  group_id UUID NOT NULL REFERENCES groups (group_id) ON DELETE CASCADE,
+  role_id UUID NOT NULL REFERENCES roles (role_id) ON DELETE CASCADE,
+  granted_by UUID REFERENCES users (user_id) ON DELETE SET NULL,
+  granted_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (group_id, role_id)
+);
+
+CREATE INDEX group_roles_role_id_idx ON group_roles (role_id);
+
+INSERT INTO permissions (name, description) VALUES
+  ('groups.read', 'View groups, their members and the groups of users'),
+  ('groups.manage', 'Create, edit, nest and delete groups'),
+  ('groups.members', 'Add users to groups and remove them');
+
+INSERT INTO role_permissions (role_id, permission)
+SELECT role_id, permissions.name FROM roles CROSS JOIN permissions
+WHERE roles.name = 'admin' AND roles.system AND permissions.name LIKE 'groups.%';
@@ -0,0 +1,33 @@
+-- This file should undo anything in `up.sql`
+-- users of other tenants than the default one cannot be kept, their names would collide
+DELETE FROM users WHERE tenant_id <> (SELECT tenant_id FROM tenants WHERE slug = 'default');
+
+DELETE FROM roles WHERE name = 'superadmin' AND system;
+DELETE FROM permissions WHERE name IN ('tenants.read', 'tenants.manage');
+
+ALTER TABLE roles DROP CONSTRAINT roles_name_key;
+ALTER TABLE roles DROP CONSTRAINT roles_tenant_unless_system;
+ALTER TABLE roles DROP COLUMN tenant_id;
+ALTER TABLE roles ADD CONSTRAINT roles_name_key UNIQUE (name);
+
+ALTER TABLE username_history DROP COLUMN tenant_id;
+
+ALTER TABLE groups DROP CONSTRAINT groups_name_key;
+ALTER TABLE groups DROP COLUMN tenant_id;
+ALTER TABLE groups ADD CONSTRAINT groups_name_key UNIQUE (name);
+
+DROP INDEX IF EXISTS otp_batches_tenant_id_idx;
+ALTER TABLE otp_batches DROP COLUMN tenant_id;
+
+ALTER TABLE otp DROP CONSTRAINT otp_code_hash_key;
+ALTER TABLE otp DROP COLUMN tenant_id;
+ALTER TABLE otp ADD CONSTRAINT otp_code_hash_key UNIQUE (code_hash);
+
+DROP INDEX users_email_lower_key;
+DROP INDEX users_username_canonical_key;
+ALTER TABLE users DROP COLUMN tenant_id;
+CREATE UNIQUE INDEX users_email_lower_key ON users (lower(email));
+CREATE UNIQUE INDEX users_username_canonical_key ON users (username_canonical);
+ALTER TABLE users ADD CONSTRAINT users_username_key UNIQUE (username);
+
+DROP TABLE tenants;
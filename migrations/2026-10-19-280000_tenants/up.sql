@@ -0,0 +1,68 @@
+-- Your SQL goes here
+-- every user, code, group and custom role belongs to a tenant, requests naming no tenant are served by the `default` one
+CREATE TABLE tenants (
+  tenant_id UUID NOT NULL PRIMARY KEY DEFAULT gen_random_uuid(),
+  slug VARCHAR(64) NOT NULL UNIQUE,
+  name VARCHAR(128) NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+INSERT INTO tenants (slug, name) VALUES ('default', 'Default');
+
+-- existing data moves into the default tenant
+ALTER TABLE users ADD COLUMN tenant_id UUID REFERENCES tenants (tenant_id);
+UPDATE users SET tenant_id = (SELECT tenant_id FROM tenants WHERE slug = 'default');
+ALTER TABLE users ALTER COLUMN tenant_id SET NOT NULL;
+
+-- usernames and email addresses are only unique within a tenant
+ALTER TABLE users DROP CONSTRAINT users_username_key;
+DROP INDEX users_username_canonical_key;
+CREATE UNIQUE INDEX users_username_canonical_key ON users (tenant_id, username_canonical);
+DROP INDEX users_email_lower_key;
+CREATE UNIQUE INDEX users_email_lower_key ON users (tenant_id, lower(email));
+
+-- the remaining tables go away with their tenant, a tenant can only be deleted once it has no users left
+ALTER TABLE otp ADD COLUMN tenant_id UUID REFERENCES tenants (tenant_id) ON DELETE CASCADE;
+UPDATE otp SET tenant_id = (SELECT tenant_id FROM tenants WHERE slug = 'default');
+ALTER TABLE otp ALTER COLUMN tenant_id SET NOT NULL;
+ALTER TABLE otp DROP CONSTRAINT otp_code_hash_key;
+ALTER TABLE otp ADD CONSTRAINT otp_code_hash_key UNIQUE (tenant_id, code_hash);
+
+ALTER TABLE otp_batches ADD COLUMN tenant_id UUID REFERENCES tenants (tenant_id) ON DELETE CASCADE;
+UPDATE otp_batches SET tenant_id = (SELECT tenant_id FROM tenants WHERE slug = 'default');
+ALTER TABLE otp_batches ALTER COLUMN tenant_id SET NOT NULL;
+CREATE INDEX otp_batches_tenant_id_idx ON otp_batches (tenant_id);
+
+ALTER TABLE groups ADD COLUMN tenant_id UUID REFERENCES tenants (tenant_id) ON DELETE CASCADE;
+UPDATE groups SET tenant_id = (SELECT tenant_id FROM tenants WHERE slug = 'default');
+ALTER TABLE groups ALTER COLUMN tenant_id SET NOT NULL;
+ALTER TABLE groups DROP CONSTRAINT groups_name_key;
+ALTER TABLE groups ADD CONSTRAINT groups_name_key UNIQUE (tenant_id, name);
+
+ALTER TABLE username_history ADD COLUMN tenant_id UUID REFERENCES tenants (tenant_id) ON DELETE CASCADE;
+UPDATE username_history SET tenant_id = COALESCE(
+  (SELECT tenant_id FROM users WHERE users.user_id = username_history.user_id),
+  (SELECT tenant_id FROM tenants WHERE slug = 'default')
+);
+ALTER TABLE username_history ALTER COLUMN tenant_id SET NOT NULL;
+
+-- system roles are shared by all tenants, custom roles belong to the tenant they have been created in
+ALTER TABLE roles ADD COLUMN tenant_id UUID REFERENCES tenants (tenant_id) ON DELETE CASCADE;
+UPDATE roles SET tenant_id = (SELECT tenant_id FROM tenants WHERE slug = 'default') WHERE NOT system;
+ALTER TABLE roles ADD CONSTRAINT roles_tenant_unless_system CHECK (system = (tenant_id IS NULL));
+ALTER TABLE roles DROP CONSTRAINT roles_name_key;
+ALTER TABLE roles ADD CONSTRAINT roles_name_key UNIQUE NULLS NOT DISTINCT (tenant_id, name);
+
+INSERT INTO permissions (name, description) VALUES
+  ('tenants.read', 'View tenants'),
+  ('tenants.manage', 'Create, rename and delete tenants and invite their first admin');
+
+-- the admin of a tenant administers their own tenant only, the permissions for tenants are left to super-admins
+INSERT INTO roles (name, description, system) VALUES ('superadmin', 'Full access to all tenants, including the management of tenants', true);
+
+INSERT INTO role_permissions (role_id, permission)
+SELECT roles.role_id, permissions.name FROM roles CROSS JOIN permissions WHERE roles.name = 'superadmin' AND roles.system;
+
+-- the admins of the single tenant so far administered everything, they keep doing so
+INSERT INTO user_roles (user_id, role_id)
+SELECT users.user_id, roles.role_id FROM users CROSS JOIN roles WHERE users.admin AND roles.name = 'superadmin' AND roles.system;
@@ -0,0 +1,4 @@
+-- Your SQL goes here
+-- a lockout due to failed logins ends by itself, so failed logins of others cannot keep an account locked for good
+ALTER TABLE users ADD COLUMN locked_until TIMESTAMPTZ;
+UPDATE users SET locked_until = COALESCE(locked_at, NOW()) + INTERVAL '15 minutes' WHERE status = 'LOCKED';
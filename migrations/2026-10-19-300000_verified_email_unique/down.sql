@@ -0,0 +1,14 @@
+-- This file should undo anything in `up.sql`
+-- unverified duplicates of an address cannot be kept
+UPDATE users SET email = NULL
+  WHERE email_verified_at IS NULL
+  AND EXISTS (
+    SELECT 1 FROM users other
+    WHERE other.tenant_id = users.tenant_id
+    AND lower(other.email) = lower(users.email)
+    AND other.user_id <> users.user_id
+    AND (other.email_verified_at IS NOT NULL OR (other.created_at, other.user_id) < (users.created_at, users.user_id))
+  );
+
+DROP INDEX users_email_lower_key;
+CREATE UNIQUE INDEX users_email_lower_key ON users (tenant_id, lower(email));
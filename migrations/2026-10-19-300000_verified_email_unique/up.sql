@@ -0,0 +1,4 @@
+-- Your SQL goes here
+-- only a verified address is claimed, so nobody can block the address of someone else by entering it first
+DROP INDEX users_email_lower_key;
+CREATE UNIQUE INDEX users_email_lower_key ON users (tenant_id, lower(email)) WHERE email_verified_at IS NOT NULL;
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+UPDATE roles SET description = 'Full access to all tenants, including the management of tenants'
+  WHERE name = 'superadmin' AND system;
@@ -0,0 +1,4 @@
+-- Your SQL goes here
+-- super-admins manage tenants, but like every other user they only act within the tenant they belong to
+UPDATE roles SET description = 'All permissions within its tenant, including the management of tenants'
+  WHERE name = 'superadmin' AND system;
@@ -0,0 +1,48 @@
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use diesel_async::AsyncPgConnection;
+use uuid::Uuid;
+
+use crate::models::audit::{AuditEvent, NewAuditEvent};
+use crate::utils::error::Fault;
+
+type Conn = AsyncPgConnection;
+
+/// Records an audit event, should be called inside the transaction of the audited change
+pub async fn i_audit_event(connection: &mut Conn, event: NewAuditEvent) -> Result<(), Fault> {
+  use crate::schema::audit_events;
+
+  diesel::insert_into(audit_events::table)
+    .values(event)
+    .execute(connection)
+    .await
+    .map_err(|_| Fault::Diesel)
+    .map(|_| ())
+}
+
+/// The latest `limit` audit events about a user, newest first
+pub async fn q_recent_audit_events_about(connection: &mut Conn, user: Uuid, limit: i64) -> Result<Vec<AuditEvent>, Fault> {
+  use crate::schema::audit_events::dsl::*;
+
+  audit_events
+    .filter(subject.eq(user))
+    .order(id.desc())
+    .limit(limit)
+    .select(AuditEvent::as_select())
+    .load::<AuditEvent>(connection)
+    .await
+    .map_err(|_| Fault::Diesel)
+}
+
+/// Audit events about a user, oldest first
+pub async fn q_audit_events_about(connection: &mut Conn, user: Uuid) -> Result<Vec<AuditEvent>, Fault> {
+  use crate::schema::audit_events::dsl::*;
+
+  audit_events
+    .filter(subject.eq(user))
+    .order(id.asc())
+    .select(AuditEvent::as_select())
+    .load::<AuditEvent>(connection)
+    .await
+    .map_err(|_| Fault::Diesel)
+}
@@ -0,0 +1,110 @@
+use chrono::{Duration, Utc};
+use diesel_async::AsyncPgConnection;
+use uuid::Uuid;
+
+use crate::{
+  api::otp::{routes::insert_generated_otp, queries::d_unredeemed_codes_of_user},
+  models::{otp::{InsertableOtp, OtpEnum}, user::User},
+  state::{AppState, config::{AppConfig, EmailVerificationRequirement}, mailer::WrappedMailer},
+  utils::error::Fault,
+};
+
+/// Fails with `Fault::EmailNotVerified` if the configuration requires a verified address for sensitive actions
+pub fn ensure_verified_email(user: &User, config: &AppConfig) -> Result<(), Fault> {
+  if config.email.requirement != EmailVerificationRequirement::Off && user.email_verified_at.is_none() {
+    return Err(Fault::EmailNotVerified);
+  }
+
+  Ok(())
+}
+
+/// Issues an `EMAIL_VERIFY` code for `email` in the tenant of the user, superseding all codes the user has not redeemed yet.
+/// Returns the code in its display format. Should be called inside the transaction that sets the address.
+pub async fn issue_email_code(
+  connection: &mut AsyncPgConnection,
+  config: &AppConfig,
+  tenant: Uuid,
+  user: Uuid,
+  email: &str,
+  created_by: Option<Uuid>,
+) -> Result<String, Fault> {
+  d_unredeemed_codes_of_user(connection, user, OtpEnum::EMAILVERIFY).await?;
+
+  let template = InsertableOtp {
+    code_hash: String::new(),
+    code_prefix: String::new(),
+    tenant_id: tenant,
+    user: Some(user),
+    code_type: OtpEnum::EMAILVERIFY,
+    grant_admin: false,
+    restricted_to: None,
+    groups: vec![],
+    account_expires_at: None,
+    email: Some(email.to_owned()),
+    batch_id: None,
+    expires_at: Some(Utc::now() + Duration::minutes(config.email.verify_code_ttl)),
+    created_by,
+  };
+
+  let (_, code) = insert_generated_otp(connection, &config.otp, &template).await?;
+  Ok(code)
+}
+
+/// Issues an `UNLOCK` code for a user that just got locked, valid as long as the lockout lasts.
+/// Supersedes all unlock codes the user has not redeemed yet.
+pub async fn issue_unlock_code(connection: &mut AsyncPgConnection, config: &AppConfig, user: &User) -> Result<String, Fault> {
+  d_unredeemed_codes_of_user(connection, user.user_id, OtpEnum::UNLOCK).await?;
+
+  let template = InsertableOtp {
+    code_hash: String::new(),
+    code_prefix: String::new(),
+    tenant_id: user.tenant_id,
+    user: Some(user.user_id),
+    code_type: OtpEnum::UNLOCK,
+    grant_admin: false,
+    restricted_to: None,
+    groups: vec![],
+    account_expires_at: None,
+    email: None,
+    batch_id: None,
+    expires_at: Some(Utc::now() + Duration::minutes(config.auth.lockout_minutes)),
+    created_by: None,
+  };
+
+  let (_, code) = insert_generated_otp(connection, &config.otp, &template).await?;
+  Ok(code)
+}
+
+/// Tells a user about a lockout and mails them a code to lift it right away.
+/// A failed delivery is only logged, the lockout runs out by itself.
+pub async fn try_send_unlock_code(state: &AppState, email: &str, code: &str) {
+  let body = format!(
+    "Your account has been locked for {} minutes after too many failed login attempts.\n\nIf that was you, unlock it right away with this code: {code}\n\nIf it was not you, consider changing your password once you are logged in again.\n",
+    state.config.auth.lockout_minutes,
+  );
+
+  if state.mailer.send(email, "Your account has been locked", body).await.is_err() {
+    println!("Failed to send an unlock code to {email}");
+  }
+}
+
+/// Mails a verification code, together with a link if `EMAIL_VERIFY_LINK` is configured
+pub async fn send_email_code(mailer: &WrappedMailer, config: &AppConfig, email: &str, code: &str) -> Result<(), Fault> {
+  let mut body = format!("Your verification code is: {code}\n");
+
+  if let Some(link) = &config.email.verify_link {
+    body.push_str(&format!("\nOr verify your address by opening this link:\n{}\n", link.replace("{code}", code)));
+  }
+
+  body.push_str(&format!("\nThe code is valid for {} minutes. If you did not request it, you can ignore this email.\n", config.email.verify_code_ttl));
+
+  mailer.send(email, "Verify your email address", body).await
+}
+
+/// Sends a code whose address has already been stored. A failed delivery is only logged,
+/// the user can request a new code.
+pub async fn try_send_email_code(state: &AppState, email: &str, code: &str) {
+  if send_email_code(&state.mailer, &state.config, email, code).await.is_err() {
+    println!("Failed to send an email verification code to {email}");
+  }
+}
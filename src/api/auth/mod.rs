@@ -1,4 +1,5 @@
-pub mod auth;
+pub mod routes;
 pub mod session;
 pub mod password;
 pub mod queries;
+pub mod email;
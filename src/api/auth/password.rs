@@ -1,7 +1,20 @@
+use rand::{distr::Alphanumeric, Rng};
+
+/// Length of temporary passwords handed out by admins
+const TEMPORARY_PASSWORD_LENGTH: usize = 16;
+
 pub fn hash_password(password: String) -> Result<String, String> {
   let hash_result = bcrypt::hash(password, 10);
   match hash_result {
     Ok(result) => Ok(result),
     Err(_) => Err("Could not generate hash from password".to_string())
   }
-}
\ No newline at end of file
+}
+/// Random password an admin hands to a user, who has to change it after logging in
+pub fn generate_temporary_password() -> String {
+  rand::rng()
+    .sample_iter(&Alphanumeric)
+    .take(TEMPORARY_PASSWORD_LENGTH)
+    .map(char::from)
+    .collect()
+}
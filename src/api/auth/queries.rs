@@ -1,38 +1,33 @@
-use bb8::PooledConnection;
-use diesel::{ExpressionMethods, SelectableHelper};
-use diesel::associations::HasTable;
+use chrono::{DateTime, Duration, Utc};
+use diesel::{BoolExpressionMethods, ExpressionMethods, SelectableHelper};
 use diesel::dsl::count_star;
 use diesel::query_dsl::methods::{FilterDsl,SelectDsl};
 use diesel::update;
+use diesel::result::{DatabaseErrorKind, DatabaseErrorInformation, Error::DatabaseError};
 use diesel_async::RunQueryDsl;
-use diesel_async::{pooled_connection::AsyncDieselConnectionManager, AsyncPgConnection};
+use diesel_async::AsyncPgConnection;
 use uuid::Uuid;
 
-use crate::models::user::{User, NewUser, UserInfo};
-use crate::utils::error::Fault;
+use crate::models::user::{AccountStatus, User, NewUser};
+use crate::utils::{error::Fault, sql::lower_nullable, username::canonical_username};
 
-type Conn<'a> = PooledConnection<'a, AsyncDieselConnectionManager<AsyncPgConnection>>;
+type Conn = AsyncPgConnection;
 
-pub async fn q_get_all_users(connection: &mut Conn<'_>) -> Result<Vec<UserInfo>, Fault> {
-  use crate::schema::users::dsl::*;
-
-  let res = users::table()
-    .load::<User>( connection)
-    .await
-    .or_else(|_| Err(Fault::DatabaseConnection))?;
-
-  let user_info_mapped = res.into_iter()
-    .map(|u| UserInfo::from(u))
-    .collect::<Vec<UserInfo>>();
-
-  Ok(user_info_mapped)
+/// Tells which unique constraint of `users` has been violated
+pub fn user_conflict(info: &(dyn DatabaseErrorInformation + Send + Sync)) -> Fault {
+  match info.constraint_name() {
+    Some("users_email_lower_key") => Fault::AlreadyExists(String::from("Email")),
+    _ => Fault::AlreadyExists(String::from("User")),
+  }
 }
 
-pub async fn q_does_user_exist(connection: &mut Conn<'_>, _username: &String) -> Result<(), ()> {
+/// Compares canonical names, deleted users are counted as well, their names stay taken until they are purged
+pub async fn q_does_user_exist(connection: &mut Conn, tenant: Uuid, _username: &String) -> Result<(), ()> {
   use crate::schema::users::dsl::*;
 
   let results: i64 = users
-    .filter(username.eq(_username))
+    .filter(tenant_id.eq(tenant))
+    .filter(username_canonical.eq(canonical_username(_username)))
     .select(count_star())
     .first(connection)
     .await.ok().unwrap();
@@ -43,51 +38,180 @@ pub async fn q_does_user_exist(connection: &mut Conn<'_>, _username: &String) ->
   Err(())
 }
 
-pub async fn q_insert_user(connection: &mut Conn<'_>, to_insert: NewUser<'_>) -> Result<(), Fault> {
+pub async fn q_insert_user(connection: &mut Conn, to_insert: NewUser<'_>) -> Result<(), Fault> {
   use crate::schema::users;
 
   diesel::insert_into(users::table)
     .values(to_insert)
     .execute(connection)
     .await
-    .or_else(|_| Err(Fault::Diesel))
-    .and_then(|_| Ok(()))
+    .map_err(|diesel_error| {
+      match diesel_error {
+        DatabaseError(DatabaseErrorKind::UniqueViolation, info) => user_conflict(info.as_ref()),
+        _ => Fault::Diesel
+      }
+    })
+    .map(|_| ())
 }
 
-pub async fn q_get_user_by_name(connection: &mut Conn<'_>, _username: &String) -> Result<User, Fault> {
+/// Looks up a user by any spelling of their name that has the same canonical form
+pub async fn q_get_user_by_name(connection: &mut Conn, tenant: Uuid, _username: &String) -> Result<User, Fault> {
   use crate::schema::users::dsl::*;
 
   users
-    .filter(username.eq(_username))
+    .filter(tenant_id.eq(tenant))
+    .filter(username_canonical.eq(canonical_username(_username)))
+    .filter(status.ne(AccountStatus::Deleted))
     .select(User::as_select())
     .first::<User>(connection)
     .await
-    .or_else(|_| Err(Fault::NotFound(String::from("User"))))
+    .map_err(|_| Fault::NotFound(String::from("User")))
 }
 
-pub async fn q_get_user_by_id(connection: &mut Conn<'_>, _user_id: Uuid) -> Result<User, Fault> {
+/// Looks up a user of a tenant by an email address that has been verified, ignoring its casing
+pub async fn q_get_user_by_verified_email(connection: &mut Conn, tenant: Uuid, address: &str) -> Result<User, Fault> {
+  use crate::schema::users::dsl::*;
+
+  users
+    .filter(tenant_id.eq(tenant))
+    .filter(lower_nullable(email).eq(address.to_lowercase()))
+    .filter(email_verified_at.is_not_null())
+    .filter(status.ne(AccountStatus::Deleted))
+    .select(User::as_select())
+    .first::<User>(connection)
+    .await
+    .map_err(|_| Fault::NotFound(String::from("User")))
+}
+
+pub async fn q_get_user_by_id(connection: &mut Conn, _user_id: Uuid) -> Result<User, Fault> {
   use crate::schema::users::dsl::*;
 
   users
     .filter(user_id.eq(_user_id))
+    .filter(status.ne(AccountStatus::Deleted))
     .select(User::as_select())
     .first::<User>(connection)
     .await
-    .or_else(|_| Err(Fault::NotFound(String::from("User"))))
+    .map_err(|_| Fault::NotFound(String::from("User")))
 }
 
-pub async fn u_set_user_password(connection: &mut Conn<'_>, user: &User) -> Result<(), Fault> {
+/// Sets a password chosen by the user, which lifts an admin's demand to change it
+pub async fn u_set_user_password(connection: &mut Conn, user: &User) -> Result<(), Fault> {
   use crate::schema::users::dsl::*;
 
   let result: usize = update(users.filter(user_id.eq(user.user_id)))
-    .set(password.eq(user.password.to_string()))
+    .set((password.eq(user.password.to_string()), password_must_change.eq(false)))
+    .execute(connection)
+    .await
+    .map_err(|_| Fault::Diesel)?;
+
+  if result == 0 {
+    return Err(Fault::NotFound("user".to_owned()));
+  }
+
+  Ok(())
+}
+
+/// Counts a failed login and locks the account for `lockout` once `max_attempts` is reached.
+/// Once a lockout has run out, every further failed login locks the account again.
+/// Returns whether the account got locked.
+pub async fn u_register_failed_login(connection: &mut Conn, user_uuid: Uuid, max_attempts: i32, lockout: Duration) -> Result<bool, Fault> {
+  use crate::schema::users::dsl::*;
+
+  let attempts: i32 = update(users.filter(user_id.eq(user_uuid)))
+    .set(failed_login_attempts.eq(failed_login_attempts + 1))
+    .returning(failed_login_attempts)
+    .get_result(connection)
+    .await
+    .map_err(|_| Fault::Diesel)?;
+
+  if max_attempts == 0 || attempts < max_attempts {
+    return Ok(false);
+  }
+
+  // a suspension or lockout that has run out counts as active, even if it has not been lifted yet
+  let now = Utc::now();
+  let lockable = status.eq_any(AccountStatus::predecessors(AccountStatus::Locked))
+    .or(status.eq(AccountStatus::Suspended).and(blocked_until.le(now)))
+    .or(status.eq(AccountStatus::Locked).and(locked_until.le(now)));
+
+  let result: usize = update(users.filter(user_id.eq(user_uuid)).filter(lockable))
+    .set((
+      status.eq(AccountStatus::Locked),
+      locked_at.eq(now),
+      locked_until.eq(now + lockout),
+      blocked_reason.eq(None::<String>),
+      blocked_until.eq(None::<DateTime<Utc>>),
+      blocked_by.eq(None::<Uuid>),
+    ))
+    .execute(connection)
+    .await
+    .map_err(|_| Fault::Diesel)?;
+
+  Ok(result > 0)
+}
+
+pub async fn u_record_login(connection: &mut Conn, user_uuid: Uuid) -> Result<(), Fault> {
+  use crate::schema::users::dsl::*;
+
+  let now = Utc::now();
+  update(users.filter(user_id.eq(user_uuid)))
+    .set((last_login_at.eq(now), last_active_at.eq(now)))
     .execute(connection)
     .await
-    .or_else(|_| Err(Fault::Diesel))?;
+    .map_err(|_| Fault::Diesel)
+    .map(|_| ())
+}
+
+/// Marks the user as active, called when their tokens are refreshed
+pub async fn u_record_activity(connection: &mut Conn, user_uuid: Uuid) -> Result<(), Fault> {
+  use crate::schema::users::dsl::*;
+
+  update(users.filter(user_id.eq(user_uuid)))
+    .set(last_active_at.eq(Utc::now()))
+    .execute(connection)
+    .await
+    .map_err(|_| Fault::Diesel)
+    .map(|_| ())
+}
+
+/// Resets the failed logins after a successful login, which also lifts a lockout that has run out
+pub async fn u_reset_failed_logins(connection: &mut Conn, user_uuid: Uuid) -> Result<(), Fault> {
+  use crate::schema::users::dsl::*;
+
+  update(users.filter(user_id.eq(user_uuid)))
+    .set(failed_login_attempts.eq(0))
+    .execute(connection)
+    .await
+    .map_err(|_| Fault::Diesel)?;
+
+  update(users.filter(user_id.eq(user_uuid)).filter(status.eq(AccountStatus::Locked)).filter(locked_until.le(Utc::now())))
+    .set((
+      status.eq(AccountStatus::Active),
+      locked_at.eq(None::<DateTime<Utc>>),
+      locked_until.eq(None::<DateTime<Utc>>),
+    ))
+    .execute(connection)
+    .await
+    .map_err(|_| Fault::Diesel)
+    .map(|_| ())
+}
+
+pub async fn u_set_verified_email(connection: &mut Conn, user_uuid: Uuid, verified_email: &str) -> Result<(), Fault> {
+  use crate::schema::users::dsl::*;
+
+  let result: usize = update(users.filter(user_id.eq(user_uuid)).filter(status.ne(AccountStatus::Deleted)))
+    .set((email.eq(verified_email), email_verified_at.eq(Utc::now())))
+    .execute(connection)
+    .await
+    .map_err(|diesel_error| match diesel_error {
+      DatabaseError(DatabaseErrorKind::UniqueViolation, info) => user_conflict(info.as_ref()),
+      _ => Fault::Diesel,
+    })?;
 
   if result == 0 {
     return Err(Fault::NotFound("user".to_owned()));
   }
 
   Ok(())
-}
\ No newline at end of file
+}
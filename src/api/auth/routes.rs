@@ -0,0 +1,538 @@
+use std::str::FromStr;
+
+use axum::{
+  Router,
+  extract::{State,Json,Path,Extension},
+  routing::{get,post},
+  http::{StatusCode,HeaderMap,header},
+  response::IntoResponse,
+  middleware,
+  // debug_handler,
+};
+use chrono::Duration;
+use diesel_async::{AsyncConnection, AsyncPgConnection, scoped_futures::ScopedFutureExt};
+use serde::{Serialize,Deserialize};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{state::{AppState, config::{EmailVerificationRequirement, RegistrationMode}}, middleware::authorized::logged_in_guard, models::{audit::{AuditAction, NewAuditEvent}, group::GroupMembership, tenant::Tenant, user::{AccountStatus, NewUser, UserInfo, UserPatch, UserResponse}}, api::{auth::queries::{q_does_user_exist, q_get_user_by_name}, otp::queries::{q_lock_registration_code, q_lock_password_code, q_lock_email_code, q_lock_unlock_code, u_mark_code_redeemed}, audit::queries::i_audit_event, user::{queries::{u_approve_user, u_clear_lockout}, profile::update_profile, account::{collect_personal_data, delete_account}, username::ensure_username_available}, group::{routes::collect_memberships, queries::{i_ensure_groups, i_group_memberships}}}, utils::{error::Fault, otp_code::code_prefix, parser::get_authorization_as_uuid, username::{canonical_username, ensure_username_not_blocked, validate_username}, validation::is_valid_email}};
+use crate::api::auth::session::TokenPair;
+use crate::api::auth::password::hash_password;
+use crate::models::user::User;
+
+use super::email::{ensure_verified_email, issue_email_code, issue_unlock_code, send_email_code, try_send_email_code, try_send_unlock_code};
+use super::queries::{u_record_activity, u_record_login, q_get_user_by_verified_email, q_insert_user, u_set_user_password, q_get_user_by_id, u_register_failed_login, u_reset_failed_logins, u_set_verified_email};
+
+#[derive(Deserialize)]
+#[serde(rename_all="camelCase")]
+struct NewUserBody {
+  username: String,
+  password: String,
+  /// Required if `REGISTRATION_MODE` is `invite`, in the other modes a code applies its payload and skips the approval
+  registration_code: Option<String>,
+  /// Gets a verification code mailed to it, required if `REQUIRE_VERIFIED_EMAIL` is `login`
+  email: Option<String>,
+}
+
+#[derive(Serialize)]
+struct RegistrationResponse {
+  /// `PENDING_APPROVAL` if an admin has to approve the user, or in `Domain` mode they have to verify their email address, before they can log in
+  status: AccountStatus,
+}
+
+async fn add_user(
+  State(state): State<AppState>,
+  Extension(tenant): Extension<Tenant>,
+  Json(new_user): Json<NewUserBody>,
+) -> Result<(StatusCode, Json<RegistrationResponse>), Fault> {
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  let email = new_user.email.as_ref().map(|e| e.trim().to_owned());
+  if email.as_ref().is_some_and(|e| !is_valid_email(e)) {
+    return Err(Fault::EmailRequired);
+  }
+  if email.is_none() && state.config.email.requirement == EmailVerificationRequirement::Login {
+    return Err(Fault::EmailRequired);
+  }
+
+  let registration = &state.config.registration;
+  if new_user.registration_code.is_none() {
+    match registration.mode {
+      RegistrationMode::Invite => return Err(Fault::RegistrationCodeRequired),
+      RegistrationMode::Domain if !email.as_deref().is_some_and(|e| registration.is_allowed_email(e)) => return Err(Fault::EmailDomainNotAllowed),
+      _ => (),
+    }
+  }
+
+  validate_username(&new_user.username, &state.config.profile.username)?;
+  let username_canonical = canonical_username(&new_user.username);
+
+  let code_lookup = new_user.registration_code.as_ref().map(|code| (state.config.otp.hash_code(code), code_prefix(code)));
+  // without a code, a sign-up of `Domain` mode gets activated by verifying its address
+  let status = match (&code_lookup, registration.mode) {
+    (None, RegistrationMode::Approval | RegistrationMode::Domain) => AccountStatus::PendingApproval,
+    _ => AccountStatus::Active,
+  };
+  let hashed = hash_password(new_user.password)
+    .map_err(|_| Fault::Unexpected)?;
+  let config = state.config.clone();
+  let tenant_id = tenant.tenant_id;
+
+  // redeeming the code, creating the user and applying the payload of the code either succeed together or not at all
+  let email_code = connection.transaction::<_, Fault, _>(|connection| async move {
+    let does_exist = q_does_user_exist(connection, tenant_id, &new_user.username).await;
+
+    if does_exist.is_ok() {
+      return Err(Fault::AlreadyExists(String::from("User")));
+    }
+
+    ensure_username_available(connection, tenant_id, &new_user.username, None).await?;
+
+    let (otp_id, issuer, payload) = match &code_lookup {
+      Some((code_hash, prefix)) => {
+        let otp = q_lock_registration_code(connection, tenant_id, code_hash, prefix).await?;
+        (Some(otp.id), otp.created_by, otp.registration_payload().unwrap_or_default())
+      },
+      None => (None, None, Default::default()),
+    };
+
+    // a code restricted to the name has been handed out by an admin, who may hand out blocked names as well
+    match &payload.restricted_to {
+      Some(restricted) if canonical_username(restricted) != username_canonical => return Err(Fault::RegistrationCodeInvalid),
+      Some(_) => (),
+      None => ensure_username_not_blocked(&new_user.username, &config.profile.username)?,
+    }
+
+    let user_id = Uuid::new_v4();
+    let new_user_ = NewUser {
+      tenant_id,
+      username: &new_user.username,
+      username_canonical: &username_canonical,
+      user_id: &user_id,
+      password: &hashed,
+      admin: payload.admin,
+      expires_at: payload.account_expires_at,
+      email: email.as_deref(),
+      status,
+    };
+
+    q_insert_user(connection, new_user_).await?;
+    if let Some(otp_id) = otp_id {
+      u_mark_code_redeemed(connection, otp_id, user_id).await?;
+    }
+
+    if !payload.groups.is_empty() {
+      let groups = i_ensure_groups(connection, tenant_id, &payload.groups).await?;
+      let group_ids: Vec<Uuid> = groups.iter().map(|g| g.group_id).collect();
+      i_group_memberships(connection, user_id, &group_ids, issuer).await?;
+    }
+
+    match &email {
+      Some(email) => Ok(Some((email.clone(), issue_email_code(connection, &config, tenant_id, user_id, email, None).await?))),
+      None => Ok(None),
+    }
+  }.scope_boxed()).await?;
+
+  if let Some((email, code)) = email_code {
+    try_send_email_code(&state, &email, &code).await;
+  }
+
+  Ok((StatusCode::CREATED, Json(RegistrationResponse { status })))
+}
+
+/// Verifies the password of a user, failures count towards the lockout.
+/// A user with a verified address gets an unlock code mailed once they get locked.
+async fn verify_password_counting_failures(connection: &mut AsyncPgConnection, state: &AppState, user: &User, password: String) -> Result<(), Fault> {
+  if let Err(fault) = user.verify_password(password) {
+    let lockout = Duration::minutes(state.config.auth.lockout_minutes);
+    let locked = u_register_failed_login(connection, user.user_id, state.config.auth.max_failed_logins, lockout).await?;
+    if !locked {
+      return Err(fault);
+    }
+
+    if let (Some(email), Some(_)) = (&user.email, user.email_verified_at) {
+      let code = issue_unlock_code(connection, &state.config, user).await?;
+      try_send_unlock_code(state, email, &code).await;
+    }
+    return Err(Fault::UserLocked);
+  }
+
+  Ok(())
+}
+
+/// Resets the failed logins once the user got past all checks
+async fn reset_failed_logins(connection: &mut AsyncPgConnection, user: &User) -> Result<(), Fault> {
+  if user.failed_login_attempts > 0 || user.status == AccountStatus::Locked {
+    u_reset_failed_logins(connection, user.user_id).await?;
+  }
+
+  Ok(())
+}
+
+/// The password is correct, so the user gets a fresh code to get past the missing verification
+async fn resend_email_code_on_login(connection: &mut AsyncPgConnection, state: &AppState, user: &User) -> Fault {
+  if let Some(email) = &user.email {
+    match issue_email_code(connection, &state.config, user.tenant_id, user.user_id, email, None).await {
+      Ok(code) => try_send_email_code(state, email, &code).await,
+      Err(fault) => return fault,
+    }
+  }
+
+  Fault::EmailNotVerified
+}
+
+#[derive(Deserialize)]
+struct LoginBody {
+  /// Username or verified email address
+  #[serde(alias = "username")]
+  identifier: String,
+  password: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all(serialize="camelCase"))]
+struct LoginResponse {
+  tokens: TokenPair,
+  /// Set on login, the client should lead the user to change the temporary password an admin handed out
+  #[serde(skip_serializing_if = "Option::is_none")]
+  password_change_required: Option<bool>,
+}
+async fn login_user(
+  State(state): State<AppState>,
+  Extension(tenant): Extension<Tenant>,
+  Json(user_data): Json<LoginBody>
+) -> Result<(StatusCode, Json<LoginResponse>), Fault> {
+  let mut connection = state
+    .pool.get_connection().await?.connection;
+
+  // find user of the tenant by username, fall back to a verified email address
+  let result: User = match q_get_user_by_name(&mut connection, tenant.tenant_id, &user_data.identifier).await {
+    Ok(user) => user,
+    Err(_) => q_get_user_by_verified_email(&mut connection, tenant.tenant_id, &user_data.identifier).await?,
+  };
+  
+  // let y = state.pool.with_connection(|connection| async move {
+  //   let o = q_get_all_users(&mut connection.as_mut().connection).await?;
+  //   Ok(o)
+  // }).await?;
+
+  // the status is only revealed to someone who knows the password
+  verify_password_counting_failures(&mut connection, &state, &result, user_data.password).await?;
+
+  // a sign-up of `Domain` mode waits for its address to be verified rather than for an admin
+  let awaits_verification = result.status == AccountStatus::PendingApproval && state.config.registration.mode == RegistrationMode::Domain;
+  if awaits_verification && result.email_verified_at.is_none() {
+    return Err(resend_email_code_on_login(&mut connection, &state, &result).await);
+  }
+
+  result.ensure_active()?;
+  reset_failed_logins(&mut connection, &result).await?;
+
+  // admins are exempt, so a misconfiguration cannot lock everybody out
+  let requires_verification = state.config.email.requirement == EmailVerificationRequirement::Login && !result.admin;
+  if requires_verification && result.email_verified_at.is_none() {
+    return Err(resend_email_code_on_login(&mut connection, &state, &result).await);
+  }
+  
+  // generate token pair, save it
+  let token_pair = TokenPair::new(&result.user_id);
+  state.redis.save_token_pair_for_user(&token_pair).await?;
+
+  u_record_login(&mut connection, result.user_id).await?;
+
+  Ok((StatusCode::OK, Json(LoginResponse { tokens: token_pair, password_change_required: Some(result.password_must_change) })))
+}
+
+async fn refresh_user_token(
+  State(state): State<AppState>,
+  Extension(tenant): Extension<Tenant>,
+  Path(refresh_token): Path<Uuid>,
+) -> Result<(StatusCode, Json<LoginResponse>), Fault> {
+  let (user_id, access_token) = state.redis.invalidate_refresh_token_and_get_result(refresh_token).await?;
+  state.redis.clear_token(&access_token).await?;
+
+  let user_uuid = Uuid::parse_str(&user_id).map_err(|_| Fault::UuidConversion)?;
+
+  // the status may have changed since the tokens have been issued, and a session is only valid within its tenant
+  let mut connection = state.pool.get_connection().await?.connection;
+  let user = q_get_user_by_id(&mut connection, user_uuid).await?;
+  if user.tenant_id != tenant.tenant_id {
+    return Err(Fault::NotLoggedIn);
+  }
+  user.ensure_active()?;
+
+  // generate new pair of tokens, save it
+  let token_pair = TokenPair::new(&user_uuid);
+
+  state.redis.save_token_pair_for_user(&token_pair).await?;
+
+  u_record_activity(&mut connection, user_uuid).await?;
+
+  Ok((StatusCode::OK, Json(LoginResponse { tokens: token_pair, password_change_required: None })))
+}
+
+#[derive(Serialize)]
+struct SelfResponse {
+  user: UserInfo,
+  /// The tenant the user belongs to
+  tenant: Tenant,
+  /// Groups the user belongs to, including the groups above the ones they have been added to
+  groups: Vec<GroupMembership>,
+}
+
+async fn get_user_info (
+  State(state): State<AppState>,
+  Extension(tenant): Extension<Tenant>,
+  Extension(user): Extension<User>,
+) -> Result<(StatusCode, Json<SelfResponse>), Fault> {
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  let groups = collect_memberships(&mut connection, user.user_id).await?;
+
+  Ok((StatusCode::OK, Json(SelfResponse { user: UserInfo::from(user), tenant, groups })))
+}
+
+async fn update_user_info (
+  State(state): State<AppState>,
+  Extension(user): Extension<User>,
+  Json(patch): Json<UserPatch>,
+) -> Result<(StatusCode, Json<UserResponse>), Fault> {
+  let updated = update_profile(&state, &user, patch, user.user_id, false).await?;
+
+  Ok((StatusCode::OK, Json(UserResponse { user: UserInfo::from(updated) })))
+}
+
+#[derive(Deserialize)]
+struct DeleteSelfBody {
+  password: String,
+}
+
+async fn delete_self (
+  State(state): State<AppState>,
+  Extension(user): Extension<User>,
+  Json(body): Json<DeleteSelfBody>,
+) -> Result<StatusCode, Fault> {
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  // there is no second factor yet, so the password is the only confirmation
+  verify_password_counting_failures(&mut connection, &state, &user, body.password).await?;
+
+  let user_id = user.user_id;
+  connection.transaction::<_, Fault, _>(|connection| async move {
+    delete_account(connection, &user, user_id).await
+  }.scope_boxed()).await?;
+
+  state.redis.invalidate_sessions_of_user(user_id).await?;
+
+  Ok(StatusCode::OK)
+}
+
+async fn export_self (
+  State(state): State<AppState>,
+  Extension(user): Extension<User>,
+  headers: HeaderMap,
+) -> Result<impl IntoResponse, Fault> {
+  let auth_token = get_authorization_as_uuid(&headers)?;
+  let filename = format!("attachment; filename=\"personal-data-{}.json\"", user.user_id);
+
+  let export = collect_personal_data(&state, user, &auth_token).await?;
+
+  Ok((StatusCode::OK, [(header::CONTENT_DISPOSITION, filename)], Json(export)))
+}
+
+async fn logout_user (
+  State(state): State<AppState>,
+  headers: HeaderMap,
+) -> Result<StatusCode, Fault> {
+  let auth_token = get_authorization_as_uuid(&headers)?;
+  state.redis.invalidate_session_by_access_token(Uuid::from_str(&auth_token).ok().unwrap()).await?;
+  Ok(StatusCode::OK)
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all="camelCase")]
+struct UpdatePasswordByPasswordBody {
+  old_password: String,
+  new_password: String,
+}
+
+async fn reset_password_by_password (
+  State(state): State<AppState>,
+  Extension(mut user): Extension<User>,
+  Json(body): Json<UpdatePasswordByPasswordBody>
+) -> Result<StatusCode, Fault> {
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  // a temporary password from an admin has to be replaced, even before the email address is verified
+  if !user.password_must_change {
+    ensure_verified_email(&user, &state.config)?;
+  }
+  user.verify_password(body.old_password)?;
+  if user.password_must_change && user.verify_password(body.new_password.clone()).is_ok() {
+    return Err(Fault::Validation("the new password has to differ from the temporary password".to_owned()));
+  }
+  user.set_password(body.new_password)?;
+
+  u_set_user_password(&mut connection, &user).await?;
+
+  Ok(StatusCode::OK)
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all="camelCase")]
+struct UpdatePasswordByOtp {
+  otp_code: String,
+  new_password: String,
+}
+
+async fn reset_password_by_otp (
+  State(state): State<AppState>,
+  Extension(tenant): Extension<Tenant>,
+  Json(body): Json<UpdatePasswordByOtp>
+) -> Result<StatusCode, Fault> {
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  let code_hash = state.config.otp.hash_code(&body.otp_code);
+  let prefix = code_prefix(&body.otp_code);
+  let hashed = hash_password(body.new_password)
+    .map_err(|_| Fault::Unexpected)?;
+
+  // the code only counts as redeemed if the password has been updated as well
+  connection.transaction::<_, Fault, _>(|connection| async move {
+    // lock InternalOtp by otp_code
+    let otp = q_lock_password_code(connection, tenant.tenant_id, &code_hash, &prefix).await?;
+    // get the associated user by this otp
+    let mut user = q_get_user_by_id(connection, otp.user.ok_or(Fault::PasswordCodeInvalid)?).await?;
+    // update password in database
+    user.password = hashed;
+    u_set_user_password(connection, &user).await?;
+
+    u_mark_code_redeemed(connection, otp.id, user.user_id).await
+  }.scope_boxed()).await?;
+
+  Ok(StatusCode::OK)
+}
+
+#[derive(Deserialize)]
+struct VerifyEmailBody {
+  code: String,
+}
+
+async fn verify_email (
+  State(state): State<AppState>,
+  Extension(tenant): Extension<Tenant>,
+  Json(body): Json<VerifyEmailBody>
+) -> Result<StatusCode, Fault> {
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  let code_hash = state.config.otp.hash_code(&body.code);
+  let prefix = code_prefix(&body.code);
+
+  let config = state.config.clone();
+
+  connection.transaction::<_, Fault, _>(|connection| async move {
+    let otp = q_lock_email_code(connection, tenant.tenant_id, &code_hash, &prefix).await?;
+
+    // the code proves ownership of the address it has been sent to, for the user it has been issued for
+    let (user, email) = match (otp.user, otp.email) {
+      (Some(user), Some(email)) => (user, email),
+      _ => return Err(Fault::EmailCodeInvalid),
+    };
+
+    u_set_verified_email(connection, user, &email).await?;
+    u_mark_code_redeemed(connection, otp.id, user).await?;
+
+    // in `Domain` mode, owning an address of an allowed domain is what activates a sign-up
+    let pending = q_get_user_by_id(connection, user).await?.status == AccountStatus::PendingApproval;
+    let registration = &config.registration;
+    if registration.mode == RegistrationMode::Domain && pending && registration.is_allowed_email(&email) {
+      u_approve_user(connection, user).await?;
+      i_audit_event(connection, NewAuditEvent::new(
+        AuditAction::RegistrationApproved,
+        None,
+        Some(user),
+        json!({ "emailVerified": email }),
+      )).await?;
+    }
+
+    Ok(())
+  }.scope_boxed()).await?;
+
+  Ok(StatusCode::OK)
+}
+
+async fn resend_email_code (
+  State(state): State<AppState>,
+  Extension(user): Extension<User>,
+) -> Result<StatusCode, Fault> {
+  let email = match (&user.email, user.email_verified_at) {
+    (Some(email), None) => email,
+    (Some(_), Some(_)) => return Err(Fault::Validation("The email address is already verified".to_string())),
+    (None, _) => return Err(Fault::EmailRequired),
+  };
+
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  let code = issue_email_code(&mut connection, &state.config, user.tenant_id, user.user_id, email, Some(user.user_id)).await?;
+  send_email_code(&state.mailer, &state.config, email, &code).await?;
+
+  Ok(StatusCode::OK)
+}
+
+/// Unlock codes that may be tried per user within `LOCKOUT_MINUTES`
+const MAX_UNLOCK_ATTEMPTS: i64 = 5;
+
+#[derive(Deserialize)]
+struct UnlockBody {
+  username: String,
+  code: String,
+}
+
+async fn unlock_account (
+  State(state): State<AppState>,
+  Extension(tenant): Extension<Tenant>,
+  Json(body): Json<UnlockBody>
+) -> Result<StatusCode, Fault> {
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  let code_hash = state.config.otp.hash_code(&body.code);
+  let prefix = code_prefix(&body.code);
+
+  let user = q_get_user_by_name(&mut connection, tenant.tenant_id, &body.username).await
+    .map_err(|_| Fault::UnlockCodeInvalid)?;
+
+  // codes are short, so guessing them is limited per user for as long as a lockout lasts
+  let attempts = state.redis.count_attempt(&format!("UNLOCK_ATTEMPTS:{}", user.user_id), state.config.auth.lockout_minutes * 60).await?;
+  if attempts > MAX_UNLOCK_ATTEMPTS {
+    return Err(Fault::TooManyAttempts);
+  }
+
+  connection.transaction::<_, Fault, _>(|connection| async move {
+    let otp = q_lock_unlock_code(connection, tenant.tenant_id, &code_hash, &prefix).await?;
+
+    // a code issued for another user must not be redeemed
+    if otp.user != Some(user.user_id) {
+      return Err(Fault::UnlockCodeInvalid);
+    }
+
+    u_clear_lockout(connection, user.user_id).await?;
+    u_mark_code_redeemed(connection, otp.id, user.user_id).await
+  }.scope_boxed()).await?;
+
+  Ok(StatusCode::OK)
+}
+
+pub fn router(state: AppState) -> Router<AppState> {
+  Router::new()
+    .route("/auth/self", get(get_user_info).patch(update_user_info).delete(delete_self).layer(middleware::from_fn_with_state(state.clone(), logged_in_guard)))
+    .route("/auth/self/export", get(export_self).layer(middleware::from_fn_with_state(state.clone(), logged_in_guard)))
+    .route("/auth/register", post(add_user))
+    .route("/auth/login", post(login_user))
+    .route("/auth/refresh/{refresh_token}", get(refresh_user_token))
+    .route("/auth/logout", get(logout_user).layer(middleware::from_fn_with_state(state.clone(), logged_in_guard)))
+    .route("/auth/update-password-by-password", post(reset_password_by_password).layer(middleware::from_fn_with_state(state.clone(), logged_in_guard)))
+    .route("/auth/update-password-by-otp", post(reset_password_by_otp))
+    .route("/auth/verify-email", post(verify_email))
+    .route("/auth/verify-email/resend", post(resend_email_code).layer(middleware::from_fn_with_state(state.clone(), logged_in_guard)))
+    .route("/auth/unlock", post(unlock_account))
+}
\ No newline at end of file
@@ -0,0 +1,3 @@
+pub mod routes;
+
+pub mod queries;
@@ -0,0 +1,331 @@
+use std::collections::BTreeSet;
+
+use diesel::prelude::*;
+use diesel::result::{DatabaseErrorKind, Error::DatabaseError};
+use diesel_async::RunQueryDsl;
+use diesel_async::AsyncPgConnection;
+use uuid::Uuid;
+
+use crate::models::group::{Group, GroupChangeset, GroupMember, NewGroup, NewGroupMember, NewGroupRole, NewNestedGroup};
+use crate::models::role::GrantedRole;
+use crate::models::user::AccountStatus;
+use crate::utils::error::Fault;
+
+type Conn = AsyncPgConnection;
+
+/// Returns the groups of a tenant with the given names, groups that do not exist yet are created
+pub async fn i_ensure_groups(connection: &mut Conn, tenant: Uuid, names: &[String]) -> Result<Vec<Group>, Fault> {
+  use crate::schema::groups::dsl::*;
+
+  let to_insert: Vec<NewGroup> = names.iter()
+    .map(|group_name| NewGroup { group_id: Uuid::new_v4(), tenant_id: tenant, name: group_name })
+    .collect();
+
+  diesel::insert_into(groups)
+    .values(to_insert)
+    .on_conflict((tenant_id, name))
+    .do_nothing()
+    .execute(connection)
+    .await
+    .map_err(|_| Fault::Diesel)?;
+
+  q_groups_by_name(connection, tenant, names).await
+}
+
+pub async fn i_group_memberships(connection: &mut Conn, member: Uuid, group_ids: &[Uuid], issuer: Option<Uuid>) -> Result<(), Fault> {
+  use crate::schema::group_members;
+
+  let to_insert: Vec<NewGroupMember> = group_ids.iter()
+    .map(|g| NewGroupMember { group_id: *g, user_id: member, added_by: issuer })
+    .collect();
+
+  diesel::insert_into(group_members::table)
+    .values(to_insert)
+    .on_conflict_do_nothing()
+    .execute(connection)
+    .await
+    .map_err(|_| Fault::Diesel)
+    .map(|_| ())
+}
+
+/// The groups a user has been added to directly
+pub async fn q_groups_of_user(connection: &mut Conn, member: Uuid) -> Result<Vec<Group>, Fault> {
+  use crate::schema::{groups, group_members};
+
+  groups::table
+    .inner_join(group_members::table)
+    .filter(group_members::user_id.eq(member))
+    .order(groups::name.asc())
+    .select(Group::as_select())
+    .load::<Group>(connection)
+    .await
+    .map_err(|_| Fault::Diesel)
+}
+
+pub async fn q_groups(connection: &mut Conn, tenant: Uuid) -> Result<Vec<Group>, Fault> {
+  use crate::schema::groups::dsl::*;
+
+  groups
+    .filter(tenant_id.eq(tenant))
+    .order(name.asc())
+    .select(Group::as_select())
+    .load::<Group>(connection)
+    .await
+    .map_err(|_| Fault::Diesel)
+}
+
+/// The groups directly below a group
+pub async fn q_subgroups(connection: &mut Conn, parent: Uuid) -> Result<Vec<Group>, Fault> {
+  use crate::schema::groups::dsl::*;
+
+  groups
+    .filter(parent_id.eq(parent))
+    .order(name.asc())
+    .select(Group::as_select())
+    .load::<Group>(connection)
+    .await
+    .map_err(|_| Fault::Diesel)
+}
+
+pub async fn q_groups_by_id(connection: &mut Conn, ids: &[Uuid]) -> Result<Vec<Group>, Fault> {
+  use crate::schema::groups::dsl::*;
+
+  groups
+    .filter(group_id.eq_any(ids))
+    .order(name.asc())
+    .select(Group::as_select())
+    .load::<Group>(connection)
+    .await
+    .map_err(|_| Fault::Diesel)
+}
+
+pub async fn q_groups_by_name(connection: &mut Conn, tenant: Uuid, names: &[String]) -> Result<Vec<Group>, Fault> {
+  use crate::schema::groups::dsl::*;
+
+  groups
+    .filter(tenant_id.eq(tenant))
+    .filter(name.eq_any(names))
+    .select(Group::as_select())
+    .load::<Group>(connection)
+    .await
+    .map_err(|_| Fault::Diesel)
+}
+
+pub async fn q_group(connection: &mut Conn, group: Uuid) -> Result<Group, Fault> {
+  use crate::schema::groups::dsl::*;
+
+  groups
+    .filter(group_id.eq(group))
+    .select(Group::as_select())
+    .first::<Group>(connection)
+    .await
+    .map_err(|_| Fault::NotFound("Group".to_string()))
+}
+
+pub async fn i_group(connection: &mut Conn, new_group: NewNestedGroup<'_>) -> Result<Group, Fault> {
+  use crate::schema::groups;
+
+  diesel::insert_into(groups::table)
+    .values(new_group)
+    .returning(Group::as_returning())
+    .get_result::<Group>(connection)
+    .await
+    .map_err(|error| match error {
+      DatabaseError(DatabaseErrorKind::UniqueViolation, _) => Fault::AlreadyExists("Group".to_string()),
+      _ => Fault::Diesel,
+    })
+}
+
+pub async fn u_group(connection: &mut Conn, group: Uuid, changes: GroupChangeset) -> Result<Group, Fault> {
+  use crate::schema::groups::dsl::*;
+
+  diesel::update(groups.filter(group_id.eq(group)))
+    .set(changes)
+    .returning(Group::as_returning())
+    .get_result::<Group>(connection)
+    .await
+    .map_err(|error| match error {
+      DatabaseError(DatabaseErrorKind::UniqueViolation, _) => Fault::AlreadyExists("Group".to_string()),
+      diesel::result::Error::NotFound => Fault::NotFound("Group".to_string()),
+      _ => Fault::Diesel,
+    })
+}
+
+/// Deletes a group without subgroups, its members and roles are removed with it
+pub async fn d_group(connection: &mut Conn, group: Uuid) -> Result<(), Fault> {
+  use crate::schema::groups::dsl::*;
+
+  let deleted = diesel::delete(groups.filter(group_id.eq(group)))
+    .execute(connection)
+    .await
+    .map_err(|error| match error {
+      DatabaseError(DatabaseErrorKind::ForeignKeyViolation, _) => Fault::GroupHasSubgroups,
+      _ => Fault::Diesel,
+    })?;
+
+  match deleted {
+    0 => Err(Fault::NotFound("Group".to_string())),
+    _ => Ok(()),
+  }
+}
+
+/// Serializes changes to the nesting of the groups of a tenant until the transaction ends.
+///
+/// Two groups moved below each other at the same time would each pass the cycle check on their own,
+/// so the check and the move have to happen while no other move of the tenant is in progress.
+pub async fn q_lock_group_hierarchy(connection: &mut Conn, tenant: Uuid) -> Result<(), Fault> {
+  diesel::sql_query("SELECT pg_advisory_xact_lock(hashtext($1))")
+    .bind::<diesel::sql_types::Text, _>(format!("group_hierarchy:{tenant}"))
+    .execute(connection)
+    .await
+    .map_err(|_| Fault::Diesel)?;
+
+  Ok(())
+}
+
+/// The given groups and all groups above them
+pub async fn q_ancestor_groups(connection: &mut Conn, start: &[Uuid]) -> Result<Vec<Uuid>, Fault> {
+  use crate::schema::groups::dsl::*;
+
+  let mut found: BTreeSet<Uuid> = start.iter().copied().collect();
+  let mut frontier: Vec<Uuid> = start.to_vec();
+
+  // one query per level, the set of found groups stops a cycle from looping forever
+  while !frontier.is_empty() {
+    let parents: Vec<Option<Uuid>> = groups
+      .filter(group_id.eq_any(&frontier))
+      .filter(parent_id.is_not_null())
+      .select(parent_id)
+      .load(connection)
+      .await
+      .map_err(|_| Fault::Diesel)?;
+
+    frontier = parents.into_iter().flatten().filter(|parent| found.insert(*parent)).collect();
+  }
+
+  Ok(found.into_iter().collect())
+}
+
+/// The given group and all groups below it
+pub async fn q_descendant_groups(connection: &mut Conn, start: Uuid) -> Result<Vec<Uuid>, Fault> {
+  use crate::schema::groups::dsl::*;
+
+  let mut found: BTreeSet<Uuid> = BTreeSet::from([start]);
+  let mut frontier: Vec<Uuid> = vec![start];
+
+  while !frontier.is_empty() {
+    let children: Vec<Uuid> = groups
+      .filter(parent_id.eq_any(&frontier))
+      .select(group_id)
+      .load(connection)
+      .await
+      .map_err(|_| Fault::Diesel)?;
+
+    frontier = children.into_iter().filter(|child| found.insert(*child)).collect();
+  }
+
+  Ok(found.into_iter().collect())
+}
+
+/// The groups a user belongs to, directly or through a group below them
+pub async fn q_effective_group_ids_of_user(connection: &mut Conn, member: Uuid) -> Result<Vec<Uuid>, Fault> {
+  use crate::schema::group_members::dsl::*;
+
+  let direct: Vec<Uuid> = group_members
+    .filter(user_id.eq(member))
+    .select(group_id)
+    .load(connection)
+    .await
+    .map_err(|_| Fault::Diesel)?;
+
+  q_ancestor_groups(connection, &direct).await
+}
+
+/// The members of the given groups that have not been deleted, ordered by username
+pub async fn q_members_of_groups(connection: &mut Conn, group_ids: &[Uuid]) -> Result<Vec<GroupMember>, Fault> {
+  use crate::schema::{group_members, users};
+
+  group_members::table
+    .inner_join(users::table.on(users::user_id.eq(group_members::user_id)))
+    .filter(group_members::group_id.eq_any(group_ids))
+    .filter(users::status.ne(AccountStatus::Deleted))
+    .order((users::username.asc(), group_members::group_id.asc()))
+    .select((users::user_id, users::username, group_members::group_id, group_members::added_by, group_members::added_at))
+    .load::<GroupMember>(connection)
+    .await
+    .map_err(|_| Fault::Diesel)
+}
+
+/// Adds a user to a group, returns whether the user has not been a member yet
+pub async fn i_group_member(connection: &mut Conn, member: NewGroupMember) -> Result<bool, Fault> {
+  use crate::schema::group_members;
+
+  diesel::insert_into(group_members::table)
+    .values(member)
+    .on_conflict_do_nothing()
+    .execute(connection)
+    .await
+    .map_err(|_| Fault::Diesel)
+    .map(|inserted| inserted > 0)
+}
+
+/// Removes a user from a group, returns whether the user has been a member
+pub async fn d_group_member(connection: &mut Conn, group: Uuid, member: Uuid) -> Result<bool, Fault> {
+  use crate::schema::group_members::dsl::*;
+
+  diesel::delete(group_members.filter(group_id.eq(group)).filter(user_id.eq(member)))
+    .execute(connection)
+    .await
+    .map_err(|_| Fault::Diesel)
+    .map(|deleted| deleted > 0)
+}
+
+/// The roles granted to the given groups
+pub async fn q_role_ids_of_groups(connection: &mut Conn, group_ids: &[Uuid]) -> Result<Vec<Uuid>, Fault> {
+  use crate::schema::group_roles::dsl::*;
+
+  group_roles
+    .filter(group_id.eq_any(group_ids))
+    .select(role_id)
+    .distinct()
+    .load::<Uuid>(connection)
+    .await
+    .map_err(|_| Fault::Diesel)
+}
+
+pub async fn q_roles_of_group(connection: &mut Conn, group: Uuid) -> Result<Vec<GrantedRole>, Fault> {
+  use crate::schema::{roles, group_roles};
+
+  group_roles::table
+    .inner_join(roles::table)
+    .filter(group_roles::group_id.eq(group))
+    .order(roles::name.asc())
+    .select((roles::role_id, roles::name, group_roles::granted_by, group_roles::granted_at))
+    .load::<GrantedRole>(connection)
+    .await
+    .map_err(|_| Fault::Diesel)
+}
+
+/// Grants a role to a group, returns whether the group did not hold it yet
+pub async fn i_group_role(connection: &mut Conn, grant: NewGroupRole) -> Result<bool, Fault> {
+  use crate::schema::group_roles;
+
+  diesel::insert_into(group_roles::table)
+    .values(grant)
+    .on_conflict_do_nothing()
+    .execute(connection)
+    .await
+    .map_err(|_| Fault::Diesel)
+    .map(|inserted| inserted > 0)
+}
+
+/// Revokes a role from a group, returns whether the group held it
+pub async fn d_group_role(connection: &mut Conn, group: Uuid, role: Uuid) -> Result<bool, Fault> {
+  use crate::schema::group_roles::dsl::*;
+
+  diesel::delete(group_roles.filter(group_id.eq(group)).filter(role_id.eq(role)))
+    .execute(connection)
+    .await
+    .map_err(|_| Fault::Diesel)
+    .map(|deleted| deleted > 0)
+}
@@ -0,0 +1,356 @@
+use axum::{
+  Router,
+  routing::{get, patch, post, put},
+  Extension,
+  extract::{State, Path, Query},
+  http::StatusCode,
+  Json,
+};
+use diesel_async::{AsyncConnection, AsyncPgConnection, scoped_futures::ScopedFutureExt};
+use serde::Serialize;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+  api::{audit::queries::i_audit_event, auth::queries::q_get_user_by_id, role::queries::q_permissions_of_roles, tenant::scope::ensure_group_in_tenant},
+  middleware::authorized::require_permission,
+  models::{audit::{AuditAction, NewAuditEvent}, group::{Group, GroupChangeset, GroupMember, GroupMemberQuery, GroupMembership, GroupPatch, NewGroupMember, NewGroupRequest, NewNestedGroup}, role::{GrantedRole, Permissions}, tenant::Tenant, user::User},
+  state::AppState,
+  utils::{error::Fault, validation::{MAX_GROUP_DESCRIPTION_LENGTH, MAX_GROUP_NAME_LENGTH}},
+};
+
+use super::queries::{d_group, d_group_member, i_group, i_group_member, q_ancestor_groups, q_descendant_groups, q_effective_group_ids_of_user, q_group, q_groups, q_groups_by_id, q_groups_by_name, q_groups_of_user, q_lock_group_hierarchy, q_members_of_groups, q_role_ids_of_groups, q_roles_of_group, q_subgroups, u_group};
+
+/// A group with its direct subgroups, its direct members and the roles granted to it, handed out by `GET /groups/{group_id}`
+#[derive(Serialize)]
+#[serde(rename_all(serialize="camelCase"))]
+pub struct GroupDetail {
+  pub group: Group,
+  pub subgroups: Vec<Group>,
+  pub members: Vec<GroupMember>,
+  pub roles: Vec<GrantedRole>,
+}
+
+/// The groups a user belongs to, including the groups above the ones they have been added to
+pub async fn collect_memberships(connection: &mut AsyncPgConnection, user: Uuid) -> Result<Vec<GroupMembership>, Fault> {
+  let direct: Vec<Uuid> = q_groups_of_user(connection, user).await?.into_iter().map(|g| g.group_id).collect();
+  let effective = q_effective_group_ids_of_user(connection, user).await?;
+
+  let memberships = q_groups_by_id(connection, &effective).await?
+    .into_iter()
+    .map(|group| GroupMembership { direct: direct.contains(&group.group_id), group_id: group.group_id, name: group.name })
+    .collect();
+
+  Ok(memberships)
+}
+
+/// Joining or leaving a group grants or withdraws the roles of the group and of all groups above it.
+/// Only users holding all of their permissions may change who is affected by them.
+pub async fn ensure_holds_group_permissions(connection: &mut AsyncPgConnection, held: &Permissions, groups: &[Uuid]) -> Result<(), Fault> {
+  let affected = q_ancestor_groups(connection, groups).await?;
+  let roles = q_role_ids_of_groups(connection, &affected).await?;
+  if roles.is_empty() {
+    return Ok(());
+  }
+
+  let granted = q_permissions_of_roles(connection, &roles).await?;
+
+  held.ensure_all(granted.iter().map(|(_, permission)| permission))
+}
+
+/// Registration codes adding users to groups of a tenant, groups that do not exist yet are created without roles on redemption
+pub async fn ensure_can_join_groups(connection: &mut AsyncPgConnection, held: &Permissions, tenant: Uuid, names: &[String]) -> Result<(), Fault> {
+  if names.is_empty() {
+    return Ok(());
+  }
+
+  held.ensure("groups.members")?;
+
+  let existing: Vec<Uuid> = q_groups_by_name(connection, tenant, names).await?.into_iter().map(|g| g.group_id).collect();
+
+  ensure_holds_group_permissions(connection, held, &existing).await
+}
+
+fn validate_group_name(name: &str) -> Result<(), Fault> {
+  if name.trim().is_empty() || name.trim() != name || name.chars().count() > MAX_GROUP_NAME_LENGTH {
+    return Err(Fault::Validation(format!("A group name must consist of 1 to {MAX_GROUP_NAME_LENGTH} characters without surrounding whitespace")));
+  }
+
+  Ok(())
+}
+
+fn validate_group_description(description: &str) -> Result<(), Fault> {
+  if description.chars().count() > MAX_GROUP_DESCRIPTION_LENGTH {
+    return Err(Fault::Validation(format!("A group description may consist of at most {MAX_GROUP_DESCRIPTION_LENGTH} characters")));
+  }
+
+  Ok(())
+}
+
+async fn get_groups(
+  State(state): State<AppState>,
+  Extension(tenant): Extension<Tenant>,
+) -> Result<(StatusCode, Json<Vec<Group>>), Fault> {
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  let groups = q_groups(&mut connection, tenant.tenant_id).await?;
+
+  Ok((StatusCode::OK, Json(groups)))
+}
+
+async fn get_group(
+  State(state): State<AppState>,
+  Path(group_id): Path<Uuid>,
+) -> Result<(StatusCode, Json<GroupDetail>), Fault> {
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  let group = q_group(&mut connection, group_id).await?;
+  let subgroups = q_subgroups(&mut connection, group_id).await?;
+  let members = q_members_of_groups(&mut connection, &[group_id]).await?;
+  let roles = q_roles_of_group(&mut connection, group_id).await?;
+
+  Ok((StatusCode::OK, Json(GroupDetail { group, subgroups, members, roles })))
+}
+
+async fn create_group(
+  State(state): State<AppState>,
+  Extension(tenant): Extension<Tenant>,
+  Extension(actor): Extension<User>,
+  Json(request): Json<NewGroupRequest>,
+) -> Result<(StatusCode, Json<Group>), Fault> {
+  validate_group_name(&request.name)?;
+  if let Some(description) = &request.description {
+    validate_group_description(description)?;
+  }
+
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  let group = connection.transaction::<_, Fault, _>(|connection| async move {
+    if let Some(parent) = request.parent_id {
+      ensure_group_in_tenant(connection, &tenant, parent).await?;
+    }
+
+    let group = i_group(connection, NewNestedGroup {
+      group_id: Uuid::new_v4(),
+      tenant_id: tenant.tenant_id,
+      name: &request.name,
+      description: request.description.as_deref(),
+      parent_id: request.parent_id,
+    }).await?;
+
+    i_audit_event(connection, NewAuditEvent::new(
+      AuditAction::GroupCreated,
+      Some(actor.user_id),
+      None,
+      json!({ "groupId": group.group_id, "name": group.name, "parentId": group.parent_id }),
+    )).await?;
+
+    Ok(group)
+  }.scope_boxed()).await?;
+
+  Ok((StatusCode::CREATED, Json(group)))
+}
+
+async fn update_group(
+  State(state): State<AppState>,
+  Extension(tenant): Extension<Tenant>,
+  Extension(actor): Extension<User>,
+  Extension(held): Extension<Permissions>,
+  Path(group_id): Path<Uuid>,
+  Json(patch): Json<GroupPatch>,
+) -> Result<(StatusCode, Json<Group>), Fault> {
+  if let Some(name) = &patch.name {
+    validate_group_name(name)?;
+  }
+  if let Some(Some(description)) = &patch.description {
+    validate_group_description(description)?;
+  }
+
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  let group = connection.transaction::<_, Fault, _>(|connection| async move {
+    let current = q_group(connection, group_id).await?;
+
+    if let Some(parent) = patch.parent_id.filter(|parent| *parent != current.parent_id) {
+      if let Some(parent) = parent {
+        ensure_group_in_tenant(connection, &tenant, parent).await?;
+
+        q_lock_group_hierarchy(connection, tenant.tenant_id).await?;
+        if q_descendant_groups(connection, group_id).await?.contains(&parent) {
+          return Err(Fault::Validation("A group cannot be nested below itself or a group below it".to_string()));
+        }
+      }
+
+      // the members move from the roles above the old parent to the roles above the new one
+      let parents: Vec<Uuid> = current.parent_id.into_iter().chain(parent).collect();
+      ensure_holds_group_permissions(connection, &held, &parents).await?;
+    }
+
+    let group = match (&patch.name, &patch.description, &patch.parent_id) {
+      (None, None, None) => current,
+      _ => u_group(connection, group_id, GroupChangeset { name: patch.name, description: patch.description, parent_id: patch.parent_id }).await?,
+    };
+
+    i_audit_event(connection, NewAuditEvent::new(
+      AuditAction::GroupUpdated,
+      Some(actor.user_id),
+      None,
+      json!({ "groupId": group.group_id, "name": group.name, "parentId": group.parent_id }),
+    )).await?;
+
+    Ok(group)
+  }.scope_boxed()).await?;
+
+  Ok((StatusCode::OK, Json(group)))
+}
+
+async fn delete_group(
+  State(state): State<AppState>,
+  Extension(actor): Extension<User>,
+  Extension(held): Extension<Permissions>,
+  Path(group_id): Path<Uuid>,
+) -> Result<StatusCode, Fault> {
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  connection.transaction::<_, Fault, _>(|connection| async move {
+    let group = q_group(connection, group_id).await?;
+
+    ensure_holds_group_permissions(connection, &held, &[group_id]).await?;
+
+    d_group(connection, group_id).await?;
+
+    i_audit_event(connection, NewAuditEvent::new(
+      AuditAction::GroupDeleted,
+      Some(actor.user_id),
+      None,
+      json!({ "groupId": group.group_id, "name": group.name }),
+    )).await
+  }.scope_boxed()).await?;
+
+  Ok(StatusCode::OK)
+}
+
+async fn get_group_members(
+  State(state): State<AppState>,
+  Path(group_id): Path<Uuid>,
+  Query(query): Query<GroupMemberQuery>,
+) -> Result<(StatusCode, Json<Vec<GroupMember>>), Fault> {
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  q_group(&mut connection, group_id).await?;
+
+  let groups = match query.nested {
+    true => q_descendant_groups(&mut connection, group_id).await?,
+    false => vec![group_id],
+  };
+  let members = q_members_of_groups(&mut connection, &groups).await?;
+
+  Ok((StatusCode::OK, Json(members)))
+}
+
+async fn add_group_member(
+  State(state): State<AppState>,
+  Extension(actor): Extension<User>,
+  Extension(held): Extension<Permissions>,
+  Path((group_id, user_id)): Path<(Uuid, Uuid)>,
+) -> Result<(StatusCode, Json<Vec<GroupMember>>), Fault> {
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  let members = connection.transaction::<_, Fault, _>(|connection| async move {
+    let group = q_group(connection, group_id).await?;
+    q_get_user_by_id(connection, user_id).await?;
+
+    ensure_holds_group_permissions(connection, &held, &[group_id]).await?;
+
+    let added = i_group_member(connection, NewGroupMember { group_id, user_id, added_by: Some(actor.user_id) }).await?;
+
+    if added {
+      i_audit_event(connection, NewAuditEvent::new(
+        AuditAction::GroupMemberAdded,
+        Some(actor.user_id),
+        Some(user_id),
+        json!({ "groupId": group.group_id, "name": group.name }),
+      )).await?;
+    }
+
+    q_members_of_groups(connection, &[group_id]).await
+  }.scope_boxed()).await?;
+
+  Ok((StatusCode::OK, Json(members)))
+}
+
+async fn remove_group_member(
+  State(state): State<AppState>,
+  Extension(actor): Extension<User>,
+  Extension(held): Extension<Permissions>,
+  Path((group_id, user_id)): Path<(Uuid, Uuid)>,
+) -> Result<(StatusCode, Json<Vec<GroupMember>>), Fault> {
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  let members = connection.transaction::<_, Fault, _>(|connection| async move {
+    let group = q_group(connection, group_id).await?;
+
+    ensure_holds_group_permissions(connection, &held, &[group_id]).await?;
+
+    let removed = d_group_member(connection, group_id, user_id).await?;
+
+    if removed {
+      i_audit_event(connection, NewAuditEvent::new(
+        AuditAction::GroupMemberRemoved,
+        Some(actor.user_id),
+        Some(user_id),
+        json!({ "groupId": group.group_id, "name": group.name }),
+      )).await?;
+    }
+
+    q_members_of_groups(connection, &[group_id]).await
+  }.scope_boxed()).await?;
+
+  Ok((StatusCode::OK, Json(members)))
+}
+
+async fn get_user_groups(
+  State(state): State<AppState>,
+  Path(user_id): Path<Uuid>,
+) -> Result<(StatusCode, Json<Vec<GroupMembership>>), Fault> {
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  q_get_user_by_id(&mut connection, user_id).await?;
+  let memberships = collect_memberships(&mut connection, user_id).await?;
+
+  Ok((StatusCode::OK, Json(memberships)))
+}
+
+pub fn router(state: AppState) -> Router<AppState> {
+  Router::new()
+    .route("/groups",
+      get(get_groups)
+      .layer(require_permission(&state, "groups.read"))
+    )
+    .route("/groups",
+      post(create_group)
+      .layer(require_permission(&state, "groups.manage"))
+    )
+    .route("/groups/{group_id}",
+      get(get_group)
+      .layer(require_permission(&state, "groups.read"))
+    )
+    .route("/groups/{group_id}",
+      patch(update_group)
+        .delete(delete_group)
+        .layer(require_permission(&state, "groups.manage"))
+    )
+    .route("/groups/{group_id}/members",
+      get(get_group_members)
+      .layer(require_permission(&state, "groups.read"))
+    )
+    .route("/groups/{group_id}/members/{user_id}",
+      put(add_group_member)
+        .delete(remove_group_member)
+        .layer(require_permission(&state, "groups.members"))
+    )
+    .route("/users/{user_id}/groups",
+      get(get_user_groups)
+      .layer(require_permission(&state, "groups.read"))
+    )
+}
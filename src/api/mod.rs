@@ -5,3 +5,11 @@ pub mod user;
 pub mod system_setup;
 
 pub mod otp;
+
+pub mod group;
+
+pub mod audit;
+
+pub mod role;
+
+pub mod tenant;
@@ -1,3 +1,3 @@
-pub mod otp;
+pub mod routes;
 
 pub mod queries;
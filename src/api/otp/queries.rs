@@ -1,55 +1,131 @@
-use bb8::PooledConnection;
-use diesel::{delete, ExpressionMethods};
-use diesel::query_dsl::methods::FilterDsl;
-use diesel_async::{pooled_connection::AsyncDieselConnectionManager, AsyncPgConnection};
+use chrono::Utc;
+use diesel::{delete, update, BoolExpressionMethods, ExpressionMethods, OptionalExtension, QueryDsl, SelectableHelper};
+use diesel_async::AsyncPgConnection;
 use diesel_async::RunQueryDsl;
-use diesel::result::Error::DatabaseError;
-use diesel::associations::HasTable;
+use uuid::Uuid;
 
-use crate::models::otp::{OtpInternal, OtpEnum, InsertableOtp};
-use crate::{models::otp::NewOtp, utils::error::Fault};
+use crate::models::otp::{OtpInternal, OtpEnum, InsertableOtp, OtpBatch, NewOtpBatch, OtpListQuery, OtpStatus};
+use crate::utils::error::Fault;
 
-type Conn<'a> = PooledConnection<'a, AsyncDieselConnectionManager<AsyncPgConnection>>;
+type Conn = AsyncPgConnection;
 
-pub async fn i_otp(connection: &mut Conn<'_>, new_otp: NewOtp, code_type: OtpEnum) -> Result<(), Fault> {
+/// Inserts a new OTP, a code that already exists results in `Fault::AlreadyExists`.
+///
+/// The collision is detected with `ON CONFLICT DO NOTHING` instead of a failing statement,
+/// so the surrounding transaction stays usable and the caller can retry with another code.
+pub async fn i_otp(connection: &mut Conn, to_insert: InsertableOtp) -> Result<OtpInternal, Fault> {
   use crate::schema::otp;
 
-  let to_insert = InsertableOtp {
-    code: new_otp.code,
-    user: new_otp.user,
-    code_type,
-  };
-
   diesel::insert_into(otp::table)
     .values(to_insert)
-    .execute(connection)
+    .on_conflict((otp::tenant_id, otp::code_hash))
+    .do_nothing()
+    .returning(OtpInternal::as_returning())
+    .get_result::<OtpInternal>(connection)
     .await
-    .or_else(|diesel_error| {
-      match diesel_error {
-        DatabaseError(diesel::result::DatabaseErrorKind::UniqueViolation, _) => Err(Fault::AlreadyExists("code".to_owned())),
-        _ => Err(Fault::Diesel)
-      }
-    })
-    .and_then(|_| Ok(()))
+    .optional()
+    .map_err(|_| Fault::Diesel)?
+    .ok_or(Fault::AlreadyExists("code".to_owned()))
 }
 
-pub async fn q_otp_list(connection: &mut Conn<'_>) -> Result<Vec<OtpInternal>, Fault> {
+/// Default and maximum page size of `q_otp_list`
+pub const OTP_PAGE_SIZE: i64 = 50;
+pub const OTP_MAX_PAGE_SIZE: i64 = 200;
+
+/// Lists the OTPs of a tenant ordered by id, starting after `filter.cursor`.
+/// Returns one more row than requested, so the caller can tell whether there is a next page.
+pub async fn q_otp_list(connection: &mut Conn, tenant: Uuid, filter: &OtpListQuery, filter_type: Option<OtpEnum>, page_size: i64) -> Result<Vec<OtpInternal>, Fault> {
   use crate::schema::otp::dsl::*;
 
-  let res = otp::table()
+  let mut query = otp
+    .filter(tenant_id.eq(tenant))
+    .select(OtpInternal::as_select())
+    .order(id.asc())
+    .limit(page_size + 1)
+    .into_boxed();
+
+  if let Some(filter_batch) = filter.batch {
+    query = query.filter(batch_id.eq(filter_batch));
+  }
+
+  if let Some(filter_type) = filter_type {
+    query = query.filter(code_type.eq(filter_type));
+  }
+
+  if let Some(filter_user) = filter.user {
+    query = query.filter(user.eq(filter_user));
+  }
+
+  if let Some(after) = filter.cursor {
+    query = query.filter(id.gt(after));
+  }
+
+  let now = Utc::now();
+  query = match filter.status {
+    Some(OtpStatus::Active) => query.filter(redeemed_at.is_null()).filter(expires_at.is_null().or(expires_at.gt(now))),
+    Some(OtpStatus::Redeemed) => query.filter(redeemed_at.is_not_null()),
+    Some(OtpStatus::Expired) => query.filter(redeemed_at.is_null()).filter(expires_at.le(now)),
+    None => query,
+  };
+
+  query
     .load::<OtpInternal>(connection)
     .await
-    .or_else(|_| Err(Fault::Diesel))?;
+    .map_err(|_| Fault::Diesel)
+}
+
+pub async fn i_otp_batch(connection: &mut Conn, to_insert: NewOtpBatch<'_>) -> Result<OtpBatch, Fault> {
+  use crate::schema::otp_batches;
+
+  diesel::insert_into(otp_batches::table)
+    .values(to_insert)
+    .returning(OtpBatch::as_returning())
+    .get_result::<OtpBatch>(connection)
+    .await
+    .map_err(|_| Fault::Diesel)
+}
+
+pub async fn q_otp_batches(connection: &mut Conn, tenant: Uuid) -> Result<Vec<OtpBatch>, Fault> {
+  use crate::schema::otp_batches::dsl::*;
+
+  otp_batches
+    .filter(tenant_id.eq(tenant))
+    .select(OtpBatch::as_select())
+    .order(created_at.desc())
+    .load::<OtpBatch>(connection)
+    .await
+    .map_err(|_| Fault::Diesel)
+}
+
+/// Marks a batch as revoked and deletes all of its codes that have not been redeemed yet.
+/// Returns the amount of deleted codes.
+pub async fn u_revoke_otp_batch(connection: &mut Conn, revoked_batch: Uuid) -> Result<usize, Fault> {
+  use crate::schema::otp_batches::dsl::*;
 
-  Ok(res)
+  let result: usize = update(otp_batches.filter(batch_id.eq(revoked_batch)))
+    .set(revoked_at.eq(Utc::now()))
+    .execute(connection)
+    .await
+    .map_err(|_| Fault::Diesel)?;
+
+  if result == 0 {
+    return Err(Fault::NotFound("batch".to_owned()));
+  }
+
+  delete(crate::schema::otp::table
+    .filter(crate::schema::otp::batch_id.eq(revoked_batch))
+    .filter(crate::schema::otp::redeemed_at.is_null()))
+    .execute(connection)
+    .await
+    .map_err(|_| Fault::Diesel)
 }
 
-pub async fn d_otp(connection: &mut Conn<'_>, otp_id: i32) -> Result<(), Fault> {
+pub async fn d_otp(connection: &mut Conn, otp_id: i32) -> Result<(), Fault> {
   use crate::schema::otp::dsl::*;
 
   let result: usize = delete(otp.filter(id.eq(otp_id)))
     .execute(connection)
-    .await.or_else(|_| Err(Fault::Diesel))?;
+    .await.map_err(|_| Fault::Diesel)?;
 
   if result == 0 {
     return Err(Fault::NotFound("code".to_owned()));
@@ -58,53 +134,115 @@ pub async fn d_otp(connection: &mut Conn<'_>, otp_id: i32) -> Result<(), Fault>
   Ok(())
 }
 
-async fn d_code(connection: &mut Conn<'_>, otp_code: &String) {
+/// Deletes the codes of `code_type` a user has not redeemed yet, so a newly issued code supersedes them
+pub async fn d_unredeemed_codes_of_user(connection: &mut Conn, owner: Uuid, expected_type: OtpEnum) -> Result<usize, Fault> {
   use crate::schema::otp::dsl::*;
 
-  let _ = delete(otp.filter(code.eq(otp_code)))
-  .execute(connection)
-  .await;
+  delete(otp
+    .filter(user.eq(owner))
+    .filter(code_type.eq(expected_type))
+    .filter(redeemed_at.is_null()))
+    .execute(connection)
+    .await
+    .map_err(|_| Fault::Diesel)
 }
 
-pub async fn q_check_registration_code(connection: &mut Conn<'_>, otp_code: &String) -> Result<(), Fault> {
+/// Codes bound to a user or redeemed by them
+pub async fn q_otps_of_user(connection: &mut Conn, owner: Uuid) -> Result<Vec<OtpInternal>, Fault> {
   use crate::schema::otp::dsl::*;
 
-  let found_code = otp
-    .filter(code.eq(otp_code))
-    .first::<OtpInternal>(connection)
-    .await;
+  otp
+    .filter(user.eq(owner).or(redeemed_by.eq(owner)))
+    .order(id.asc())
+    .select(OtpInternal::as_select())
+    .load::<OtpInternal>(connection)
+    .await
+    .map_err(|_| Fault::Diesel)
+}
 
-  match found_code {
-    Ok(c) => {
-      if c.code_type != OtpEnum::REGISTER {
-        return Err(Fault::RegistrationCodeInvalid);
-      }
+/// Codes bound to a user that can still be redeemed
+pub async fn q_active_otps_of_user(connection: &mut Conn, owner: Uuid) -> Result<Vec<OtpInternal>, Fault> {
+  use crate::schema::otp::dsl::*;
 
-      d_code(connection, otp_code).await;
+  otp
+    .filter(user.eq(owner))
+    .filter(redeemed_at.is_null())
+    .filter(expires_at.is_null().or(expires_at.gt(Utc::now())))
+    .order(id.asc())
+    .select(OtpInternal::as_select())
+    .load::<OtpInternal>(connection)
+    .await
+    .map_err(|_| Fault::Diesel)
+}
 
-      return Ok(());
-    }
-    _ => return Err(Fault::RegistrationCodeInvalid)
-  }
+/// Deletes all codes bound to a user, which otherwise prevent the user from being deleted
+pub async fn d_otps_of_user(connection: &mut Conn, owner: Uuid) -> Result<usize, Fault> {
+  use crate::schema::otp::dsl::*;
+
+  delete(otp.filter(user.eq(owner)))
+    .execute(connection)
+    .await
+    .map_err(|_| Fault::Diesel)
 }
 
-pub async fn q_check_password_code(connection: &mut Conn<'_>, otp_code: &String) -> Result<OtpInternal, Fault> {
+/// Looks up a redeemable (not redeemed, not expired) code of a tenant with the given hash, prefix and type and locks its row.
+///
+/// When two transactions try to redeem the same code, the second one waits for the row lock of the first.
+/// Once the first one commits, the row no longer matches `redeemed_at IS NULL` and the second one finds nothing.
+/// The code only counts as redeemed if `u_mark_code_redeemed` is called and the surrounding transaction commits.
+async fn q_lock_redeemable_code(connection: &mut Conn, tenant: Uuid, otp_code_hash: &str, otp_code_prefix: &str, expected_type: OtpEnum) -> Result<Option<OtpInternal>, Fault> {
   use crate::schema::otp::dsl::*;
 
-  let found_code = otp
-    .filter(code.eq(otp_code))
-    .first::<OtpInternal>(connection).await;
+  let not_expired = expires_at.is_null().or(expires_at.gt(Utc::now()));
+
+  otp
+    .filter(tenant_id.eq(tenant))
+    .filter(code_hash.eq(otp_code_hash))
+    .filter(code_prefix.eq(otp_code_prefix))
+    .filter(code_type.eq(expected_type))
+    .filter(redeemed_at.is_null())
+    .filter(not_expired)
+    .select(OtpInternal::as_select())
+    .for_update()
+    .first::<OtpInternal>(connection)
+    .await
+    .optional()
+    .map_err(|_| Fault::Diesel)
+}
+
+pub async fn q_lock_registration_code(connection: &mut Conn, tenant: Uuid, otp_code_hash: &str, otp_code_prefix: &str) -> Result<OtpInternal, Fault> {
+  q_lock_redeemable_code(connection, tenant, otp_code_hash, otp_code_prefix, OtpEnum::REGISTER).await?
+    .ok_or(Fault::RegistrationCodeInvalid)
+}
 
-  match found_code {
-    Ok(c) => {
-      if c.code_type != OtpEnum::PWRESET {
-        return Err(Fault::PasswordCodeInvalid);
-      }
+pub async fn q_lock_password_code(connection: &mut Conn, tenant: Uuid, otp_code_hash: &str, otp_code_prefix: &str) -> Result<OtpInternal, Fault> {
+  q_lock_redeemable_code(connection, tenant, otp_code_hash, otp_code_prefix, OtpEnum::PWRESET).await?
+    .ok_or(Fault::PasswordCodeInvalid)
+}
 
-      let _ = d_code(connection, otp_code).await;
+pub async fn q_lock_email_code(connection: &mut Conn, tenant: Uuid, otp_code_hash: &str, otp_code_prefix: &str) -> Result<OtpInternal, Fault> {
+  q_lock_redeemable_code(connection, tenant, otp_code_hash, otp_code_prefix, OtpEnum::EMAILVERIFY).await?
+    .ok_or(Fault::EmailCodeInvalid)
+}
+
+pub async fn q_lock_unlock_code(connection: &mut Conn, tenant: Uuid, otp_code_hash: &str, otp_code_prefix: &str) -> Result<OtpInternal, Fault> {
+  q_lock_redeemable_code(connection, tenant, otp_code_hash, otp_code_prefix, OtpEnum::UNLOCK).await?
+    .ok_or(Fault::UnlockCodeInvalid)
+}
+
+/// Marks a code as redeemed, it is kept as history
+pub async fn u_mark_code_redeemed(connection: &mut Conn, otp_id: i32, redeemer: Uuid) -> Result<(), Fault> {
+  use crate::schema::otp::dsl::*;
 
-      return Ok(c);
-    }
-    _ => return Err(Fault::PasswordCodeInvalid)
+  let result: usize = update(otp.filter(id.eq(otp_id)).filter(redeemed_at.is_null()))
+    .set((redeemed_at.eq(Utc::now()), redeemed_by.eq(redeemer)))
+    .execute(connection)
+    .await
+    .map_err(|_| Fault::Diesel)?;
+
+  if result == 0 {
+    return Err(Fault::NotFound("code".to_owned()));
   }
-}
\ No newline at end of file
+
+  Ok(())
+}
@@ -0,0 +1,434 @@
+use std::str::FromStr;
+
+use axum::{Router, routing::{post, delete, get}, http::{StatusCode, header}, Json, extract::{State, Path, Query, Extension}, response::{IntoResponse, Response}};
+use chrono::Utc;
+use diesel_async::{AsyncConnection, AsyncPgConnection, scoped_futures::ScopedFutureExt};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{state::{AppState, config::OtpConfig}, middleware::authorized::require_permission, api::{group::routes::ensure_can_join_groups, tenant::scope::ensure_user_in_tenant, user::safeguards::{ensure_can_grant_admin, ensure_outranks}}, utils::{error::Fault, otp_code::{code_prefix, validate_custom_code}, validation::is_valid_email}, models::{otp::{NewOtp, OtpExternal, OtpEnum, OtpInternal, CreatedOtp, InsertableOtp, RegistrationPayload, OtpBatch, NewOtpBatch, NewOtpBatchRequest, OtpListQuery, OtpPage}, role::Permissions, tenant::Tenant, user::User}};
+
+use super::queries::{i_otp, q_otp_list, d_otp, i_otp_batch, q_otp_batches, u_revoke_otp_batch, OTP_PAGE_SIZE, OTP_MAX_PAGE_SIZE};
+
+/// How often a generated code is re-rolled when it collides with an existing one
+const GENERATION_ATTEMPTS: usize = 5;
+
+/// Upper limit of codes that can be generated with a single batch
+const MAX_BATCH_SIZE: usize = 1000;
+
+fn validate_registration_payload(payload: &RegistrationPayload) -> Result<(), Fault> {
+  if payload.restricted_to.as_ref().is_some_and(|r| r.is_empty() || r.len() > 254) {
+    return Err(Fault::Validation("restrictedTo must be between 1 and 254 characters long".to_owned()));
+  }
+
+  if payload.groups.iter().any(|g| g.is_empty() || g.len() > 64) {
+    return Err(Fault::Validation("Group names must be between 1 and 64 characters long".to_owned()));
+  }
+
+  if payload.account_expires_at.is_some_and(|expiry| expiry <= Utc::now()) {
+    return Err(Fault::Validation("accountExpiresAt must be in the future".to_owned()));
+  }
+
+  Ok(())
+}
+
+/// Inserts `template` with a freshly generated code, re-rolling the code on collisions.
+/// Returns the created OTP together with the code in its display format.
+pub async fn insert_generated_otp(
+  connection: &mut AsyncPgConnection,
+  config: &OtpConfig,
+  template: &InsertableOtp,
+) -> Result<(OtpInternal, String), Fault> {
+  for _ in 0..GENERATION_ATTEMPTS {
+    let code = config.format.generate();
+
+    let to_insert = InsertableOtp {
+      code_hash: config.hash_code(&code),
+      code_prefix: code_prefix(&code),
+      ..template.clone()
+    };
+
+    match i_otp(connection, to_insert).await {
+      Ok(created) => return Ok((created, code)),
+      Err(Fault::AlreadyExists(_)) => continue,
+      Err(fault) => return Err(fault),
+    }
+  }
+
+  Err(Fault::Unexpected)
+}
+
+/// Redeeming these codes hands over or reopens the account, so they can only be created for users holding no more permissions than the admin
+fn requires_outranking(code_type: &OtpEnum) -> bool {
+  matches!(code_type, OtpEnum::PWRESET | OtpEnum::EMAILVERIFY | OtpEnum::UNLOCK)
+}
+
+fn build_template(new_otp: &NewOtp, payload: &RegistrationPayload, code_type: OtpEnum, tenant: &Tenant, created_by: Uuid) -> InsertableOtp {
+  InsertableOtp {
+    code_hash: String::new(),
+    code_prefix: String::new(),
+    tenant_id: tenant.tenant_id,
+    user: new_otp.user,
+    grant_admin: payload.admin,
+    restricted_to: payload.restricted_to.clone(),
+    groups: payload.groups.iter().cloned().map(Some).collect(),
+    account_expires_at: payload.account_expires_at,
+    email: match code_type {
+      OtpEnum::EMAILVERIFY => new_otp.email.as_ref().map(|e| e.trim().to_owned()),
+      _ => None,
+    },
+    code_type,
+    batch_id: None,
+    expires_at: new_otp.expires_at,
+    created_by: Some(created_by),
+  }
+}
+
+async fn create_otp(
+  state: &AppState,
+  tenant: &Tenant,
+  admin: &User,
+  permissions: &Permissions,
+  new_otp: NewOtp,
+  code_type: OtpEnum,
+) -> Result<(StatusCode, Json<CreatedOtp>), Fault> {
+  let payload = new_otp.payload.clone().unwrap_or_default();
+  validate_registration_payload(&payload)?;
+
+  if new_otp.expires_at.is_some_and(|expiry| expiry <= Utc::now()) {
+    return Err(Fault::Validation("expiresAt must be in the future".to_owned()));
+  }
+
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  if let Some(user) = new_otp.user {
+    ensure_user_in_tenant(&mut connection, tenant, user).await?;
+  }
+
+  if payload.admin {
+    ensure_can_grant_admin(&mut connection, permissions).await?;
+  }
+  ensure_can_join_groups(&mut connection, permissions, tenant.tenant_id, &payload.groups).await?;
+
+  if let (true, Some(user)) = (requires_outranking(&code_type), new_otp.user) {
+    ensure_outranks(&mut connection, permissions, user).await?;
+  }
+
+  let template = build_template(&new_otp, &payload, code_type, tenant, admin.user_id);
+
+  if let Some(custom_code) = &new_otp.code {
+    if !state.config.otp.allow_client_codes {
+      return Err(Fault::CustomOtpCodeDisabled);
+    }
+
+    validate_custom_code(custom_code)?;
+    let to_insert = InsertableOtp {
+      code_hash: state.config.otp.hash_code(custom_code),
+      code_prefix: code_prefix(custom_code),
+      ..template
+    };
+    let created = i_otp(&mut connection, to_insert).await?;
+
+    return Ok((StatusCode::CREATED, Json(CreatedOtp::from_internal(created, custom_code.to_owned()))));
+  }
+
+  let (created, code) = insert_generated_otp(&mut connection, &state.config.otp, &template).await?;
+
+  Ok((StatusCode::CREATED, Json(CreatedOtp::from_internal(created, code))))
+}
+
+async fn create_register_otp(
+  State(state): State<AppState>,
+  Extension(tenant): Extension<Tenant>,
+  Extension(admin): Extension<User>,
+  Extension(permissions): Extension<Permissions>,
+  Json(new_otp): Json<NewOtp>,
+) -> Result<(StatusCode, Json<CreatedOtp>), Fault> {
+  create_otp(&state, &tenant, &admin, &permissions, new_otp, OtpEnum::REGISTER).await
+}
+
+async fn create_password_otp(
+  State(state): State<AppState>,
+  Extension(tenant): Extension<Tenant>,
+  Extension(admin): Extension<User>,
+  Extension(permissions): Extension<Permissions>,
+  Json(new_otp): Json<NewOtp>,
+) -> Result<(StatusCode, Json<CreatedOtp>), Fault> {
+  if new_otp.user.is_none() {
+    return Err(Fault::MissingUserIdOtp);
+  }
+
+  if new_otp.payload.is_some() {
+    return Err(Fault::OtpPayloadUnsupported);
+  }
+
+  create_otp(&state, &tenant, &admin, &permissions, new_otp, OtpEnum::PWRESET).await
+}
+
+async fn create_email_verify_otp(
+  State(state): State<AppState>,
+  Extension(tenant): Extension<Tenant>,
+  Extension(admin): Extension<User>,
+  Extension(permissions): Extension<Permissions>,
+  Json(new_otp): Json<NewOtp>,
+) -> Result<(StatusCode, Json<CreatedOtp>), Fault> {
+  if new_otp.user.is_none() {
+    return Err(Fault::MissingUserIdOtp);
+  }
+
+  if !new_otp.email.as_ref().is_some_and(|e| is_valid_email(e.trim())) {
+    return Err(Fault::MissingEmailOtp);
+  }
+
+  if new_otp.payload.is_some() {
+    return Err(Fault::OtpPayloadUnsupported);
+  }
+
+  create_otp(&state, &tenant, &admin, &permissions, new_otp, OtpEnum::EMAILVERIFY).await
+}
+
+async fn create_unlock_otp(
+  State(state): State<AppState>,
+  Extension(tenant): Extension<Tenant>,
+  Extension(admin): Extension<User>,
+  Extension(permissions): Extension<Permissions>,
+  Json(new_otp): Json<NewOtp>,
+) -> Result<(StatusCode, Json<CreatedOtp>), Fault> {
+  if new_otp.user.is_none() {
+    return Err(Fault::MissingUserIdOtp);
+  }
+
+  if new_otp.payload.is_some() {
+    return Err(Fault::OtpPayloadUnsupported);
+  }
+
+  create_otp(&state, &tenant, &admin, &permissions, new_otp, OtpEnum::UNLOCK).await
+}
+
+#[derive(Deserialize, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum ExportFormat {
+  #[default]
+  Json,
+  Csv,
+}
+
+#[derive(Deserialize)]
+struct ExportQuery {
+  #[serde(default)]
+  format: ExportFormat,
+}
+
+#[derive(Serialize)]
+struct CreatedBatchResponse {
+  batch: OtpBatch,
+  codes: Vec<CreatedOtp>,
+}
+
+fn batch_to_csv(batch: &OtpBatch, codes: &[CreatedOtp]) -> String {
+  let label = batch.label.replace('"', "\"\"");
+  let expires_at = batch.expires_at.map(|e| e.to_rfc3339()).unwrap_or_default();
+
+  let mut csv = String::from("id,code,batch,label,expires_at\n");
+  for code in codes {
+    csv.push_str(&format!("{},{},{},\"{}\",{}\n", code.id, code.code, batch.batch_id, label, expires_at));
+  }
+
+  csv
+}
+
+async fn create_register_otp_batch(
+  State(state): State<AppState>,
+  Extension(tenant): Extension<Tenant>,
+  Extension(admin): Extension<User>,
+  Extension(permissions): Extension<Permissions>,
+  Query(export): Query<ExportQuery>,
+  Json(request): Json<NewOtpBatchRequest>,
+) -> Result<Response, Fault> {
+  if request.count == 0 || request.count > MAX_BATCH_SIZE {
+    return Err(Fault::Validation(format!("count must be between 1 and {MAX_BATCH_SIZE}")));
+  }
+
+  if request.label.is_empty() || request.label.len() > 128 {
+    return Err(Fault::Validation("label must be between 1 and 128 characters long".to_owned()));
+  }
+
+  if request.expires_at.is_some_and(|expiry| expiry <= Utc::now()) {
+    return Err(Fault::Validation("expiresAt must be in the future".to_owned()));
+  }
+
+  let payload = request.payload.clone().unwrap_or_default();
+  validate_registration_payload(&payload)?;
+
+  if payload.restricted_to.is_some() {
+    return Err(Fault::Validation("Codes of a batch can not be restricted to a single user".to_owned()));
+  }
+
+  let mut connection = state.pool.get_connection().await?.connection;
+  let config = state.config.clone();
+
+  if payload.admin {
+    ensure_can_grant_admin(&mut connection, &permissions).await?;
+  }
+  ensure_can_join_groups(&mut connection, &permissions, tenant.tenant_id, &payload.groups).await?;
+
+  // either the whole batch is created or none of it
+  let (batch, codes) = connection.transaction::<_, Fault, _>(|connection| async move {
+    let batch = i_otp_batch(connection, NewOtpBatch {
+      batch_id: Uuid::new_v4(),
+      tenant_id: tenant.tenant_id,
+      label: &request.label,
+      created_by: Some(admin.user_id),
+      expires_at: request.expires_at,
+    }).await?;
+
+    let template_request = NewOtp { code: None, user: None, email: None, payload: None, expires_at: request.expires_at };
+    let template = InsertableOtp {
+      batch_id: Some(batch.batch_id),
+      ..build_template(&template_request, &payload, OtpEnum::REGISTER, &tenant, admin.user_id)
+    };
+
+    let mut codes = Vec::with_capacity(request.count);
+    for _ in 0..request.count {
+      let (created, code) = insert_generated_otp(connection, &config.otp, &template).await?;
+      codes.push(CreatedOtp::from_internal(created, code));
+    }
+
+    Ok((batch, codes))
+  }.scope_boxed()).await?;
+
+  if export.format == ExportFormat::Csv {
+    let disposition = format!("attachment; filename=\"otp-batch-{}.csv\"", batch.batch_id);
+    let csv = batch_to_csv(&batch, &codes);
+
+    return Ok((
+      StatusCode::CREATED,
+      [(header::CONTENT_TYPE, "text/csv; charset=utf-8".to_owned()), (header::CONTENT_DISPOSITION, disposition)],
+      csv,
+    ).into_response());
+  }
+
+  Ok((StatusCode::CREATED, Json(CreatedBatchResponse { batch, codes })).into_response())
+}
+
+async fn list_otp_batches(
+  State(state): State<AppState>,
+  Extension(tenant): Extension<Tenant>,
+) -> Result<(StatusCode, Json<Vec<OtpBatch>>), Fault> {
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  let batches = q_otp_batches(&mut connection, tenant.tenant_id).await?;
+
+  Ok((StatusCode::OK, Json(batches)))
+}
+
+async fn revoke_otp_batch(
+  State(state): State<AppState>,
+  Path(batch_id): Path<Uuid>,
+) -> Result<StatusCode, Fault> {
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  connection.transaction::<_, Fault, _>(|connection| async move {
+    u_revoke_otp_batch(connection, batch_id).await
+  }.scope_boxed()).await?;
+
+  Ok(StatusCode::OK)
+}
+
+async fn list_otp(
+  State(state): State<AppState>,
+  Extension(tenant): Extension<Tenant>,
+  Query(filter): Query<OtpListQuery>,
+) -> Result<(StatusCode, Json<OtpPage>), Fault> {
+  let filter_type = match &filter.code_type {
+    Some(t) => Some(OtpEnum::from_str(t).map_err(|_| Fault::Validation(format!("Unknown code type '{t}'")))?),
+    None => None,
+  };
+  let page_size = filter.limit.unwrap_or(OTP_PAGE_SIZE).clamp(1, OTP_MAX_PAGE_SIZE);
+
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  let mut otp_list = q_otp_list(&mut connection, tenant.tenant_id, &filter, filter_type, page_size).await?;
+
+  // one more row than requested has been loaded to find out whether another page follows
+  let next_cursor = if otp_list.len() as i64 > page_size {
+    otp_list.truncate(page_size as usize);
+    otp_list.last().map(|otp| otp.id)
+  } else {
+    None
+  };
+
+  let mapped: Vec<OtpExternal> = otp_list.into_iter().map(|otp| otp.into()).collect();
+
+  Ok((StatusCode::OK, Json(OtpPage { otps: mapped, next_cursor })))
+}
+
+async fn delete_otp (
+  State(state): State<AppState>,
+  Path(otp_id): Path<i32>
+) -> Result<StatusCode, Fault> {
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  d_otp(&mut connection, otp_id).await?;
+
+  Ok(StatusCode::OK)
+}
+
+pub fn router(state: AppState) -> Router<AppState> {
+  Router::new()
+    .route("/otp",
+      get(list_otp)
+      .layer(require_permission(&state, "otp.read"))
+    )
+    .route("/otp/register",
+      post(create_register_otp)
+      .layer(require_permission(&state, "otp.create"))
+    )
+    .route("/otp/password",
+      post(create_password_otp)
+      .layer(require_permission(&state, "otp.create"))
+    )
+    .route("/otp/register/batch",
+      post(create_register_otp_batch)
+      .layer(require_permission(&state, "otp.create"))
+    )
+    .route("/otp/batches",
+      get(list_otp_batches)
+      .layer(require_permission(&state, "otp.read"))
+    )
+    .route("/otp/batches/{batch_id}",
+      delete(revoke_otp_batch)
+      .layer(require_permission(&state, "otp.revoke"))
+    )
+    .route("/otp/email-verify",
+      post(create_email_verify_otp)
+      .layer(require_permission(&state, "otp.create"))
+    )
+    .route("/otp/unlock",
+      post(create_unlock_otp)
+      .layer(require_permission(&state, "otp.create"))
+    )
+    .route("/otp/{otp_id}",
+      delete(delete_otp)
+      .layer(require_permission(&state, "otp.revoke"))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::models::otp::OtpEnum;
+
+  use super::requires_outranking;
+
+  #[test]
+  fn codes_bound_to_an_account_require_outranking() {
+    assert!(requires_outranking(&OtpEnum::PWRESET));
+    assert!(requires_outranking(&OtpEnum::EMAILVERIFY));
+    assert!(requires_outranking(&OtpEnum::UNLOCK));
+  }
+
+  #[test]
+  fn registration_codes_do_not_require_outranking() {
+    assert!(!requires_outranking(&OtpEnum::REGISTER));
+  }
+}
@@ -0,0 +1,220 @@
+use diesel::prelude::*;
+use diesel::result::{DatabaseErrorKind, Error::DatabaseError};
+use diesel_async::RunQueryDsl;
+use diesel_async::AsyncPgConnection;
+use uuid::Uuid;
+
+use crate::api::group::queries::q_effective_group_ids_of_user;
+use crate::models::role::{GrantedRole, NewRole, NewRolePermission, NewUserRole, Permission, Role, RoleChangeset, ADMIN_ROLE};
+use crate::utils::error::Fault;
+
+type Conn = AsyncPgConnection;
+
+pub async fn q_permission_catalog(connection: &mut Conn) -> Result<Vec<Permission>, Fault> {
+  use crate::schema::permissions::dsl::*;
+
+  permissions
+    .order(name.asc())
+    .select(Permission::as_select())
+    .load::<Permission>(connection)
+    .await
+    .map_err(|_| Fault::Diesel)
+}
+
+/// All permissions granted to a user by their own roles and the roles of the groups they belong to
+pub async fn q_permissions_of_user(connection: &mut Conn, user: Uuid) -> Result<Vec<String>, Fault> {
+  use crate::schema::{role_permissions, user_roles, group_roles};
+
+  let mut granted = role_permissions::table
+    .inner_join(user_roles::table.on(user_roles::role_id.eq(role_permissions::role_id)))
+    .filter(user_roles::user_id.eq(user))
+    .select(role_permissions::permission)
+    .distinct()
+    .load::<String>(connection)
+    .await
+    .map_err(|_| Fault::Diesel)?;
+
+  let groups = q_effective_group_ids_of_user(connection, user).await?;
+  if !groups.is_empty() {
+    let via_groups = role_permissions::table
+      .inner_join(group_roles::table.on(group_roles::role_id.eq(role_permissions::role_id)))
+      .filter(group_roles::group_id.eq_any(&groups))
+      .select(role_permissions::permission)
+      .distinct()
+      .load::<String>(connection)
+      .await
+      .map_err(|_| Fault::Diesel)?;
+
+    granted.extend(via_groups);
+    granted.sort();
+    granted.dedup();
+  }
+
+  Ok(granted)
+}
+
+/// The system roles and the custom roles of a tenant
+pub async fn q_roles(connection: &mut Conn, tenant: Uuid) -> Result<Vec<Role>, Fault> {
+  use crate::schema::roles::dsl::*;
+
+  roles
+    .filter(tenant_id.is_null().or(tenant_id.eq(tenant)))
+    .order(name.asc())
+    .select(Role::as_select())
+    .load::<Role>(connection)
+    .await
+    .map_err(|_| Fault::Diesel)
+}
+
+pub async fn q_role(connection: &mut Conn, role: Uuid) -> Result<Role, Fault> {
+  use crate::schema::roles::dsl::*;
+
+  roles
+    .filter(role_id.eq(role))
+    .select(Role::as_select())
+    .first::<Role>(connection)
+    .await
+    .map_err(|_| Fault::NotFound("Role".to_string()))
+}
+
+pub async fn q_admin_role(connection: &mut Conn) -> Result<Role, Fault> {
+  q_system_role(connection, ADMIN_ROLE).await
+}
+
+pub async fn q_system_role(connection: &mut Conn, wanted: &str) -> Result<Role, Fault> {
+  use crate::schema::roles::dsl::*;
+
+  roles
+    .filter(name.eq(wanted))
+    .filter(system.eq(true))
+    .select(Role::as_select())
+    .first::<Role>(connection)
+    .await
+    .map_err(|_| Fault::Diesel)
+}
+
+/// Whether a system role has this name, custom roles of all tenants have to keep apart from system roles
+pub async fn q_is_system_role_name(connection: &mut Conn, wanted: &str) -> Result<bool, Fault> {
+  use crate::schema::roles::dsl::*;
+
+  diesel::select(diesel::dsl::exists(roles.filter(system.eq(true)).filter(name.eq(wanted))))
+    .get_result::<bool>(connection)
+    .await
+    .map_err(|_| Fault::Diesel)
+}
+
+/// The permissions of the given roles as pairs of role and permission
+pub async fn q_permissions_of_roles(connection: &mut Conn, roles: &[Uuid]) -> Result<Vec<(Uuid, String)>, Fault> {
+  use crate::schema::role_permissions::dsl::*;
+
+  role_permissions
+    .filter(role_id.eq_any(roles))
+    .order((role_id.asc(), permission.asc()))
+    .select((role_id, permission))
+    .load::<(Uuid, String)>(connection)
+    .await
+    .map_err(|_| Fault::Diesel)
+}
+
+pub async fn i_role(connection: &mut Conn, new_role: NewRole<'_>) -> Result<Role, Fault> {
+  use crate::schema::roles;
+
+  diesel::insert_into(roles::table)
+    .values(new_role)
+    .returning(Role::as_returning())
+    .get_result::<Role>(connection)
+    .await
+    .map_err(|error| match error {
+      DatabaseError(DatabaseErrorKind::UniqueViolation, _) => Fault::AlreadyExists("Role".to_string()),
+      _ => Fault::Diesel,
+    })
+}
+
+/// Updates a role that is not a system role
+pub async fn u_role(connection: &mut Conn, role: Uuid, changes: RoleChangeset) -> Result<Role, Fault> {
+  use crate::schema::roles::dsl::*;
+
+  diesel::update(roles.filter(role_id.eq(role)).filter(system.eq(false)))
+    .set(changes)
+    .returning(Role::as_returning())
+    .get_result::<Role>(connection)
+    .await
+    .map_err(|error| match error {
+      DatabaseError(DatabaseErrorKind::UniqueViolation, _) => Fault::AlreadyExists("Role".to_string()),
+      diesel::result::Error::NotFound => Fault::SystemRole,
+      _ => Fault::Diesel,
+    })
+}
+
+/// Replaces the permissions of a role
+pub async fn u_role_permissions(connection: &mut Conn, role: Uuid, granted: &[String]) -> Result<(), Fault> {
+  use crate::schema::role_permissions::dsl::*;
+
+  diesel::delete(role_permissions.filter(role_id.eq(role)))
+    .execute(connection)
+    .await
+    .map_err(|_| Fault::Diesel)?;
+
+  let to_insert: Vec<NewRolePermission> = granted.iter()
+    .map(|p| NewRolePermission { role_id: role, permission: p })
+    .collect();
+
+  diesel::insert_into(role_permissions)
+    .values(to_insert)
+    .execute(connection)
+    .await
+    .map_err(|_| Fault::Diesel)
+    .map(|_| ())
+}
+
+/// Deletes a role that is not a system role, the users holding it lose it
+pub async fn d_role(connection: &mut Conn, role: Uuid) -> Result<(), Fault> {
+  use crate::schema::roles::dsl::*;
+
+  let deleted = diesel::delete(roles.filter(role_id.eq(role)).filter(system.eq(false)))
+    .execute(connection)
+    .await
+    .map_err(|_| Fault::Diesel)?;
+
+  match deleted {
+    0 => Err(Fault::SystemRole),
+    _ => Ok(()),
+  }
+}
+
+pub async fn q_roles_of_user(connection: &mut Conn, user: Uuid) -> Result<Vec<GrantedRole>, Fault> {
+  use crate::schema::{roles, user_roles};
+
+  user_roles::table
+    .inner_join(roles::table)
+    .filter(user_roles::user_id.eq(user))
+    .order(roles::name.asc())
+    .select((roles::role_id, roles::name, user_roles::granted_by, user_roles::granted_at))
+    .load::<GrantedRole>(connection)
+    .await
+    .map_err(|_| Fault::Diesel)
+}
+
+/// Grants a role, returns whether the user did not hold it yet
+pub async fn i_user_role(connection: &mut Conn, grant: NewUserRole) -> Result<bool, Fault> {
+  use crate::schema::user_roles;
+
+  diesel::insert_into(user_roles::table)
+    .values(grant)
+    .on_conflict_do_nothing()
+    .execute(connection)
+    .await
+    .map_err(|_| Fault::Diesel)
+    .map(|inserted| inserted > 0)
+}
+
+/// Revokes a role, returns whether the user held it
+pub async fn d_user_role(connection: &mut Conn, user: Uuid, role: Uuid) -> Result<bool, Fault> {
+  use crate::schema::user_roles::dsl::*;
+
+  diesel::delete(user_roles.filter(user_id.eq(user)).filter(role_id.eq(role)))
+    .execute(connection)
+    .await
+    .map_err(|_| Fault::Diesel)
+    .map(|deleted| deleted > 0)
+}
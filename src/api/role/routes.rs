@@ -0,0 +1,434 @@
+use std::collections::BTreeSet;
+
+use axum::{
+  Router,
+  routing::{get, patch, post, put},
+  Extension,
+  extract::{State, Path, Query},
+  http::StatusCode,
+  Json,
+};
+use diesel_async::{AsyncConnection, AsyncPgConnection, scoped_futures::ScopedFutureExt};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+  api::{audit::queries::i_audit_event, auth::queries::q_get_user_by_id, group::queries::{d_group_role, i_group_role, q_group, q_roles_of_group}, user::safeguards::{ensure_confirmed_if_self, ensure_other_admin_remains}},
+  middleware::authorized::require_permission,
+  models::{audit::{AuditAction, NewAuditEvent}, group::NewGroupRole, role::{GrantedRole, NewRole, NewRoleRequest, NewUserRole, Permission, Permissions, Role, RoleChangeset, RoleInfo, RolePatch, ADMIN_ROLE}, tenant::Tenant, user::{Confirmation, User}},
+  state::AppState,
+  utils::{error::Fault, validation::{MAX_ROLE_DESCRIPTION_LENGTH, MAX_ROLE_NAME_LENGTH}},
+};
+
+use super::queries::{d_role, d_user_role, i_role, i_user_role, q_permission_catalog, q_permissions_of_roles, q_is_system_role_name, q_role, q_roles, q_roles_of_user, u_role, u_role_permissions};
+
+fn validate_role_name(name: &str) -> Result<(), Fault> {
+  if name.trim().is_empty() || name.trim() != name || name.chars().count() > MAX_ROLE_NAME_LENGTH {
+    return Err(Fault::Validation(format!("A role name must consist of 1 to {MAX_ROLE_NAME_LENGTH} characters without surrounding whitespace")));
+  }
+
+  Ok(())
+}
+
+fn validate_role_description(description: &str) -> Result<(), Fault> {
+  if description.chars().count() > MAX_ROLE_DESCRIPTION_LENGTH {
+    return Err(Fault::Validation(format!("A role description may consist of at most {MAX_ROLE_DESCRIPTION_LENGTH} characters")));
+  }
+
+  Ok(())
+}
+
+/// Custom roles are unique per tenant by the database, but have to keep apart from the system roles shared by all tenants
+async fn ensure_role_name_free(connection: &mut AsyncPgConnection, name: &str) -> Result<(), Fault> {
+  match q_is_system_role_name(connection, name).await? {
+    true => Err(Fault::AlreadyExists("Role".to_string())),
+    false => Ok(()),
+  }
+}
+
+/// Deduplicates the requested permissions and makes sure they are part of the catalog
+async fn validate_permissions(connection: &mut AsyncPgConnection, requested: Vec<String>) -> Result<Vec<String>, Fault> {
+  let catalog: BTreeSet<String> = q_permission_catalog(connection).await?.into_iter().map(|p| p.name).collect();
+  let requested: BTreeSet<String> = requested.into_iter().collect();
+
+  match requested.iter().find(|permission| !catalog.contains(*permission)) {
+    Some(unknown) => Err(Fault::Validation(format!("The permission '{unknown}' does not exist"))),
+    None => Ok(requested.into_iter().collect()),
+  }
+}
+
+async fn permissions_of_role(connection: &mut AsyncPgConnection, role: Uuid) -> Result<Vec<String>, Fault> {
+  let granted = q_permissions_of_roles(connection, &[role]).await?;
+
+  Ok(granted.into_iter().map(|(_, permission)| permission).collect())
+}
+
+async fn get_permissions(
+  State(state): State<AppState>,
+) -> Result<(StatusCode, Json<Vec<Permission>>), Fault> {
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  let catalog = q_permission_catalog(&mut connection).await?;
+
+  Ok((StatusCode::OK, Json(catalog)))
+}
+
+async fn get_roles(
+  State(state): State<AppState>,
+  Extension(tenant): Extension<Tenant>,
+) -> Result<(StatusCode, Json<Vec<RoleInfo>>), Fault> {
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  let roles = q_roles(&mut connection, tenant.tenant_id).await?;
+  let role_ids: Vec<Uuid> = roles.iter().map(|r| r.role_id).collect();
+  let granted = q_permissions_of_roles(&mut connection, &role_ids).await?;
+
+  let response = roles.into_iter()
+    .map(|role| {
+      let permissions = granted.iter().filter(|(id, _)| *id == role.role_id).map(|(_, p)| p.clone()).collect();
+      RoleInfo::new(role, permissions)
+    })
+    .collect();
+
+  Ok((StatusCode::OK, Json(response)))
+}
+
+async fn get_role(
+  State(state): State<AppState>,
+  Path(role_id): Path<Uuid>,
+) -> Result<(StatusCode, Json<RoleInfo>), Fault> {
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  let role = q_role(&mut connection, role_id).await?;
+  let permissions = permissions_of_role(&mut connection, role_id).await?;
+
+  Ok((StatusCode::OK, Json(RoleInfo::new(role, permissions))))
+}
+
+async fn create_role(
+  State(state): State<AppState>,
+  Extension(tenant): Extension<Tenant>,
+  Extension(actor): Extension<User>,
+  Extension(held): Extension<Permissions>,
+  Json(request): Json<NewRoleRequest>,
+) -> Result<(StatusCode, Json<RoleInfo>), Fault> {
+  validate_role_name(&request.name)?;
+  if let Some(description) = &request.description {
+    validate_role_description(description)?;
+  }
+
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  let info = connection.transaction::<_, Fault, _>(|connection| async move {
+    let permissions = validate_permissions(connection, request.permissions).await?;
+    held.ensure_all(&permissions)?;
+    ensure_role_name_free(connection, &request.name).await?;
+
+    let role = i_role(connection, NewRole {
+      role_id: Uuid::new_v4(),
+      tenant_id: tenant.tenant_id,
+      name: &request.name,
+      description: request.description.as_deref(),
+    }).await?;
+    u_role_permissions(connection, role.role_id, &permissions).await?;
+
+    i_audit_event(connection, NewAuditEvent::new(
+      AuditAction::RoleCreated,
+      Some(actor.user_id),
+      None,
+      json!({ "roleId": role.role_id, "name": role.name, "permissions": permissions }),
+    )).await?;
+
+    Ok(RoleInfo::new(role, permissions))
+  }.scope_boxed()).await?;
+
+  Ok((StatusCode::CREATED, Json(info)))
+}
+
+async fn update_role(
+  State(state): State<AppState>,
+  Extension(actor): Extension<User>,
+  Extension(held): Extension<Permissions>,
+  Path(role_id): Path<Uuid>,
+  Json(patch): Json<RolePatch>,
+) -> Result<(StatusCode, Json<RoleInfo>), Fault> {
+  if let Some(name) = &patch.name {
+    validate_role_name(name)?;
+  }
+  if let Some(Some(description)) = &patch.description {
+    validate_role_description(description)?;
+  }
+
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  let info = connection.transaction::<_, Fault, _>(|connection| async move {
+    let role = q_role(connection, role_id).await?;
+    if role.system {
+      return Err(Fault::SystemRole);
+    }
+
+    // whoever edits a role could hand out or take away each of its permissions
+    let mut permissions = permissions_of_role(connection, role_id).await?;
+    held.ensure_all(&permissions)?;
+
+    if let Some(requested) = patch.permissions {
+      permissions = validate_permissions(connection, requested).await?;
+      held.ensure_all(&permissions)?;
+      u_role_permissions(connection, role_id, &permissions).await?;
+    }
+
+    if let Some(name) = &patch.name {
+      ensure_role_name_free(connection, name).await?;
+    }
+
+    let role = match (patch.name, patch.description) {
+      (None, None) => role,
+      (name, description) => u_role(connection, role_id, RoleChangeset { name, description }).await?,
+    };
+
+    i_audit_event(connection, NewAuditEvent::new(
+      AuditAction::RoleUpdated,
+      Some(actor.user_id),
+      None,
+      json!({ "roleId": role.role_id, "name": role.name, "permissions": permissions }),
+    )).await?;
+
+    Ok(RoleInfo::new(role, permissions))
+  }.scope_boxed()).await?;
+
+  Ok((StatusCode::OK, Json(info)))
+}
+
+async fn delete_role(
+  State(state): State<AppState>,
+  Extension(actor): Extension<User>,
+  Extension(held): Extension<Permissions>,
+  Path(role_id): Path<Uuid>,
+) -> Result<StatusCode, Fault> {
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  connection.transaction::<_, Fault, _>(|connection| async move {
+    let role = q_role(connection, role_id).await?;
+    if role.system {
+      return Err(Fault::SystemRole);
+    }
+
+    held.ensure_all(&permissions_of_role(connection, role_id).await?)?;
+
+    d_role(connection, role_id).await?;
+
+    i_audit_event(connection, NewAuditEvent::new(
+      AuditAction::RoleDeleted,
+      Some(actor.user_id),
+      None,
+      json!({ "roleId": role.role_id, "name": role.name }),
+    )).await
+  }.scope_boxed()).await?;
+
+  Ok(StatusCode::OK)
+}
+
+async fn get_user_roles(
+  State(state): State<AppState>,
+  Path(user_id): Path<Uuid>,
+) -> Result<(StatusCode, Json<Vec<GrantedRole>>), Fault> {
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  q_get_user_by_id(&mut connection, user_id).await?;
+  let roles = q_roles_of_user(&mut connection, user_id).await?;
+
+  Ok((StatusCode::OK, Json(roles)))
+}
+
+/// Only roles whose permissions the acting user holds can be granted or revoked
+async fn assignable_role(connection: &mut AsyncPgConnection, held: &Permissions, role_id: Uuid) -> Result<Role, Fault> {
+  let role = q_role(connection, role_id).await?;
+
+  held.ensure_all(&permissions_of_role(connection, role_id).await?)?;
+
+  Ok(role)
+}
+
+async fn grant_role(
+  State(state): State<AppState>,
+  Extension(actor): Extension<User>,
+  Extension(held): Extension<Permissions>,
+  Path((user_id, role_id)): Path<(Uuid, Uuid)>,
+) -> Result<(StatusCode, Json<Vec<GrantedRole>>), Fault> {
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  let roles = connection.transaction::<_, Fault, _>(|connection| async move {
+    let role = assignable_role(connection, &held, role_id).await?;
+    q_get_user_by_id(connection, user_id).await?;
+
+    let granted = i_user_role(connection, NewUserRole { user_id, role_id, granted_by: Some(actor.user_id) }).await?;
+
+    if granted {
+      i_audit_event(connection, NewAuditEvent::new(
+        AuditAction::RoleGranted,
+        Some(actor.user_id),
+        Some(user_id),
+        json!({ "roleId": role.role_id, "name": role.name }),
+      )).await?;
+    }
+
+    q_roles_of_user(connection, user_id).await
+  }.scope_boxed()).await?;
+
+  Ok((StatusCode::OK, Json(roles)))
+}
+
+async fn revoke_role(
+  State(state): State<AppState>,
+  Extension(actor): Extension<User>,
+  Extension(held): Extension<Permissions>,
+  Path((user_id, role_id)): Path<(Uuid, Uuid)>,
+  Query(confirmation): Query<Confirmation>,
+) -> Result<(StatusCode, Json<Vec<GrantedRole>>), Fault> {
+  ensure_confirmed_if_self(actor.user_id, user_id, confirmation.confirm)?;
+
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  let roles = connection.transaction::<_, Fault, _>(|connection| async move {
+    let role = assignable_role(connection, &held, role_id).await?;
+    q_get_user_by_id(connection, user_id).await?;
+
+    if role.system && role.name == ADMIN_ROLE {
+      ensure_other_admin_remains(connection, user_id).await?;
+    }
+
+    let revoked = d_user_role(connection, user_id, role_id).await?;
+
+    if revoked {
+      i_audit_event(connection, NewAuditEvent::new(
+        AuditAction::RoleRevoked,
+        Some(actor.user_id),
+        Some(user_id),
+        json!({ "roleId": role.role_id, "name": role.name }),
+      )).await?;
+    }
+
+    q_roles_of_user(connection, user_id).await
+  }.scope_boxed()).await?;
+
+  Ok((StatusCode::OK, Json(roles)))
+}
+
+async fn get_group_roles(
+  State(state): State<AppState>,
+  Path(group_id): Path<Uuid>,
+) -> Result<(StatusCode, Json<Vec<GrantedRole>>), Fault> {
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  q_group(&mut connection, group_id).await?;
+  let roles = q_roles_of_group(&mut connection, group_id).await?;
+
+  Ok((StatusCode::OK, Json(roles)))
+}
+
+/// Grants a role to all members of a group and of the groups below it.
+/// The admin role is only granted to users, as `users.admin` and the last admin safeguard rely on holding it directly.
+async fn grant_group_role(
+  State(state): State<AppState>,
+  Extension(actor): Extension<User>,
+  Extension(held): Extension<Permissions>,
+  Path((group_id, role_id)): Path<(Uuid, Uuid)>,
+) -> Result<(StatusCode, Json<Vec<GrantedRole>>), Fault> {
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  let roles = connection.transaction::<_, Fault, _>(|connection| async move {
+    let role = assignable_role(connection, &held, role_id).await?;
+    if role.system && role.name == ADMIN_ROLE {
+      return Err(Fault::Validation("The admin role can only be granted to users".to_string()));
+    }
+
+    let group = q_group(connection, group_id).await?;
+
+    let granted = i_group_role(connection, NewGroupRole { group_id, role_id, granted_by: Some(actor.user_id) }).await?;
+
+    if granted {
+      i_audit_event(connection, NewAuditEvent::new(
+        AuditAction::RoleGranted,
+        Some(actor.user_id),
+        None,
+        json!({ "roleId": role.role_id, "name": role.name, "groupId": group.group_id, "group": group.name }),
+      )).await?;
+    }
+
+    q_roles_of_group(connection, group_id).await
+  }.scope_boxed()).await?;
+
+  Ok((StatusCode::OK, Json(roles)))
+}
+
+async fn revoke_group_role(
+  State(state): State<AppState>,
+  Extension(actor): Extension<User>,
+  Extension(held): Extension<Permissions>,
+  Path((group_id, role_id)): Path<(Uuid, Uuid)>,
+) -> Result<(StatusCode, Json<Vec<GrantedRole>>), Fault> {
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  let roles = connection.transaction::<_, Fault, _>(|connection| async move {
+    let role = assignable_role(connection, &held, role_id).await?;
+    let group = q_group(connection, group_id).await?;
+
+    let revoked = d_group_role(connection, group_id, role_id).await?;
+
+    if revoked {
+      i_audit_event(connection, NewAuditEvent::new(
+        AuditAction::RoleRevoked,
+        Some(actor.user_id),
+        None,
+        json!({ "roleId": role.role_id, "name": role.name, "groupId": group.group_id, "group": group.name }),
+      )).await?;
+    }
+
+    q_roles_of_group(connection, group_id).await
+  }.scope_boxed()).await?;
+
+  Ok((StatusCode::OK, Json(roles)))
+}
+
+pub fn router(state: AppState) -> Router<AppState> {
+  Router::new()
+    .route("/permissions",
+      get(get_permissions)
+      .layer(require_permission(&state, "roles.read"))
+    )
+    .route("/roles",
+      get(get_roles)
+      .layer(require_permission(&state, "roles.read"))
+    )
+    .route("/roles",
+      post(create_role)
+      .layer(require_permission(&state, "roles.manage"))
+    )
+    .route("/roles/{role_id}",
+      get(get_role)
+      .layer(require_permission(&state, "roles.read"))
+    )
+    .route("/roles/{role_id}",
+      patch(update_role)
+        .delete(delete_role)
+        .layer(require_permission(&state, "roles.manage"))
+    )
+    .route("/users/{user_id}/roles",
+      get(get_user_roles)
+      .layer(require_permission(&state, "roles.read"))
+    )
+    .route("/users/{user_id}/roles/{role_id}",
+      put(grant_role)
+        .delete(revoke_role)
+        .layer(require_permission(&state, "roles.assign"))
+    )
+    .route("/groups/{group_id}/roles",
+      get(get_group_roles)
+      .layer(require_permission(&state, "roles.read"))
+    )
+    .route("/groups/{group_id}/roles/{role_id}",
+      put(grant_group_role)
+        .delete(revoke_group_role)
+        .layer(require_permission(&state, "roles.assign"))
+    )
+}
@@ -0,0 +1,40 @@
+use diesel::{prelude::*, update};
+use diesel_async::RunQueryDsl;
+use uuid::Uuid;
+
+use crate::{utils::username::canonical_username, PgPool};
+
+/// Recomputes canonical usernames that differ from the server's normalization.
+/// The migration only approximates them in SQL, which lacks full Unicode case folding.
+/// Returns the amount of updated users, names that would collide with another user are skipped and printed.
+pub async fn canonicalize_usernames(pool: &PgPool) -> Result<usize, ()> {
+  use crate::schema::users::dsl::*;
+
+  let mut connection = pool.get().await.map_err(|_| ())?;
+
+  let names: Vec<(Uuid, String, String)> = users
+    .select((user_id, username, username_canonical))
+    .load(&mut connection)
+    .await
+    .map_err(|_| ())?;
+
+  let mut updated = 0;
+  for (id, name, stored) in names.iter() {
+    let canonical = canonical_username(name);
+    if &canonical == stored {
+      continue;
+    }
+
+    let result = update(users.filter(user_id.eq(id)))
+      .set(username_canonical.eq(&canonical))
+      .execute(&mut connection)
+      .await;
+
+    match result {
+      Ok(_) => updated += 1,
+      Err(_) => println!("The username '{}' collides with another user once normalized, please rename one of them", name),
+    }
+  }
+
+  Ok(updated)
+}
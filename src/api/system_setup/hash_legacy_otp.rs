@@ -0,0 +1,35 @@
+use diesel::{prelude::*, update};
+use diesel_async::RunQueryDsl;
+
+use crate::{state::config::OtpConfig, utils::otp_code::code_prefix as prefix_of, PgPool};
+
+/// Replaces codes that are still stored in plaintext (created before codes were hashed) with their keyed hash.
+/// Returns the amount of codes that have been migrated.
+pub async fn hash_legacy_codes(pool: &PgPool, config: &OtpConfig) -> Result<usize, ()> {
+  use crate::schema::otp::dsl::*;
+
+  let mut connection = pool.get().await.map_err(|_| ())?;
+
+  let legacy_codes: Vec<(i32, Option<String>)> = otp
+    .filter(legacy_code.is_not_null())
+    .select((id, legacy_code))
+    .load(&mut connection)
+    .await
+    .map_err(|_| ())?;
+
+  for (otp_id, plaintext) in legacy_codes.iter() {
+    let plaintext = plaintext.as_deref().unwrap_or_default();
+
+    update(otp.filter(id.eq(otp_id)))
+      .set((
+        code_hash.eq(config.hash_code(plaintext)),
+        code_prefix.eq(prefix_of(plaintext)),
+        legacy_code.eq(None::<String>),
+      ))
+      .execute(&mut connection)
+      .await
+      .map_err(|_| ())?;
+  }
+
+  Ok(legacy_codes.len())
+}
@@ -1,34 +1,44 @@
 use diesel_async::RunQueryDsl;
+use uuid::Uuid;
 
-use crate::{models::user::NewAdmUser, api::auth::password::hash_password, PgPool};
+use crate::{models::{role::{NewUserRole, SUPERADMIN_ROLE}, tenant::DEFAULT_TENANT, user::NewAdmUser}, api::{auth::password::hash_password, role::queries::{i_user_role, q_system_role}, tenant::queries::q_tenant_by_slug}, utils::username::canonical_username, PgPool};
 
-fn get_default_admin_user () -> NewAdmUser {
+fn get_default_admin_user (tenant_id: Uuid) -> NewAdmUser {
   let username = std::env::var("ADMIN_USER").expect("ADMIN_USER environment config should be set!");
   let password = std::env::var("ADMIN_PASSWORD").expect("ADMIN_PASSWORD environment config should be set!");
 
   NewAdmUser {
     user_id: uuid::Uuid::new_v4(),
+    tenant_id,
+    username_canonical: canonical_username(&username),
     username,
     password: hash_password(password).expect("Failed to hash a password!"),
-    admin: Some(true)
+    admin: true
   }
 }
 
+/// Creates the admin of the default tenant, who is super-admin and thereby manages the other tenants
 pub async fn setup(pool: &PgPool) -> Result<(), ()> {
   use crate::schema::users;
 
-  let adm_user = get_default_admin_user();
   let mut connection = pool.get().await.unwrap();
+  let tenant = q_tenant_by_slug(&mut connection, DEFAULT_TENANT).await.map_err(|_| ())?;
+  let adm_user = get_default_admin_user(tenant.tenant_id);
+  let user_id = adm_user.user_id;
   
   let inserted = diesel::insert_into(users::table)
   .values(adm_user)
-  .on_conflict(users::username)
+  .on_conflict((users::tenant_id, users::username_canonical))
   .do_nothing()
   .execute(&mut connection)
-  .await.or_else(|_| Err(()))?;
+  .await.map_err(|_| ())?;
 
-  match inserted {
-    0 => Err(()),
-    _ => Ok(())
+  if inserted == 0 {
+    return Err(());
   }
-}
\ No newline at end of file
+
+  let superadmin = q_system_role(&mut connection, SUPERADMIN_ROLE).await.map_err(|_| ())?;
+  i_user_role(&mut connection, NewUserRole { user_id, role_id: superadmin.role_id, granted_by: None }).await.map_err(|_| ())?;
+
+  Ok(())
+}
@@ -1 +1,3 @@
 pub mod init_admin_user;
+pub mod hash_legacy_otp;
+pub mod canonical_usernames;
@@ -0,0 +1,5 @@
+pub mod routes;
+
+pub mod queries;
+
+pub mod scope;
@@ -0,0 +1,148 @@
+use diesel::prelude::*;
+use diesel::result::{DatabaseErrorKind, Error::DatabaseError};
+use diesel_async::RunQueryDsl;
+use diesel_async::AsyncPgConnection;
+use uuid::Uuid;
+
+use crate::models::tenant::{NewTenant, Tenant, TenantPatch};
+use crate::utils::error::Fault;
+
+type Conn = AsyncPgConnection;
+
+pub async fn q_tenants(connection: &mut Conn) -> Result<Vec<Tenant>, Fault> {
+  use crate::schema::tenants::dsl::*;
+
+  tenants
+    .order(slug.asc())
+    .select(Tenant::as_select())
+    .load::<Tenant>(connection)
+    .await
+    .map_err(|_| Fault::Diesel)
+}
+
+pub async fn q_tenant(connection: &mut Conn, tenant: Uuid) -> Result<Tenant, Fault> {
+  use crate::schema::tenants::dsl::*;
+
+  tenants
+    .filter(tenant_id.eq(tenant))
+    .select(Tenant::as_select())
+    .first::<Tenant>(connection)
+    .await
+    .map_err(|_| Fault::NotFound("Tenant".to_string()))
+}
+
+pub async fn q_tenant_by_slug(connection: &mut Conn, wanted: &str) -> Result<Tenant, Fault> {
+  use crate::schema::tenants::dsl::*;
+
+  tenants
+    .filter(slug.eq(wanted))
+    .select(Tenant::as_select())
+    .first::<Tenant>(connection)
+    .await
+    .map_err(|_| Fault::NotFound("Tenant".to_string()))
+}
+
+pub async fn i_tenant(connection: &mut Conn, new_tenant: NewTenant<'_>) -> Result<Tenant, Fault> {
+  use crate::schema::tenants;
+
+  diesel::insert_into(tenants::table)
+    .values(new_tenant)
+    .returning(Tenant::as_returning())
+    .get_result::<Tenant>(connection)
+    .await
+    .map_err(|error| match error {
+      DatabaseError(DatabaseErrorKind::UniqueViolation, _) => Fault::AlreadyExists("Tenant".to_string()),
+      _ => Fault::Diesel,
+    })
+}
+
+pub async fn u_tenant(connection: &mut Conn, tenant: Uuid, changes: TenantPatch) -> Result<Tenant, Fault> {
+  use crate::schema::tenants::dsl::*;
+
+  diesel::update(tenants.filter(tenant_id.eq(tenant)))
+    .set(changes)
+    .returning(Tenant::as_returning())
+    .get_result::<Tenant>(connection)
+    .await
+    .map_err(|_| Fault::NotFound("Tenant".to_string()))
+}
+
+/// Deletes a tenant without users, its codes, groups and roles are removed with it
+pub async fn d_tenant(connection: &mut Conn, tenant: Uuid) -> Result<(), Fault> {
+  use crate::schema::tenants::dsl::*;
+
+  let deleted = diesel::delete(tenants.filter(tenant_id.eq(tenant)))
+    .execute(connection)
+    .await
+    .map_err(|error| match error {
+      DatabaseError(DatabaseErrorKind::ForeignKeyViolation, _) => Fault::TenantNotEmpty,
+      _ => Fault::Diesel,
+    })?;
+
+  match deleted {
+    0 => Err(Fault::NotFound("Tenant".to_string())),
+    _ => Ok(()),
+  }
+}
+
+/// The tenant of a user including deleted ones, `None` if the user does not exist
+pub async fn q_tenant_of_user(connection: &mut Conn, user: Uuid) -> Result<Option<Uuid>, Fault> {
+  use crate::schema::users::dsl::*;
+
+  users
+    .filter(user_id.eq(user))
+    .select(tenant_id)
+    .first::<Uuid>(connection)
+    .await
+    .optional()
+    .map_err(|_| Fault::Diesel)
+}
+
+pub async fn q_tenant_of_group(connection: &mut Conn, group: Uuid) -> Result<Option<Uuid>, Fault> {
+  use crate::schema::groups::dsl::*;
+
+  groups
+    .filter(group_id.eq(group))
+    .select(tenant_id)
+    .first::<Uuid>(connection)
+    .await
+    .optional()
+    .map_err(|_| Fault::Diesel)
+}
+
+/// The tenant of a role, `Some(None)` for system roles that are shared by all tenants
+pub async fn q_tenant_of_role(connection: &mut Conn, role: Uuid) -> Result<Option<Option<Uuid>>, Fault> {
+  use crate::schema::roles::dsl::*;
+
+  roles
+    .filter(role_id.eq(role))
+    .select(tenant_id)
+    .first::<Option<Uuid>>(connection)
+    .await
+    .optional()
+    .map_err(|_| Fault::Diesel)
+}
+
+pub async fn q_tenant_of_otp_batch(connection: &mut Conn, batch: Uuid) -> Result<Option<Uuid>, Fault> {
+  use crate::schema::otp_batches::dsl::*;
+
+  otp_batches
+    .filter(batch_id.eq(batch))
+    .select(tenant_id)
+    .first::<Uuid>(connection)
+    .await
+    .optional()
+    .map_err(|_| Fault::Diesel)
+}
+
+pub async fn q_tenant_of_otp(connection: &mut Conn, otp_id: i32) -> Result<Option<Uuid>, Fault> {
+  use crate::schema::otp::dsl::*;
+
+  otp
+    .filter(id.eq(otp_id))
+    .select(tenant_id)
+    .first::<Uuid>(connection)
+    .await
+    .optional()
+    .map_err(|_| Fault::Diesel)
+}
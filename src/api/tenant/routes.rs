@@ -0,0 +1,224 @@
+use axum::{
+  Router,
+  routing::{get, patch, post},
+  Extension,
+  extract::{State, Path},
+  http::StatusCode,
+  Json,
+};
+use chrono::Utc;
+use diesel_async::{AsyncConnection, scoped_futures::ScopedFutureExt};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+  api::{audit::queries::i_audit_event, otp::routes::insert_generated_otp, user::safeguards::ensure_can_grant_admin},
+  middleware::authorized::require_permission,
+  models::{audit::{AuditAction, NewAuditEvent}, otp::{CreatedOtp, InsertableOtp, OtpEnum}, role::Permissions, tenant::{AdminInvitationRequest, NewTenant, NewTenantRequest, Tenant, TenantPatch, DEFAULT_TENANT}, user::User},
+  state::AppState,
+  utils::{error::Fault, validation::{is_valid_tenant_slug, MAX_TENANT_NAME_LENGTH, MAX_TENANT_SLUG_LENGTH}}};
+
+use super::queries::{d_tenant, i_tenant, q_tenant, q_tenants, u_tenant};
+
+fn validate_tenant_slug(slug: &str) -> Result<(), Fault> {
+  if !is_valid_tenant_slug(slug) {
+    return Err(Fault::Validation(format!("A tenant slug must consist of 1 to {MAX_TENANT_SLUG_LENGTH} lowercase letters, digits or inner dashes")));
+  }
+
+  Ok(())
+}
+
+fn validate_tenant_name(name: &str) -> Result<(), Fault> {
+  if name.trim().is_empty() || name.trim() != name || name.chars().count() > MAX_TENANT_NAME_LENGTH {
+    return Err(Fault::Validation(format!("A tenant name must consist of 1 to {MAX_TENANT_NAME_LENGTH} characters without surrounding whitespace")));
+  }
+
+  Ok(())
+}
+
+async fn get_tenants(
+  State(state): State<AppState>,
+) -> Result<(StatusCode, Json<Vec<Tenant>>), Fault> {
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  let tenants = q_tenants(&mut connection).await?;
+
+  Ok((StatusCode::OK, Json(tenants)))
+}
+
+async fn get_tenant(
+  State(state): State<AppState>,
+  Path(tenant_id): Path<Uuid>,
+) -> Result<(StatusCode, Json<Tenant>), Fault> {
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  let tenant = q_tenant(&mut connection, tenant_id).await?;
+
+  Ok((StatusCode::OK, Json(tenant)))
+}
+
+async fn create_tenant(
+  State(state): State<AppState>,
+  Extension(actor): Extension<User>,
+  Json(request): Json<NewTenantRequest>,
+) -> Result<(StatusCode, Json<Tenant>), Fault> {
+  validate_tenant_slug(&request.slug)?;
+  validate_tenant_name(&request.name)?;
+
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  let tenant = connection.transaction::<_, Fault, _>(|connection| async move {
+    let tenant = i_tenant(connection, NewTenant {
+      tenant_id: Uuid::new_v4(),
+      slug: &request.slug,
+      name: &request.name,
+    }).await?;
+
+    i_audit_event(connection, NewAuditEvent::new(
+      AuditAction::TenantCreated,
+      Some(actor.user_id),
+      None,
+      json!({ "tenantId": tenant.tenant_id, "slug": tenant.slug, "name": tenant.name }),
+    )).await?;
+
+    Ok(tenant)
+  }.scope_boxed()).await?;
+
+  Ok((StatusCode::CREATED, Json(tenant)))
+}
+
+async fn update_tenant(
+  State(state): State<AppState>,
+  Extension(actor): Extension<User>,
+  Path(tenant_id): Path<Uuid>,
+  Json(patch): Json<TenantPatch>,
+) -> Result<(StatusCode, Json<Tenant>), Fault> {
+  if let Some(name) = &patch.name {
+    validate_tenant_name(name)?;
+  }
+
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  let tenant = connection.transaction::<_, Fault, _>(|connection| async move {
+    let tenant = match patch.name {
+      None => q_tenant(connection, tenant_id).await?,
+      Some(_) => u_tenant(connection, tenant_id, patch).await?,
+    };
+
+    i_audit_event(connection, NewAuditEvent::new(
+      AuditAction::TenantUpdated,
+      Some(actor.user_id),
+      None,
+      json!({ "tenantId": tenant.tenant_id, "slug": tenant.slug, "name": tenant.name }),
+    )).await?;
+
+    Ok(tenant)
+  }.scope_boxed()).await?;
+
+  Ok((StatusCode::OK, Json(tenant)))
+}
+
+async fn delete_tenant(
+  State(state): State<AppState>,
+  Extension(actor): Extension<User>,
+  Path(tenant_id): Path<Uuid>,
+) -> Result<StatusCode, Fault> {
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  connection.transaction::<_, Fault, _>(|connection| async move {
+    let tenant = q_tenant(connection, tenant_id).await?;
+
+    if tenant.slug == DEFAULT_TENANT {
+      return Err(Fault::DefaultTenant);
+    }
+
+    d_tenant(connection, tenant_id).await?;
+
+    i_audit_event(connection, NewAuditEvent::new(
+      AuditAction::TenantDeleted,
+      Some(actor.user_id),
+      None,
+      json!({ "tenantId": tenant.tenant_id, "slug": tenant.slug, "name": tenant.name }),
+    )).await
+  }.scope_boxed()).await?;
+
+  Ok(StatusCode::OK)
+}
+
+/// Creates a registration code for an admin of the tenant, the way a new tenant gets its first admin
+async fn invite_tenant_admin(
+  State(state): State<AppState>,
+  Extension(actor): Extension<User>,
+  Extension(permissions): Extension<Permissions>,
+  Path(tenant_id): Path<Uuid>,
+  Json(request): Json<AdminInvitationRequest>,
+) -> Result<(StatusCode, Json<CreatedOtp>), Fault> {
+  if request.username.is_empty() || request.username.len() > 254 {
+    return Err(Fault::Validation("username must be between 1 and 254 characters long".to_owned()));
+  }
+
+  if request.expires_at.is_some_and(|expiry| expiry <= Utc::now()) {
+    return Err(Fault::Validation("expiresAt must be in the future".to_owned()));
+  }
+
+  let mut connection = state.pool.get_connection().await?.connection;
+  let config = state.config.clone();
+
+  let (created, code) = connection.transaction::<_, Fault, _>(|connection| async move {
+    let tenant = q_tenant(connection, tenant_id).await?;
+    ensure_can_grant_admin(connection, &permissions).await?;
+
+    let template = InsertableOtp {
+      code_hash: String::new(),
+      code_prefix: String::new(),
+      tenant_id: tenant.tenant_id,
+      user: None,
+      code_type: OtpEnum::REGISTER,
+      grant_admin: true,
+      restricted_to: Some(request.username.clone()),
+      groups: vec![],
+      account_expires_at: None,
+      email: None,
+      batch_id: None,
+      expires_at: request.expires_at,
+      created_by: Some(actor.user_id),
+    };
+    let (created, code) = insert_generated_otp(connection, &config.otp, &template).await?;
+
+    i_audit_event(connection, NewAuditEvent::new(
+      AuditAction::TenantAdminInvited,
+      Some(actor.user_id),
+      None,
+      json!({ "tenantId": tenant.tenant_id, "slug": tenant.slug, "username": request.username, "otpId": created.id }),
+    )).await?;
+
+    Ok((created, code))
+  }.scope_boxed()).await?;
+
+  Ok((StatusCode::CREATED, Json(CreatedOtp::from_internal(created, code))))
+}
+
+pub fn router(state: AppState) -> Router<AppState> {
+  Router::new()
+    .route("/tenants",
+      get(get_tenants)
+      .layer(require_permission(&state, "tenants.read"))
+    )
+    .route("/tenants",
+      post(create_tenant)
+      .layer(require_permission(&state, "tenants.manage"))
+    )
+    .route("/tenants/{tenant_id}",
+      get(get_tenant)
+      .layer(require_permission(&state, "tenants.read"))
+    )
+    .route("/tenants/{tenant_id}",
+      patch(update_tenant)
+        .delete(delete_tenant)
+        .layer(require_permission(&state, "tenants.manage"))
+    )
+    .route("/tenants/{tenant_id}/admin-invitation",
+      post(invite_tenant_admin)
+      .layer(require_permission(&state, "tenants.manage"))
+    )
+}
@@ -0,0 +1,58 @@
+use axum::extract::RawPathParams;
+use diesel_async::AsyncPgConnection;
+use uuid::Uuid;
+
+use crate::{models::tenant::Tenant, utils::error::Fault};
+
+use super::queries::{q_tenant_of_group, q_tenant_of_otp, q_tenant_of_otp_batch, q_tenant_of_role, q_tenant_of_user};
+
+/// Fails as if the resource did not exist if it belongs to another tenant, `owner` is `None` for shared resources
+fn ensure_owned_by(tenant: &Tenant, kind: &str, owner: Option<Uuid>) -> Result<(), Fault> {
+  match owner {
+    Some(owner) if owner != tenant.tenant_id => Err(Fault::NotFound(kind.to_string())),
+    _ => Ok(()),
+  }
+}
+
+/// Checks the users, groups, roles, codes and code batches named by the path of a request against its tenant.
+/// Resources that do not exist or ids that do not parse are left to the route, which reports them itself.
+pub async fn ensure_path_in_tenant(connection: &mut AsyncPgConnection, tenant: &Tenant, params: &RawPathParams) -> Result<(), Fault> {
+  for (key, value) in params {
+    match key {
+      "user_id" => if let Ok(id) = value.parse() {
+        ensure_owned_by(tenant, "User", q_tenant_of_user(connection, id).await?)?;
+      },
+      "group_id" => if let Ok(id) = value.parse() {
+        ensure_owned_by(tenant, "Group", q_tenant_of_group(connection, id).await?)?;
+      },
+      "role_id" => if let Ok(id) = value.parse() {
+        ensure_owned_by(tenant, "Role", q_tenant_of_role(connection, id).await?.flatten())?;
+      },
+      "batch_id" => if let Ok(id) = value.parse() {
+        ensure_owned_by(tenant, "batch", q_tenant_of_otp_batch(connection, id).await?)?;
+      },
+      "otp_id" => if let Ok(id) = value.parse() {
+        ensure_owned_by(tenant, "code", q_tenant_of_otp(connection, id).await?)?;
+      },
+      _ => (),
+    }
+  }
+
+  Ok(())
+}
+
+/// Fails if a user named in the body of a request does not exist in the tenant of the request
+pub async fn ensure_user_in_tenant(connection: &mut AsyncPgConnection, tenant: &Tenant, user: Uuid) -> Result<(), Fault> {
+  match q_tenant_of_user(connection, user).await? {
+    Some(owner) if owner == tenant.tenant_id => Ok(()),
+    _ => Err(Fault::NotFound("User".to_string())),
+  }
+}
+
+/// Fails if a group named in the body of a request does not exist in the tenant of the request
+pub async fn ensure_group_in_tenant(connection: &mut AsyncPgConnection, tenant: &Tenant, group: Uuid) -> Result<(), Fault> {
+  match q_tenant_of_group(connection, group).await? {
+    Some(owner) if owner == tenant.tenant_id => Ok(()),
+    _ => Err(Fault::NotFound("Group".to_string())),
+  }
+}
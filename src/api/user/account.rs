@@ -0,0 +1,107 @@
+use chrono::{DateTime, Duration, Utc};
+use diesel_async::{AsyncConnection, AsyncPgConnection, scoped_futures::ScopedFutureExt};
+use serde::Serialize;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+  api::{audit::queries::{i_audit_event, q_audit_events_about}, group::queries::q_groups_of_user, otp::queries::{d_otps_of_user, q_otps_of_user}, user::{safeguards::ensure_other_admin_remains, queries::{d_user, q_lock_purgeable_users, q_username_history, u_restore_user, u_soft_delete_user}}},
+  models::{audit::{AuditAction, AuditEvent, NewAuditEvent}, group::Group, otp::OtpExternal, user::{User, UserInfo, UsernameChange}},
+  state::{AppState, postgres_wrapper::WrappedPostgres, redis_wrapper::SessionInfo},
+  utils::error::Fault,
+};
+
+/// Marks a user as deleted and audits the deletion, the account can be restored until it gets purged.
+/// The last active admin cannot be deleted.
+/// Should be called inside a transaction, the sessions of the user have to be ended once it committed.
+pub async fn delete_account(connection: &mut AsyncPgConnection, user: &User, actor: Uuid) -> Result<(), Fault> {
+  ensure_other_admin_remains(connection, user.user_id).await?;
+
+  i_audit_event(connection, NewAuditEvent::new(
+    AuditAction::AccountDeleted,
+    Some(actor),
+    Some(user.user_id),
+    json!({ "userId": user.user_id, "username": user.username }),
+  )).await?;
+
+  u_soft_delete_user(connection, user.user_id).await
+}
+
+/// Restores a deleted user whose retention period has not passed yet
+pub async fn restore_account(connection: &mut AsyncPgConnection, user_id: Uuid, actor: Uuid, retention_days: i64) -> Result<User, Fault> {
+  connection.transaction::<_, Fault, _>(|connection| async move {
+    let user = u_restore_user(connection, user_id, Utc::now() - Duration::days(retention_days)).await?;
+
+    i_audit_event(connection, NewAuditEvent::new(
+      AuditAction::AccountRestored,
+      Some(actor),
+      Some(user.user_id),
+      json!({}),
+    )).await?;
+
+    Ok(user)
+  }.scope_boxed()).await
+}
+
+/// Permanently deletes users whose retention period has passed, together with the codes bound to them.
+/// Returns the amount of purged users.
+pub async fn purge_deleted_accounts(pool: &WrappedPostgres, retention_days: i64) -> Result<usize, Fault> {
+  let mut connection = pool.get_connection().await?.connection;
+
+  connection.transaction::<_, Fault, _>(|connection| async move {
+    let purgeable = q_lock_purgeable_users(connection, Utc::now() - Duration::days(retention_days)).await?;
+
+    for user in purgeable.iter() {
+      // the subject is cleared once the user is gone, so the details keep who has been purged
+      i_audit_event(connection, NewAuditEvent::new(
+        AuditAction::AccountPurged,
+        None,
+        Some(user.user_id),
+        json!({ "userId": user.user_id, "username": user.username }),
+      )).await?;
+
+      d_otps_of_user(connection, user.user_id).await?;
+      d_user(connection, user.user_id).await?;
+    }
+
+    Ok(purgeable.len())
+  }.scope_boxed()).await
+}
+
+/// Everything stored about a user, handed out by `GET /auth/self/export`
+#[derive(Serialize)]
+#[serde(rename_all(serialize="camelCase"))]
+pub struct PersonalDataExport {
+  pub exported_at: DateTime<Utc>,
+  pub profile: UserInfo,
+  pub groups: Vec<Group>,
+  pub sessions: Vec<SessionInfo>,
+  /// Codes bound to the user or redeemed by them, the codes themselves are only stored hashed
+  pub otps: Vec<OtpExternal>,
+  pub username_history: Vec<UsernameChange>,
+  pub audit_events: Vec<AuditEvent>,
+}
+
+pub async fn collect_personal_data(state: &AppState, user: User, current_access_token: &str) -> Result<PersonalDataExport, Fault> {
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  let groups = q_groups_of_user(&mut connection, user.user_id).await?;
+  let otps = q_otps_of_user(&mut connection, user.user_id).await?;
+  let username_history = q_username_history(&mut connection, user.user_id).await?;
+  let audit_events = q_audit_events_about(&mut connection, user.user_id).await?;
+
+  let mut sessions = state.redis.get_sessions_of_user(user.user_id).await?;
+  for session in sessions.iter_mut() {
+    session.current = session.access_token == current_access_token;
+  }
+
+  Ok(PersonalDataExport {
+    exported_at: Utc::now(),
+    profile: UserInfo::from(user),
+    groups,
+    sessions,
+    otps: otps.into_iter().map(OtpExternal::from).collect(),
+    username_history,
+    audit_events,
+  })
+}
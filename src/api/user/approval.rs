@@ -0,0 +1,112 @@
+use diesel_async::{AsyncConnection, scoped_futures::ScopedFutureExt};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+  api::{audit::queries::i_audit_event, auth::queries::q_get_user_by_id, user::queries::{u_approve_user, u_reject_user}},
+  models::{audit::{AuditAction, NewAuditEvent}, user::{User, UserInfo}},
+  state::AppState,
+  utils::{error::Fault, validation::MAX_REJECTION_REASON_LENGTH},
+};
+
+/// Decision of an admin about a registration waiting for approval
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct RegistrationDecision {
+  /// Mails the decision to the address the user registered with
+  #[serde(default)]
+  pub notify: bool,
+  /// Only for rejections, part of the mail to the user
+  pub reason: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct RegistrationDecisionResponse {
+  pub user: UserInfo,
+  /// Whether a mail has been sent, users without an email address cannot be notified
+  pub notified: bool,
+}
+
+/// Approves a registration on behalf of `admin`, afterwards the user can log in
+pub async fn approve_registration(state: &AppState, user_id: Uuid, admin: Uuid, decision: RegistrationDecision) -> Result<RegistrationDecisionResponse, Fault> {
+  if decision.reason.is_some() {
+    return Err(Fault::Validation("Only a rejection can have a reason".to_string()));
+  }
+
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  let user = connection.transaction::<_, Fault, _>(|connection| async move {
+    q_get_user_by_id(connection, user_id).await?;
+    let user = u_approve_user(connection, user_id).await?;
+
+    i_audit_event(connection, NewAuditEvent::new(
+      AuditAction::RegistrationApproved,
+      Some(admin),
+      Some(user_id),
+      json!({ "notify": decision.notify }),
+    )).await?;
+
+    Ok(user)
+  }.scope_boxed()).await?;
+
+  let notified = decision.notify && notify(
+    state,
+    &user,
+    "Your account has been approved",
+    format!("Hello {},\n\nyour account has been approved by an admin, you can log in now.\n", user.username),
+  ).await;
+
+  Ok(RegistrationDecisionResponse { user: UserInfo::from(user), notified })
+}
+
+/// Rejects a registration on behalf of `admin`. The user is deleted like any other deleted user
+/// and purged once the retention period has passed, which releases the username.
+pub async fn reject_registration(state: &AppState, user_id: Uuid, admin: Uuid, decision: RegistrationDecision) -> Result<RegistrationDecisionResponse, Fault> {
+  if let Some(reason) = &decision.reason {
+    if reason.trim().is_empty() || reason.chars().count() > MAX_REJECTION_REASON_LENGTH {
+      return Err(Fault::Validation(format!("A reason must consist of 1 to {MAX_REJECTION_REASON_LENGTH} characters")));
+    }
+  }
+
+  let mut connection = state.pool.get_connection().await?.connection;
+  let reason = decision.reason.clone();
+
+  let user = connection.transaction::<_, Fault, _>(|connection| async move {
+    q_get_user_by_id(connection, user_id).await?;
+    let user = u_reject_user(connection, user_id).await?;
+
+    i_audit_event(connection, NewAuditEvent::new(
+      AuditAction::RegistrationRejected,
+      Some(admin),
+      Some(user_id),
+      json!({ "notify": decision.notify, "reason": reason }),
+    )).await?;
+
+    Ok(user)
+  }.scope_boxed()).await?;
+
+  let mut body = format!("Hello {},\n\nyour registration has been rejected by an admin.\n", user.username);
+  if let Some(reason) = &decision.reason {
+    body.push_str(&format!("\nReason: {reason}\n"));
+  }
+
+  let notified = decision.notify && notify(state, &user, "Your registration has been rejected", body).await;
+
+  Ok(RegistrationDecisionResponse { user: UserInfo::from(user), notified })
+}
+
+/// Mails a decision to the user, a failed delivery is only logged as the decision has been stored already
+async fn notify(state: &AppState, user: &User, subject: &str, body: String) -> bool {
+  let Some(email) = &user.email else {
+    return false;
+  };
+
+  match state.mailer.send(email, subject, body).await {
+    Ok(_) => true,
+    Err(_) => {
+      println!("Failed to send the registration decision to {email}");
+      false
+    },
+  }
+}
@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+  api::{audit::queries::q_recent_audit_events_about, group::routes::collect_memberships, otp::queries::q_active_otps_of_user, role::queries::q_roles_of_user, user::queries::q_get_any_user_by_id},
+  models::{audit::AuditEvent, group::GroupMembership, otp::OtpExternal, role::GrantedRole, user::UserInfo},
+  state::{AppState, redis_wrapper::SessionInfo},
+  utils::error::Fault,
+};
+
+/// Default and maximum amount of security events in a user detail
+pub const DETAIL_EVENTS: i64 = 20;
+pub const DETAIL_MAX_EVENTS: i64 = 100;
+
+#[derive(Deserialize, Default)]
+pub struct UserDetailQuery {
+  /// Amount of security events to include
+  pub events: Option<i64>,
+}
+
+/// Everything support needs to handle a ticket about a user, handed out by `GET /users/{user_id}`
+#[derive(Serialize)]
+#[serde(rename_all(serialize="camelCase"))]
+pub struct UserDetail {
+  pub user: UserInfo,
+  pub roles: Vec<GrantedRole>,
+  pub groups: Vec<GroupMembership>,
+  pub sessions: Vec<SessionInfo>,
+  /// Codes bound to the user that can still be redeemed
+  pub pending_otps: Vec<OtpExternal>,
+  /// Second factors are not supported yet, so this is always `None` until they are
+  pub mfa_enrolled: Option<bool>,
+  /// Latest audit events about the user, newest first
+  pub security_events: Vec<AuditEvent>,
+}
+
+/// Collects the detail of a user, deleted users included
+pub async fn collect_user_detail(state: &AppState, user_id: Uuid, events: i64) -> Result<UserDetail, Fault> {
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  let user = q_get_any_user_by_id(&mut connection, user_id).await?;
+  let roles = q_roles_of_user(&mut connection, user_id).await?;
+  let groups = collect_memberships(&mut connection, user_id).await?;
+  let pending_otps = q_active_otps_of_user(&mut connection, user_id).await?;
+  let security_events = q_recent_audit_events_about(&mut connection, user_id, events).await?;
+
+  let sessions = state.redis.get_sessions_of_user(user_id).await?;
+
+  Ok(UserDetail {
+    user: UserInfo::from(user),
+    roles,
+    groups,
+    sessions,
+    pending_otps: pending_otps.into_iter().map(OtpExternal::from).collect(),
+    mfa_enrolled: None,
+    security_events,
+  })
+}
@@ -0,0 +1,71 @@
+use chrono::{DateTime, Duration, Utc};
+use diesel_async::{AsyncConnection, scoped_futures::ScopedFutureExt};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+  api::{audit::queries::i_audit_event, user::queries::{q_lock_newly_expired_users, q_users_expiring, u_mark_expiry_processed}},
+  models::{audit::{AuditAction, NewAuditEvent}, tenant::Tenant, user::UserInfo},
+  state::AppState,
+  utils::error::Fault,
+};
+
+/// Default and maximum amount of days `GET /users/expiring` looks ahead
+pub const EXPIRY_REPORT_DAYS: i64 = 7;
+pub const EXPIRY_REPORT_MAX_DAYS: i64 = 365;
+
+#[derive(Deserialize, Default)]
+pub struct ExpiryReportQuery {
+  pub days: Option<i64>,
+}
+
+/// Accounts losing access within the next days, meant to be pulled once a day
+#[derive(Serialize)]
+#[serde(rename_all(serialize="camelCase"))]
+pub struct ExpiryReport {
+  pub generated_at: DateTime<Utc>,
+  /// Accounts expiring until then are listed
+  pub until: DateTime<Utc>,
+  pub users: Vec<UserInfo>,
+}
+
+pub async fn collect_expiry_report(state: &AppState, tenant: &Tenant, days: i64) -> Result<ExpiryReport, Fault> {
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  let generated_at = Utc::now();
+  let until = generated_at + Duration::days(days);
+  let users = q_users_expiring(&mut connection, tenant.tenant_id, until).await?;
+
+  Ok(ExpiryReport { generated_at, until, users: users.into_iter().map(UserInfo::from).collect() })
+}
+
+/// Revokes the sessions of users whose expiry has passed, each expiry is audited and handled once.
+/// Expired users are turned away when checking them anyway, this ends the sessions they are holding.
+/// Returns the amount of expired users.
+pub async fn revoke_expired_accounts(state: &AppState) -> Result<usize, Fault> {
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  let expired = connection.transaction::<_, Fault, _>(|connection| async move {
+    let expired = q_lock_newly_expired_users(connection).await?;
+
+    for user in expired.iter() {
+      i_audit_event(connection, NewAuditEvent::new(
+        AuditAction::AccountExpired,
+        None,
+        Some(user.user_id),
+        json!({ "expiresAt": user.expires_at }),
+      )).await?;
+    }
+
+    let user_ids: Vec<_> = expired.iter().map(|user| user.user_id).collect();
+    u_mark_expiry_processed(connection, &user_ids).await?;
+
+    Ok(user_ids)
+  }.scope_boxed()).await?;
+
+  for user_id in expired.iter() {
+    state.redis.invalidate_sessions_of_user(*user_id).await?;
+  }
+
+  Ok(expired.len())
+}
@@ -1,3 +1,21 @@
-pub mod user;
+pub mod routes;
 
 pub mod queries;
+
+pub mod profile;
+
+pub mod username;
+
+pub mod account;
+
+pub mod suspension;
+
+pub mod safeguards;
+
+pub mod detail;
+
+pub mod recovery;
+
+pub mod approval;
+
+pub mod expiry;
@@ -0,0 +1,207 @@
+use chrono::Utc;
+use diesel_async::{AsyncConnection, scoped_futures::ScopedFutureExt};
+use uuid::Uuid;
+
+use crate::{
+  api::{auth::email::{ensure_verified_email, issue_email_code, try_send_email_code}, user::{queries::u_update_user, safeguards::ensure_other_admin_remains, suspension::validate_suspension, username::{ensure_username_available, record_username_change}}},
+  models::user::{AccountStatus, ProfileField, Suspension, User, UserChangeset, UserPatch},
+  state::{AppState, config::ProfileConfig},
+  utils::{error::Fault, username::{canonical_username, ensure_username_not_blocked, validate_username}, validation::{is_valid_email, is_valid_locale, MAX_DISPLAY_NAME_LENGTH}},
+};
+
+/// Validates and stores a profile update of `current`, done by the user `actor`.
+/// A new email address gets a verification code mailed to it, a new username is recorded in the username history.
+pub async fn update_profile(state: &AppState, current: &User, patch: UserPatch, actor: Uuid, as_admin: bool) -> Result<User, Fault> {
+  let changes = build_changeset(current, patch, &state.config.profile, actor, as_admin)?;
+
+  let renamed = changes.username.clone().filter(|name| name != &current.username);
+
+  // the username is the login identifier, renaming oneself counts as sensitive action
+  if renamed.is_some() && !as_admin {
+    ensure_verified_email(current, &state.config)?;
+  }
+
+  let suspended = changes.status == Some(AccountStatus::Suspended);
+
+  let new_email = match (&changes.email, &changes.email_verified_at) {
+    (Some(Some(email)), Some(_)) => Some(email.clone()),
+    _ => None,
+  };
+
+  let mut connection = state.pool.get_connection().await?.connection;
+  let config = state.config.clone();
+  let user_id = current.user_id;
+  let tenant = current.tenant_id;
+  let created_by = actor;
+  let old_username = current.username.clone();
+
+  // the address and the code proving it as well as the name and its history are stored together
+  let (updated, code) = connection.transaction::<_, Fault, _>(|connection| async move {
+    if let Some(new_username) = &renamed {
+      ensure_username_available(connection, tenant, new_username, Some(user_id)).await?;
+    }
+
+    // an admin with an expiry counts as leaving as well
+    if changes.admin == Some(false) || changes.status == Some(AccountStatus::Suspended) || matches!(changes.expires_at, Some(Some(_))) {
+      ensure_other_admin_remains(connection, user_id).await?;
+    }
+
+    let updated = u_update_user(connection, user_id, changes).await?;
+
+    if let Some(new_username) = &renamed {
+      record_username_change(connection, &config.profile, tenant, user_id, &old_username, new_username, created_by).await?;
+    }
+
+    let code = match &new_email {
+      Some(email) => Some(issue_email_code(connection, &config, tenant, user_id, email, Some(created_by)).await?),
+      None => None,
+    };
+
+    Ok((updated, code))
+  }.scope_boxed()).await?;
+
+  // like `suspend_user`, blocking ends the sessions of the user
+  if suspended {
+    state.redis.invalidate_sessions_of_user(user_id).await?;
+  }
+
+  if let (Some(email), Some(code)) = (&updated.email, code) {
+    try_send_email_code(state, email, &code).await;
+  }
+
+  Ok(updated)
+}
+
+/// Turns a requested profile update into the changes to store.
+///
+/// Users may only change the fields listed in `SELF_EDITABLE_PROFILE_FIELDS`, admins may change every field.
+/// Changing the email address withdraws its verification, unblocking a user clears the details of their suspension.
+pub fn build_changeset(current: &User, patch: UserPatch, config: &ProfileConfig, actor: Uuid, as_admin: bool) -> Result<UserChangeset, Fault> {
+  if !as_admin {
+    let requested = [
+      (ProfileField::Username, "username", patch.username.is_some()),
+      (ProfileField::Email, "email", patch.email.is_some()),
+      (ProfileField::DisplayName, "displayName", patch.display_name.is_some()),
+      (ProfileField::Locale, "locale", patch.locale.is_some()),
+      (ProfileField::Attributes, "attributes", patch.attributes.is_some()),
+    ];
+    if let Some((_, name, _)) = requested.iter().find(|(field, _, present)| *present && !config.self_editable.contains(field)) {
+      return Err(Fault::FieldNotEditable(name.to_string()));
+    }
+
+    let admin_only = [
+      ("admin", patch.admin.is_some()),
+      ("blocked", patch.blocked.is_some()),
+      ("blockedReason", patch.blocked_reason.is_some()),
+      ("blockedUntil", patch.blocked_until.is_some()),
+      ("expiresAt", patch.expires_at.is_some()),
+    ];
+    if let Some((name, _)) = admin_only.iter().find(|(_, present)| *present) {
+      return Err(Fault::FieldNotEditable(name.to_string()));
+    }
+  }
+
+  if let Some(username) = &patch.username {
+    validate_username(username, &config.username)?;
+    if !as_admin {
+      ensure_username_not_blocked(username, &config.username)?;
+    }
+  }
+
+  if let Some(Some(email)) = &patch.email {
+    if !is_valid_email(email) {
+      return Err(Fault::Validation("The email address is invalid".to_string()));
+    }
+  }
+
+  if let Some(Some(display_name)) = &patch.display_name {
+    if display_name.trim().is_empty() || display_name.len() > MAX_DISPLAY_NAME_LENGTH {
+      return Err(Fault::Validation(format!("A display name must consist of 1 to {MAX_DISPLAY_NAME_LENGTH} characters")));
+    }
+  }
+
+  if let Some(Some(locale)) = &patch.locale {
+    if !is_valid_locale(locale) {
+      return Err(Fault::Validation("The locale must be a language tag like 'en' or 'de-AT'".to_string()));
+    }
+  }
+
+  if let Some(attributes) = &patch.attributes {
+    validate_attributes(attributes, config)?;
+  }
+
+  if let Some(Some(expiry)) = patch.expires_at {
+    if expiry <= Utc::now() {
+      return Err(Fault::Validation("An expiry has to be in the future".to_string()));
+    }
+  }
+
+  validate_suspension(&Suspension {
+    reason: patch.blocked_reason.clone().flatten(),
+    until: patch.blocked_until.flatten(),
+  })?;
+
+  // `blocked` moves the user between ACTIVE and SUSPENDED, unblocking a user that is not suspended changes nothing
+  let status = match patch.blocked {
+    Some(true) => {
+      current.status.ensure_can_become(AccountStatus::Suspended)?;
+      Some(AccountStatus::Suspended)
+    },
+    Some(false) if current.status == AccountStatus::Suspended => Some(AccountStatus::Active),
+    _ => None,
+  };
+
+  let (blocked_reason, blocked_until, blocked_by) = match patch.blocked {
+    Some(true) => (patch.blocked_reason, patch.blocked_until, Some(Some(actor))),
+    Some(false) => (Some(None), Some(None), Some(None)),
+    None => (patch.blocked_reason, patch.blocked_until, None),
+  };
+
+  // a suspension supersedes a lockout due to failed logins
+  let (failed_login_attempts, locked_at, locked_until) = match status {
+    Some(AccountStatus::Suspended) => (Some(0), Some(None), Some(None)),
+    _ => (None, None, None),
+  };
+
+  let email_changed = patch.email.as_ref().is_some_and(|email| email != &current.email);
+
+  Ok(UserChangeset {
+    username_canonical: patch.username.as_deref().map(canonical_username),
+    username: patch.username,
+    email: patch.email,
+    email_verified_at: email_changed.then_some(None),
+    display_name: patch.display_name,
+    locale: patch.locale,
+    attributes: patch.attributes,
+    admin: patch.admin,
+    status,
+    failed_login_attempts,
+    locked_at,
+    locked_until,
+    blocked_reason,
+    blocked_until,
+    blocked_by,
+    // a new expiry revokes the sessions again once it passes
+    expiry_processed_at: patch.expires_at.map(|_| None),
+    expires_at: patch.expires_at,
+  })
+}
+
+fn validate_attributes(attributes: &serde_json::Value, config: &ProfileConfig) -> Result<(), Fault> {
+  if !attributes.is_object() {
+    return Err(Fault::AttributesInvalid("attributes must be a JSON object".to_string()));
+  }
+
+  if let Some(schema) = &config.attributes_schema {
+    schema.validate(attributes)
+      .map_err(|e| {
+        let path = e.instance_path().to_string();
+        match path.is_empty() {
+          true => Fault::AttributesInvalid(e.to_string()),
+          false => Fault::AttributesInvalid(format!("{e} at '{path}'")),
+        }
+      })?;
+  }
+
+  Ok(())
+}
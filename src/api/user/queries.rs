@@ -1,33 +1,241 @@
-use diesel::{prelude::*, update, delete};
+use chrono::{DateTime, Utc};
+use diesel::{prelude::*, update, delete, pg::Pg, dsl::count_star};
+use diesel::result::{DatabaseErrorKind, Error::{DatabaseError, QueryBuilderError}};
 use diesel_async::RunQueryDsl;
-use bb8::PooledConnection;
-use diesel_async::{pooled_connection::AsyncDieselConnectionManager, AsyncPgConnection};
+use diesel_async::AsyncPgConnection;
 use uuid::Uuid;
 
-use crate::utils::error::Fault;
+use crate::{api::auth::queries::{q_get_user_by_id, user_conflict}, models::user::{AccountStatus, NewUsernameChange, Suspension, UsernameChange, User, UserChangeset, UserListQuery, UserSort, SortOrder}, schema::users, utils::{error::Fault, username::canonical_username}};
 
-type Conn<'a> = PooledConnection<'a, AsyncDieselConnectionManager<AsyncPgConnection>>;
+type Conn = AsyncPgConnection;
 
-pub async fn u_set_admin_on_user(connection: &mut Conn<'_>, user_uuid: Uuid, is_admin: bool) -> Result<(), Fault> {
+/// Default and maximum page size of `q_user_page`
+pub const USER_PAGE_SIZE: i64 = 50;
+pub const USER_MAX_PAGE_SIZE: i64 = 200;
+
+/// Escapes the wildcards of a `LIKE` pattern, so user input is matched literally
+fn escape_like(value: &str) -> String {
+  value
+    .replace('\\', "\\\\")
+    .replace('%', "\\%")
+    .replace('_', "\\_")
+}
+
+/// Why a status change updated no row: the user does not exist or its status cannot become `next`
+async fn status_change_fault(connection: &mut Conn, user: Uuid, next: AccountStatus) -> Fault {
+  match q_get_any_user_by_id(connection, user).await {
+    Ok(current) => Fault::InvalidStatusTransition { from: current.status, to: next },
+    Err(fault) => fault,
+  }
+}
+
+/// Users of a tenant matching the filters of `filter`, shared by the page and the total count
+fn filtered_users(tenant: Uuid, filter: &UserListQuery) -> users::BoxedQuery<'static, Pg> {
+  use crate::schema::users::dsl::*;
+
+  let mut query = users.filter(tenant_id.eq(tenant)).into_boxed();
+
+  query = match (filter.status, filter.deleted) {
+    (Some(wanted), _) => query.filter(status.eq(wanted)),
+    (None, true) => query.filter(status.eq(AccountStatus::Deleted)),
+    (None, false) => query.filter(status.ne(AccountStatus::Deleted)),
+  };
+
+  if let Some(search) = &filter.search {
+    query = query.filter(username_canonical.like(format!("%{}%", escape_like(&canonical_username(search)))));
+  }
+
+  if let Some(prefix) = &filter.prefix {
+    query = query.filter(username_canonical.like(format!("{}%", escape_like(&canonical_username(prefix)))));
+  }
+
+  query = match filter.admin {
+    Some(true) => query.filter(admin.eq(true)),
+    Some(false) => query.filter(admin.eq(false)),
+    None => query,
+  };
+
+  // suspensions that have run out do not count, even if the job lifting them has not run yet
+  query = match filter.blocked {
+    Some(true) => query.filter(status.eq(AccountStatus::Suspended).and(blocked_until.is_null().or(blocked_until.gt(Utc::now())))),
+    Some(false) => query.filter(status.ne(AccountStatus::Suspended).or(blocked_until.le(Utc::now()))),
+    None => query,
+  };
+
+  if let Some(after) = filter.created_after {
+    query = query.filter(created_at.ge(after));
+  }
+
+  if let Some(before) = filter.created_before {
+    query = query.filter(created_at.lt(before));
+  }
+
+  if let Some(before) = filter.last_login_before {
+    query = query.filter(last_login_at.is_null().or(last_login_at.lt(before)));
+  }
+
+  query
+}
+
+/// Loads one page of the users of a tenant matching `filter` together with the total amount of matching users
+pub async fn q_user_page(connection: &mut Conn, tenant: Uuid, filter: &UserListQuery, page: i64, page_size: i64) -> Result<(Vec<User>, i64), Fault> {
+  use crate::schema::users::dsl::*;
+
+  let skipped = (page - 1).checked_mul(page_size)
+    .ok_or(Fault::Validation("page is out of range".to_string()))?;
+
+  let total: i64 = filtered_users(tenant, filter)
+    .select(count_star())
+    .get_result(connection)
+    .await
+    .map_err(|_| Fault::Diesel)?;
+
+  let query = filtered_users(tenant, filter)
+    .select(User::as_select())
+    .limit(page_size)
+    .offset(skipped);
+
+  // the user id breaks ties, so pages stay stable for equal sort keys
+  let query = match (filter.sort, filter.order) {
+    (UserSort::Username, SortOrder::Asc) => query.order((username.asc(), user_id.asc())),
+    (UserSort::Username, SortOrder::Desc) => query.order((username.desc(), user_id.desc())),
+    (UserSort::CreatedAt, SortOrder::Asc) => query.order((created_at.asc(), user_id.asc())),
+    (UserSort::CreatedAt, SortOrder::Desc) => query.order((created_at.desc(), user_id.desc())),
+    // users that never logged in count as the oldest logins
+    (UserSort::LastLoginAt, SortOrder::Asc) => query.order((last_login_at.asc().nulls_first(), user_id.asc())),
+    (UserSort::LastLoginAt, SortOrder::Desc) => query.order((last_login_at.desc().nulls_last(), user_id.desc())),
+  };
+
+  let page_users = query
+    .load::<User>(connection)
+    .await
+    .map_err(|_| Fault::Diesel)?;
+
+  Ok((page_users, total))
+}
+
+/// Ids of all admins of a tenant that can log in right now, their rows stay locked until the transaction ends.
+/// Suspensions and lockouts that have run out count as active, a locked admin cannot act until the lockout is over.
+pub async fn q_lock_active_admins(connection: &mut Conn, tenant: Uuid) -> Result<Vec<Uuid>, Fault> {
+  use crate::schema::users::dsl::*;
+
+  let now = Utc::now();
+  users
+    .filter(tenant_id.eq(tenant))
+    .filter(admin.eq(true))
+    .filter(
+      status.eq(AccountStatus::Active)
+        .or(status.eq(AccountStatus::Suspended).and(blocked_until.le(now)))
+        .or(status.eq(AccountStatus::Locked).and(locked_until.le(now)))
+    )
+    .filter(expires_at.is_null().or(expires_at.gt(now)))
+    .select(user_id)
+    .for_update()
+    .load::<Uuid>(connection)
+    .await
+    .map_err(|_| Fault::Diesel)
+}
+
+pub async fn u_set_admin_on_user(connection: &mut Conn, user_uuid: Uuid, is_admin: bool) -> Result<(), Fault> {
   use crate::schema::users::dsl::*;
 
   let _ = update(users.filter(user_id.eq(user_uuid)))
     .set(admin.eq(is_admin))
     .execute(connection)
     .await
-    .or_else(|_| Err(Fault::Diesel))?;
+    .map_err(|_| Fault::Diesel)?;
 
   Ok(())
 }
 
-pub async fn u_block_user (connection: &mut Conn<'_>, user: Uuid) -> Result<(), Fault> {
+/// Suspends a user, a suspension supersedes a lockout due to failed logins.
+/// Suspending a suspended user replaces the details of the suspension.
+pub async fn u_block_user (connection: &mut Conn, user: Uuid, suspension: &Suspension, actor: Uuid) -> Result<(), Fault> {
   use crate::schema::users::dsl::*;
 
-  let result: usize = update(users.filter(user_id.eq(user)))
-    .set(blocked.eq(true))
+  let result: usize = update(users.filter(user_id.eq(user)).filter(status.eq_any(AccountStatus::predecessors(AccountStatus::Suspended))))
+    .set((
+      status.eq(AccountStatus::Suspended),
+      blocked_reason.eq(&suspension.reason),
+      blocked_until.eq(suspension.until),
+      blocked_by.eq(actor),
+      failed_login_attempts.eq(0),
+      locked_at.eq(None::<DateTime<Utc>>),
+      locked_until.eq(None::<DateTime<Utc>>),
+    ))
     .execute(connection)
     .await
-    .or_else(|_| Err(Fault::Diesel))?;
+    .map_err(|_| Fault::Diesel)?;
+
+  if result == 0 {
+    return Err(status_change_fault(connection, user, AccountStatus::Suspended).await);
+  }
+
+  Ok(())
+}
+
+/// Lifts an admin block as well as a lockout caused by failed logins, for an active user it only resets the failed logins.
+/// Users awaiting approval or deleted users cannot be unblocked, the row is locked until the transaction ends.
+pub async fn u_unblock_user (connection: &mut Conn, user: Uuid) -> Result<(), Fault> {
+  use crate::schema::users::dsl::*;
+
+  let current: AccountStatus = users
+    .filter(user_id.eq(user))
+    .select(status)
+    .for_update()
+    .first::<AccountStatus>(connection)
+    .await
+    .map_err(|_| Fault::NotFound(String::from("User")))?;
+
+  if !matches!(current, AccountStatus::Suspended | AccountStatus::Locked | AccountStatus::Active) {
+    return Err(Fault::InvalidStatusTransition { from: current, to: AccountStatus::Active });
+  }
+
+  update(users.filter(user_id.eq(user)))
+    .set((
+      status.eq(AccountStatus::Active),
+      blocked_reason.eq(None::<String>),
+      blocked_until.eq(None::<DateTime<Utc>>),
+      blocked_by.eq(None::<Uuid>),
+      failed_login_attempts.eq(0),
+      locked_at.eq(None::<DateTime<Utc>>),
+      locked_until.eq(None::<DateTime<Utc>>),
+    ))
+    .execute(connection)
+    .await
+    .map_err(|_| Fault::Diesel)?;
+
+  Ok(())
+}
+
+/// Unblocks all users whose suspension has run out, returns the amount of unblocked users
+pub async fn u_lift_expired_suspensions (connection: &mut Conn) -> Result<usize, Fault> {
+  use crate::schema::users::dsl::*;
+
+  update(users.filter(status.eq(AccountStatus::Suspended)).filter(blocked_until.le(Utc::now())))
+    .set((
+      status.eq(AccountStatus::Active),
+      blocked_reason.eq(None::<String>),
+      blocked_until.eq(None::<DateTime<Utc>>),
+      blocked_by.eq(None::<Uuid>),
+    ))
+    .execute(connection)
+    .await
+    .map_err(|_| Fault::Diesel)
+}
+
+/// Sets a password handed out by an admin, which the user has to change
+pub async fn u_set_temporary_password (connection: &mut Conn, user: Uuid, hashed: &str) -> Result<(), Fault> {
+  use crate::schema::users::dsl::*;
+
+  let result: usize = update(users.filter(user_id.eq(user)).filter(status.ne(AccountStatus::Deleted)))
+    .set((
+      password.eq(hashed),
+      password_must_change.eq(true),
+    ))
+    .execute(connection)
+    .await
+    .map_err(|_| Fault::Diesel)?;
 
   if result == 0 {
     return Err(Fault::NotFound("user".to_owned()));
@@ -36,14 +244,199 @@ pub async fn u_block_user (connection: &mut Conn<'_>, user: Uuid) -> Result<(),
   Ok(())
 }
 
-pub async fn u_unblock_user (connection: &mut Conn<'_>, user: Uuid) -> Result<(), Fault> {
+/// Lifts a lockout caused by failed logins, any other status stays in place
+pub async fn u_clear_lockout (connection: &mut Conn, user: Uuid) -> Result<(), Fault> {
+  use crate::schema::users::dsl::*;
+
+  let result: usize = update(users.filter(user_id.eq(user)).filter(status.ne(AccountStatus::Deleted)))
+    .set(failed_login_attempts.eq(0))
+    .execute(connection)
+    .await
+    .map_err(|_| Fault::Diesel)?;
+
+  if result == 0 {
+    return Err(Fault::NotFound("user".to_owned()));
+  }
+
+  update(users.filter(user_id.eq(user)).filter(status.eq(AccountStatus::Locked)))
+    .set((status.eq(AccountStatus::Active), locked_at.eq(None::<DateTime<Utc>>), locked_until.eq(None::<DateTime<Utc>>)))
+    .execute(connection)
+    .await
+    .map_err(|_| Fault::Diesel)
+    .map(|_| ())
+}
+
+/// Applies a profile update and returns the updated user, a change of the status has to be a valid transition
+pub async fn u_update_user(connection: &mut Conn, user: Uuid, changes: UserChangeset) -> Result<User, Fault> {
+  use crate::schema::users::dsl::*;
+
+  let next = changes.status;
+  let allowed = match next {
+    Some(next) => AccountStatus::predecessors(next),
+    // every user but a deleted one
+    None => AccountStatus::predecessors(AccountStatus::Deleted),
+  };
+
+  let result = update(users.filter(user_id.eq(user)).filter(status.eq_any(allowed)))
+    .set(changes)
+    .returning(User::as_returning())
+    .get_result::<User>(connection)
+    .await
+    .optional();
+
+  match result {
+    Ok(Some(updated)) => Ok(updated),
+    Ok(None) => match next {
+      Some(next) => Err(status_change_fault(connection, user, next).await),
+      None => Err(Fault::NotFound("user".to_owned())),
+    },
+    // an empty update has nothing to save, the user stays as it is
+    Err(QueryBuilderError(_)) => q_get_user_by_id(connection, user).await,
+    Err(DatabaseError(DatabaseErrorKind::UniqueViolation, info)) => Err(user_conflict(info.as_ref())),
+    Err(_) => Err(Fault::Diesel),
+  }
+}
+
+/// Whether any spelling of `name` has been released by another user than `except` and is still reserved
+pub async fn q_is_username_reserved(connection: &mut Conn, tenant: Uuid, name: &str, except: Option<Uuid>) -> Result<bool, Fault> {
+  use crate::schema::username_history::dsl::*;
+
+  let mut query = username_history
+    .filter(tenant_id.eq(tenant))
+    .filter(reserved_until.gt(Utc::now()))
+    .select(old_username)
+    .into_boxed();
+
+  // the previous owner may take the name back
+  if let Some(except) = except {
+    query = query.filter(user_id.is_distinct_from(except));
+  }
+
+  // only reservations within the reservation period are loaded, few enough to compare their canonical form here
+  let reserved: Vec<String> = query
+    .load(connection)
+    .await
+    .map_err(|_| Fault::Diesel)?;
+
+  let canonical = canonical_username(name);
+  Ok(reserved.iter().any(|reserved_name| canonical_username(reserved_name) == canonical))
+}
+
+pub async fn i_username_change(connection: &mut Conn, change: NewUsernameChange<'_>) -> Result<(), Fault> {
+  use crate::schema::username_history;
+
+  diesel::insert_into(username_history::table)
+    .values(change)
+    .execute(connection)
+    .await
+    .map_err(|_| Fault::Diesel)
+    .map(|_| ())
+}
+
+pub async fn q_username_history(connection: &mut Conn, user: Uuid) -> Result<Vec<UsernameChange>, Fault> {
+  use crate::schema::username_history::dsl::*;
+
+  username_history
+    .filter(user_id.eq(user))
+    .order(changed_at.desc())
+    .select(UsernameChange::as_select())
+    .load::<UsernameChange>(connection)
+    .await
+    .map_err(|_| Fault::Diesel)
+}
+
+/// Looks up a user including deleted ones, for admins handling an account
+pub async fn q_get_any_user_by_id (connection: &mut Conn, user: Uuid) -> Result<User, Fault> {
+  use crate::schema::users::dsl::*;
+
+  users
+    .filter(user_id.eq(user))
+    .select(User::as_select())
+    .first::<User>(connection)
+    .await
+    .map_err(|_| Fault::NotFound(String::from("User")))
+}
+
+/// Users whose expiry has passed since it has last been processed, locked until the transaction ends
+pub async fn q_lock_newly_expired_users (connection: &mut Conn) -> Result<Vec<User>, Fault> {
+  use crate::schema::users::dsl::*;
+
+  users
+    .filter(expires_at.le(Utc::now()))
+    .filter(expiry_processed_at.is_null())
+    .filter(status.ne(AccountStatus::Deleted))
+    .select(User::as_select())
+    .for_update()
+    .skip_locked()
+    .load::<User>(connection)
+    .await
+    .map_err(|_| Fault::Diesel)
+}
+
+pub async fn u_mark_expiry_processed (connection: &mut Conn, user_ids: &[Uuid]) -> Result<(), Fault> {
   use crate::schema::users::dsl::*;
 
-  let result: usize = update(users.filter(user_id.eq(user)))
-    .set(blocked.eq(false))
+  update(users.filter(user_id.eq_any(user_ids)))
+    .set(expiry_processed_at.eq(Utc::now()))
     .execute(connection)
     .await
-    .or_else(|_| Err(Fault::Diesel))?;
+    .map_err(|_| Fault::Diesel)
+    .map(|_| ())
+}
+
+/// Users that expire after now and at or before `until`, the next to expire first
+pub async fn q_users_expiring (connection: &mut Conn, tenant: Uuid, until: DateTime<Utc>) -> Result<Vec<User>, Fault> {
+  use crate::schema::users::dsl::*;
+
+  users
+    .filter(tenant_id.eq(tenant))
+    .filter(expires_at.gt(Utc::now()))
+    .filter(expires_at.le(until))
+    .filter(status.ne(AccountStatus::Deleted))
+    .order((expires_at.asc(), user_id.asc()))
+    .select(User::as_select())
+    .load::<User>(connection)
+    .await
+    .map_err(|_| Fault::Diesel)
+}
+
+/// Lets a user waiting for approval log in, returns the approved user
+pub async fn u_approve_user (connection: &mut Conn, user: Uuid) -> Result<User, Fault> {
+  use crate::schema::users::dsl::*;
+
+  update(users.filter(user_id.eq(user)).filter(status.eq(AccountStatus::PendingApproval)))
+    .set(status.eq(AccountStatus::Active))
+    .returning(User::as_returning())
+    .get_result::<User>(connection)
+    .await
+    .optional()
+    .map_err(|_| Fault::Diesel)?
+    .ok_or(Fault::NotPendingApproval)
+}
+
+/// Deletes a user waiting for approval like any other deleted user, returns the rejected user
+pub async fn u_reject_user (connection: &mut Conn, user: Uuid) -> Result<User, Fault> {
+  use crate::schema::users::dsl::*;
+
+  update(users.filter(user_id.eq(user)).filter(status.eq(AccountStatus::PendingApproval)))
+    .set((status.eq(AccountStatus::Deleted), deleted_at.eq(Utc::now())))
+    .returning(User::as_returning())
+    .get_result::<User>(connection)
+    .await
+    .optional()
+    .map_err(|_| Fault::Diesel)?
+    .ok_or(Fault::NotPendingApproval)
+}
+
+/// Marks a user as deleted, the row stays until it is purged
+pub async fn u_soft_delete_user (connection: &mut Conn, user: Uuid) -> Result<(), Fault> {
+  use crate::schema::users::dsl::*;
+
+  let result: usize = update(users.filter(user_id.eq(user)).filter(status.eq_any(AccountStatus::predecessors(AccountStatus::Deleted))))
+    .set((status.eq(AccountStatus::Deleted), deleted_at.eq(Utc::now())))
+    .execute(connection)
+    .await
+    .map_err(|_| Fault::Diesel)?;
 
   if result == 0 {
     return Err(Fault::NotFound("user".to_owned()));
@@ -52,12 +445,51 @@ pub async fn u_unblock_user (connection: &mut Conn<'_>, user: Uuid) -> Result<()
   Ok(())
 }
 
-pub async fn d_user (connection: &mut Conn<'_>, user: Uuid) -> Result<(), Fault> {
+/// Restores a user that has been deleted after `deleted_after` as active user, returns the restored user.
+/// A suspension or lockout from before the deletion does not come back.
+pub async fn u_restore_user (connection: &mut Conn, user: Uuid, deleted_after: DateTime<Utc>) -> Result<User, Fault> {
+  use crate::schema::users::dsl::*;
+
+  update(users.filter(user_id.eq(user)).filter(status.eq(AccountStatus::Deleted)).filter(deleted_at.gt(deleted_after)))
+    .set((
+      status.eq(AccountStatus::Active),
+      deleted_at.eq(None::<DateTime<Utc>>),
+      blocked_reason.eq(None::<String>),
+      blocked_until.eq(None::<DateTime<Utc>>),
+      blocked_by.eq(None::<Uuid>),
+      failed_login_attempts.eq(0),
+      locked_at.eq(None::<DateTime<Utc>>),
+      locked_until.eq(None::<DateTime<Utc>>),
+    ))
+    .returning(User::as_returning())
+    .get_result::<User>(connection)
+    .await
+    .optional()
+    .map_err(|_| Fault::Diesel)?
+    .ok_or(Fault::NotFound("user".to_owned()))
+}
+
+/// Users that have been deleted at or before `deleted_before`, locked until the transaction ends
+pub async fn q_lock_purgeable_users (connection: &mut Conn, deleted_before: DateTime<Utc>) -> Result<Vec<User>, Fault> {
+  use crate::schema::users::dsl::*;
+
+  users
+    .filter(status.eq(AccountStatus::Deleted))
+    .filter(deleted_at.le(deleted_before))
+    .select(User::as_select())
+    .for_update()
+    .skip_locked()
+    .load::<User>(connection)
+    .await
+    .map_err(|_| Fault::Diesel)
+}
+
+pub async fn d_user (connection: &mut Conn, user: Uuid) -> Result<(), Fault> {
   use crate::schema::users::dsl::*;
 
   let result: usize = delete(users.filter(user_id.eq(user)))
     .execute(connection)
-    .await.or_else(|_| Err(Fault::Diesel))?;
+    .await.map_err(|_| Fault::Diesel)?;
 
   if result == 0 {
     return Err(Fault::NotFound("user".to_owned()));
@@ -0,0 +1,135 @@
+use chrono::{DateTime, Duration, Utc};
+use diesel_async::{AsyncConnection, scoped_futures::ScopedFutureExt};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+  api::{
+    audit::queries::i_audit_event,
+    auth::{password::{generate_temporary_password, hash_password}, queries::q_get_user_by_id},
+    otp::{routes::insert_generated_otp, queries::d_unredeemed_codes_of_user},
+    user::queries::{u_clear_lockout, u_set_temporary_password},
+  },
+  models::{audit::{AuditAction, NewAuditEvent}, otp::{InsertableOtp, OtpEnum}},
+  state::AppState,
+  utils::error::Fault,
+};
+
+/// How an admin helps a user regain access
+#[derive(Serialize, Deserialize, Clone, Copy, Default, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum PasswordResetMethod {
+  /// A `PW_RESET` code the user redeems with a password of their choice
+  #[default]
+  Code,
+  /// A generated password the user has to change after logging in
+  TemporaryPassword,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct PasswordResetRequest {
+  #[serde(default)]
+  pub method: PasswordResetMethod,
+}
+
+/// Result of an admin password reset. Codes and passwords are only handed out this once, ready to be delivered to the user
+#[derive(Serialize)]
+#[serde(rename_all(serialize="camelCase"))]
+pub struct PasswordReset {
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub temporary_password: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub code: Option<String>,
+  /// `PASSWORD_RESET_LINK` with the code filled in
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub link: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub expires_at: Option<DateTime<Utc>>,
+  pub ended_sessions: usize,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all(serialize="camelCase"))]
+pub struct ForcedLogout {
+  pub ended_sessions: usize,
+}
+
+/// Resets the password of a user on behalf of `admin` and ends all of their sessions.
+/// Codes of earlier resets the user has not redeemed yet are superseded, a lockout caused by failed logins is lifted.
+pub async fn reset_password_as_admin(state: &AppState, user_id: Uuid, admin: Uuid, method: PasswordResetMethod) -> Result<PasswordReset, Fault> {
+  let mut connection = state.pool.get_connection().await?.connection;
+  let config = state.config.clone();
+
+  let mut reset = connection.transaction::<_, Fault, _>(|connection| async move {
+    let user = q_get_user_by_id(connection, user_id).await?;
+    u_clear_lockout(connection, user_id).await?;
+
+    let reset = match method {
+      PasswordResetMethod::TemporaryPassword => {
+        let temporary_password = generate_temporary_password();
+        let hashed = hash_password(temporary_password.clone())
+          .map_err(|_| Fault::Unexpected)?;
+        u_set_temporary_password(connection, user_id, &hashed).await?;
+
+        PasswordReset { temporary_password: Some(temporary_password), code: None, link: None, expires_at: None, ended_sessions: 0 }
+      },
+      PasswordResetMethod::Code => {
+        d_unredeemed_codes_of_user(connection, user_id, OtpEnum::PWRESET).await?;
+
+        let expires_at = Utc::now() + Duration::minutes(config.auth.password_reset_code_ttl);
+        let template = InsertableOtp {
+          code_hash: String::new(),
+          code_prefix: String::new(),
+          tenant_id: user.tenant_id,
+          user: Some(user_id),
+          code_type: OtpEnum::PWRESET,
+          grant_admin: false,
+          restricted_to: None,
+          groups: vec![],
+          account_expires_at: None,
+          email: None,
+          batch_id: None,
+          expires_at: Some(expires_at),
+          created_by: Some(admin),
+        };
+        let (_, code) = insert_generated_otp(connection, &config.otp, &template).await?;
+        let link = config.auth.password_reset_link.as_ref().map(|link| link.replace("{code}", &code));
+
+        PasswordReset { temporary_password: None, code: Some(code), link, expires_at: Some(expires_at), ended_sessions: 0 }
+      },
+    };
+
+    i_audit_event(connection, NewAuditEvent::new(
+      AuditAction::PasswordResetByAdmin,
+      Some(admin),
+      Some(user_id),
+      json!({ "method": method }),
+    )).await?;
+
+    Ok(reset)
+  }.scope_boxed()).await?;
+
+  // whoever knew the old password must not stay logged in
+  reset.ended_sessions = state.redis.invalidate_sessions_of_user(user_id).await?;
+
+  Ok(reset)
+}
+
+/// Ends all sessions of a user on behalf of `admin`, returns the amount of ended sessions
+pub async fn force_logout(state: &AppState, user_id: Uuid, admin: Uuid) -> Result<usize, Fault> {
+  let mut connection = state.pool.get_connection().await?.connection;
+  q_get_user_by_id(&mut connection, user_id).await?;
+
+  let ended_sessions = state.redis.invalidate_sessions_of_user(user_id).await?;
+
+  i_audit_event(&mut connection, NewAuditEvent::new(
+    AuditAction::SessionsRevoked,
+    Some(admin),
+    Some(user_id),
+    json!({ "endedSessions": ended_sessions }),
+  )).await?;
+
+  Ok(ended_sessions)
+}
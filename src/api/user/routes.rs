@@ -0,0 +1,338 @@
+use axum::{
+  Router,
+  routing::{delete, get, patch, post},
+  Extension,
+  extract::{State, Path, Query},
+  http::StatusCode,
+  Json,
+};
+use diesel_async::{AsyncConnection, scoped_futures::ScopedFutureExt};
+use uuid::Uuid;
+
+use crate::{state::AppState, middleware::authorized::require_permission, utils::error::Fault, models::{role::Permissions, tenant::Tenant, user::{AccountStatus, Confirmation, Suspension, User, UsernameChange, UserInfo, UserListQuery, UserPage, UserPatch, UserResponse}}, api::auth::queries::q_get_user_by_id};
+
+use super::{expiry::{collect_expiry_report, ExpiryReport, ExpiryReportQuery, EXPIRY_REPORT_DAYS, EXPIRY_REPORT_MAX_DAYS}, approval::{approve_registration, reject_registration, RegistrationDecision, RegistrationDecisionResponse}, account::{delete_account, restore_account}, detail::{collect_user_detail, UserDetail, UserDetailQuery, DETAIL_EVENTS, DETAIL_MAX_EVENTS}, profile::update_profile, recovery::{force_logout, reset_password_as_admin, ForcedLogout, PasswordReset, PasswordResetRequest}, safeguards::{ensure_can_grant_admin, ensure_confirmed_if_self, ensure_other_admin_remains, ensure_outranks}, suspension::suspend_user, queries::{u_set_admin_on_user, u_unblock_user, q_user_page, q_username_history, USER_PAGE_SIZE, USER_MAX_PAGE_SIZE}};
+
+async fn get_all_users(
+  State(state): State<AppState>,
+  Extension(tenant): Extension<Tenant>,
+  Query(filter): Query<UserListQuery>,
+) -> Result<(StatusCode, Json<UserPage>), Fault> {
+  let page = filter.page.unwrap_or(1).max(1);
+  let per_page = filter.per_page.unwrap_or(USER_PAGE_SIZE).clamp(1, USER_MAX_PAGE_SIZE);
+
+  let mut connection = state
+  .pool.get_connection().await?.connection;
+
+  let (users, total) = q_user_page(&mut connection, tenant.tenant_id, &filter, page, per_page).await?;
+
+  let response = UserPage {
+    users: users.into_iter().map(UserInfo::from).collect(),
+    page,
+    per_page,
+    total,
+    total_pages: (total + per_page - 1) / per_page,
+  };
+
+  Ok((StatusCode::OK, Json(response)))
+}
+
+/// Users waiting for approval, with the same filters as `get_all_users`
+async fn get_pending_users(
+  State(state): State<AppState>,
+  Extension(tenant): Extension<Tenant>,
+  Query(mut filter): Query<UserListQuery>,
+) -> Result<(StatusCode, Json<UserPage>), Fault> {
+  filter.status = Some(AccountStatus::PendingApproval);
+
+  get_all_users(State(state), Extension(tenant), Query(filter)).await
+}
+
+async fn get_expiring_users(
+  State(state): State<AppState>,
+  Extension(tenant): Extension<Tenant>,
+  Query(query): Query<ExpiryReportQuery>,
+) -> Result<(StatusCode, Json<ExpiryReport>), Fault> {
+  let days = query.days.unwrap_or(EXPIRY_REPORT_DAYS).clamp(1, EXPIRY_REPORT_MAX_DAYS);
+
+  let report = collect_expiry_report(&state, &tenant, days).await?;
+
+  Ok((StatusCode::OK, Json(report)))
+}
+
+async fn update_admin(
+  State(state): State<AppState>,
+  Extension(admin): Extension<User>,
+  Extension(permissions): Extension<Permissions>,
+  Path((user_id, is_admin)): Path<(Uuid, bool)>,
+  Query(confirmation): Query<Confirmation>,
+) -> Result<StatusCode, Fault> {
+  if !is_admin {
+    ensure_confirmed_if_self(admin.user_id, user_id, confirmation.confirm)?;
+  }
+
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  connection.transaction::<_, Fault, _>(|connection| async move {
+    ensure_can_grant_admin(connection, &permissions).await?;
+
+    if !is_admin {
+      ensure_other_admin_remains(connection, user_id).await?;
+    }
+
+    u_set_admin_on_user(connection, user_id, is_admin).await
+  }.scope_boxed()).await?;
+
+  Ok(StatusCode::OK)
+}
+async fn lock_user(
+  State(state): State<AppState>,
+  Extension(admin): Extension<User>,
+  Extension(permissions): Extension<Permissions>,
+  Path(user_id): Path<Uuid>,
+  Query(confirmation): Query<Confirmation>,
+) -> Result<StatusCode, Fault> {
+  ensure_confirmed_if_self(admin.user_id, user_id, confirmation.confirm)?;
+
+  ensure_outranks(&mut state.pool.get_connection().await?.connection, &permissions, user_id).await?;
+
+  suspend_user(&state, user_id, &Suspension::default(), admin.user_id).await?;
+
+  Ok(StatusCode::OK)
+}
+async fn suspend(
+  State(state): State<AppState>,
+  Extension(admin): Extension<User>,
+  Extension(permissions): Extension<Permissions>,
+  Path(user_id): Path<Uuid>,
+  Query(confirmation): Query<Confirmation>,
+  Json(suspension): Json<Suspension>,
+) -> Result<StatusCode, Fault> {
+  ensure_confirmed_if_self(admin.user_id, user_id, confirmation.confirm)?;
+
+  ensure_outranks(&mut state.pool.get_connection().await?.connection, &permissions, user_id).await?;
+
+  suspend_user(&state, user_id, &suspension, admin.user_id).await?;
+
+  Ok(StatusCode::OK)
+}
+async fn unlock_user(
+  State(state): State<AppState>,
+  Extension(permissions): Extension<Permissions>,
+  Path(user_id): Path<Uuid>,
+) -> Result<StatusCode, Fault> {
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  ensure_outranks(&mut connection, &permissions, user_id).await?;
+
+  connection.transaction::<_, Fault, _>(|connection| async move {
+    u_unblock_user(connection, user_id).await
+  }.scope_boxed()).await?;
+
+  Ok(StatusCode::OK)
+}
+async fn update_user(
+  State(state): State<AppState>,
+  Extension(admin): Extension<User>,
+  Extension(permissions): Extension<Permissions>,
+  Path(user_id): Path<Uuid>,
+  Query(confirmation): Query<Confirmation>,
+  Json(patch): Json<UserPatch>,
+) -> Result<(StatusCode, Json<UserResponse>), Fault> {
+  if patch.blocked.is_some() || patch.blocked_reason.is_some() || patch.blocked_until.is_some() {
+    permissions.ensure("users.lock")?;
+  }
+
+  if patch.admin == Some(false) || patch.blocked == Some(true) || matches!(patch.expires_at, Some(Some(_))) {
+    ensure_confirmed_if_self(admin.user_id, user_id, confirmation.confirm)?;
+  }
+
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  if patch.admin.is_some() {
+    ensure_can_grant_admin(&mut connection, &permissions).await?;
+  }
+  ensure_outranks(&mut connection, &permissions, user_id).await?;
+
+  let current = q_get_user_by_id(&mut connection, user_id).await?;
+  let updated = update_profile(&state, &current, patch, admin.user_id, true).await?;
+
+  Ok((StatusCode::OK, Json(UserResponse { user: UserInfo::from(updated) })))
+}
+
+async fn get_user_detail(
+  State(state): State<AppState>,
+  Path(user_id): Path<Uuid>,
+  Query(query): Query<UserDetailQuery>,
+) -> Result<(StatusCode, Json<UserDetail>), Fault> {
+  let events = query.events.unwrap_or(DETAIL_EVENTS).clamp(0, DETAIL_MAX_EVENTS);
+
+  let detail = collect_user_detail(&state, user_id, events).await?;
+
+  Ok((StatusCode::OK, Json(detail)))
+}
+
+async fn get_username_history(
+  State(state): State<AppState>,
+  Path(user_id): Path<Uuid>,
+) -> Result<(StatusCode, Json<Vec<UsernameChange>>), Fault> {
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  let history = q_username_history(&mut connection, user_id).await?;
+
+  Ok((StatusCode::OK, Json(history)))
+}
+
+async fn delete_user(
+  State(state): State<AppState>,
+  Extension(admin): Extension<User>,
+  Extension(permissions): Extension<Permissions>,
+  Path(user_id): Path<Uuid>,
+  Query(confirmation): Query<Confirmation>,
+) -> Result<StatusCode, Fault> {
+  ensure_confirmed_if_self(admin.user_id, user_id, confirmation.confirm)?;
+
+  let mut connection = state.pool.get_connection().await?.connection;
+  ensure_outranks(&mut connection, &permissions, user_id).await?;
+
+  connection.transaction::<_, Fault, _>(|connection| async move {
+    let user = q_get_user_by_id(connection, user_id).await?;
+    delete_account(connection, &user, admin.user_id).await
+  }.scope_boxed()).await?;
+
+  state.redis.invalidate_sessions_of_user(user_id).await?;
+
+  Ok(StatusCode::OK)
+}
+
+async fn restore_user(
+  State(state): State<AppState>,
+  Extension(admin): Extension<User>,
+  Extension(permissions): Extension<Permissions>,
+  Path(user_id): Path<Uuid>,
+) -> Result<(StatusCode, Json<UserResponse>), Fault> {
+  let mut connection = state.pool.get_connection().await?.connection;
+
+  ensure_outranks(&mut connection, &permissions, user_id).await?;
+
+  let user = restore_account(&mut connection, user_id, admin.user_id, state.config.account.deleted_retention_days).await?;
+
+  Ok((StatusCode::OK, Json(UserResponse { user: UserInfo::from(user) })))
+}
+
+async fn reset_user_password(
+  State(state): State<AppState>,
+  Extension(admin): Extension<User>,
+  Extension(permissions): Extension<Permissions>,
+  Path(user_id): Path<Uuid>,
+  Json(request): Json<PasswordResetRequest>,
+) -> Result<(StatusCode, Json<PasswordReset>), Fault> {
+  ensure_outranks(&mut state.pool.get_connection().await?.connection, &permissions, user_id).await?;
+
+  let reset = reset_password_as_admin(&state, user_id, admin.user_id, request.method).await?;
+
+  Ok((StatusCode::OK, Json(reset)))
+}
+
+async fn logout_user(
+  State(state): State<AppState>,
+  Extension(admin): Extension<User>,
+  Extension(permissions): Extension<Permissions>,
+  Path(user_id): Path<Uuid>,
+) -> Result<(StatusCode, Json<ForcedLogout>), Fault> {
+  ensure_outranks(&mut state.pool.get_connection().await?.connection, &permissions, user_id).await?;
+
+  let ended_sessions = force_logout(&state, user_id, admin.user_id).await?;
+
+  Ok((StatusCode::OK, Json(ForcedLogout { ended_sessions })))
+}
+
+async fn approve_user(
+  State(state): State<AppState>,
+  Extension(admin): Extension<User>,
+  Path(user_id): Path<Uuid>,
+  Json(decision): Json<RegistrationDecision>,
+) -> Result<(StatusCode, Json<RegistrationDecisionResponse>), Fault> {
+  let response = approve_registration(&state, user_id, admin.user_id, decision).await?;
+
+  Ok((StatusCode::OK, Json(response)))
+}
+
+async fn reject_user(
+  State(state): State<AppState>,
+  Extension(admin): Extension<User>,
+  Path(user_id): Path<Uuid>,
+  Json(decision): Json<RegistrationDecision>,
+) -> Result<(StatusCode, Json<RegistrationDecisionResponse>), Fault> {
+  let response = reject_registration(&state, user_id, admin.user_id, decision).await?;
+
+  Ok((StatusCode::OK, Json(response)))
+}
+
+pub fn router(state: AppState) -> Router<AppState> {
+  Router::new()
+    .route("/users",
+      get(get_all_users)
+      .layer(require_permission(&state, "users.read"))
+    )
+    .route("/users/pending",
+      get(get_pending_users)
+      .layer(require_permission(&state, "users.read"))
+    )
+    .route("/users/expiring",
+      get(get_expiring_users)
+      .layer(require_permission(&state, "users.read"))
+    )
+    .route("/users/{user_id}/approve",
+      post(approve_user)
+      .layer(require_permission(&state, "users.approve"))
+    )
+    .route("/users/{user_id}/reject",
+      post(reject_user)
+      .layer(require_permission(&state, "users.approve"))
+    )
+    .route("/users/{user_id}/admin/{is_admin}",
+      get(update_admin)
+      .layer(require_permission(&state, "roles.assign"))
+    )
+    .route("/users/{user_id}/lock",
+      get(lock_user)
+      .layer(require_permission(&state, "users.lock"))
+    )
+    .route("/users/{user_id}/suspension",
+      post(suspend)
+      .layer(require_permission(&state, "users.lock"))
+    )
+    .route("/users/{user_id}/unlock",
+      get(unlock_user)
+      .layer(require_permission(&state, "users.lock"))
+    )
+    .route("/users/{user_id}/username-history",
+      get(get_username_history)
+      .layer(require_permission(&state, "users.read"))
+    )
+    .route("/users/{user_id}/restore",
+      post(restore_user)
+      .layer(require_permission(&state, "users.delete"))
+    )
+    .route("/users/{user_id}/password-reset",
+      post(reset_user_password)
+      .layer(require_permission(&state, "users.password"))
+    )
+    .route("/users/{user_id}/logout",
+      post(logout_user)
+      .layer(require_permission(&state, "users.sessions"))
+    )
+    .route("/users/{user_id}",
+      get(get_user_detail)
+      .layer(require_permission(&state, "users.read"))
+    )
+    .route("/users/{user_id}",
+      patch(update_user)
+      .layer(require_permission(&state, "users.update"))
+    )
+    .route("/users/{user_id}",
+      delete(delete_user)
+      .layer(require_permission(&state, "users.delete"))
+    )
+}
@@ -0,0 +1,47 @@
+use diesel_async::AsyncPgConnection;
+use uuid::Uuid;
+
+use crate::{api::{role::queries::{q_admin_role, q_permissions_of_roles, q_permissions_of_user}, tenant::queries::q_tenant_of_user, user::queries::q_lock_active_admins}, models::role::Permissions, utils::error::Fault};
+
+/// Fails if `user` is the only active, unblocked admin of their tenant, so demoting, blocking, deleting or expiring them would leave no admin.
+/// Locks the rows of all active admins, which serializes concurrent changes to admins.
+/// Has to be called inside the transaction applying the change.
+pub async fn ensure_other_admin_remains(connection: &mut AsyncPgConnection, user: Uuid) -> Result<(), Fault> {
+  let tenant = q_tenant_of_user(connection, user).await?.ok_or(Fault::NotFound("User".to_string()))?;
+  let admins = q_lock_active_admins(connection, tenant).await?;
+
+  if admins.contains(&user) && admins.len() == 1 {
+    return Err(Fault::LastAdmin);
+  }
+
+  Ok(())
+}
+
+/// Admins demoting, blocking, deleting or expiring themselves have to confirm it explicitly
+pub fn ensure_confirmed_if_self(actor: Uuid, target: Uuid, confirmed: bool) -> Result<(), Fault> {
+  if actor == target && !confirmed {
+    return Err(Fault::ConfirmationRequired);
+  }
+
+  Ok(())
+}
+
+/// Fails if `target` holds a permission the acting user lacks, so nobody can take over or lock out a more privileged user
+pub async fn ensure_outranks(connection: &mut AsyncPgConnection, actor: &Permissions, target: Uuid) -> Result<(), Fault> {
+  let held = q_permissions_of_user(connection, target).await?;
+
+  match held.into_iter().find(|permission| !actor.has(permission)) {
+    Some(permission) => Err(Fault::TargetOutranks(permission)),
+    None => Ok(()),
+  }
+}
+
+/// Granting or revoking the admin role, directly or through a registration code, requires holding all of its permissions
+pub async fn ensure_can_grant_admin(connection: &mut AsyncPgConnection, actor: &Permissions) -> Result<(), Fault> {
+  actor.ensure("roles.assign")?;
+
+  let admin_role = q_admin_role(connection).await?;
+  let granted = q_permissions_of_roles(connection, &[admin_role.role_id]).await?;
+
+  actor.ensure_all(granted.iter().map(|(_, permission)| permission))
+}
@@ -0,0 +1,47 @@
+use chrono::Utc;
+use diesel_async::{AsyncConnection, scoped_futures::ScopedFutureExt};
+use uuid::Uuid;
+
+use crate::{
+  api::user::{safeguards::ensure_other_admin_remains, queries::{u_block_user, u_lift_expired_suspensions}},
+  models::user::Suspension,
+  state::{AppState, postgres_wrapper::WrappedPostgres},
+  utils::{error::Fault, validation::MAX_BLOCKED_REASON_LENGTH},
+};
+
+pub fn validate_suspension(suspension: &Suspension) -> Result<(), Fault> {
+  if let Some(reason) = &suspension.reason {
+    if reason.trim().is_empty() || reason.chars().count() > MAX_BLOCKED_REASON_LENGTH {
+      return Err(Fault::Validation(format!("A reason must consist of 1 to {MAX_BLOCKED_REASON_LENGTH} characters")));
+    }
+  }
+
+  if suspension.until.is_some_and(|until| until <= Utc::now()) {
+    return Err(Fault::Validation("A suspension has to end in the future".to_string()));
+  }
+
+  Ok(())
+}
+
+/// Blocks a user on behalf of `actor` and ends all of their sessions, the last active admin cannot be blocked
+pub async fn suspend_user(state: &AppState, user: Uuid, suspension: &Suspension, actor: Uuid) -> Result<(), Fault> {
+  validate_suspension(suspension)?;
+
+  let mut connection = state.pool.get_connection().await?.connection;
+  connection.transaction::<_, Fault, _>(|connection| async move {
+    ensure_other_admin_remains(connection, user).await?;
+    u_block_user(connection, user, suspension, actor).await
+  }.scope_boxed()).await?;
+
+  state.redis.invalidate_sessions_of_user(user).await?;
+
+  Ok(())
+}
+
+/// Unblocks users whose suspension has run out, returns the amount of unblocked users.
+/// Expired suspensions are ignored when checking a user anyway, this keeps the stored state in line.
+pub async fn lift_expired_suspensions(pool: &WrappedPostgres) -> Result<usize, Fault> {
+  let mut connection = pool.get_connection().await?.connection;
+
+  u_lift_expired_suspensions(&mut connection).await
+}
@@ -0,0 +1,49 @@
+use chrono::{Duration, Utc};
+use diesel_async::AsyncPgConnection;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+  api::{audit::queries::i_audit_event, user::queries::{i_username_change, q_is_username_reserved}},
+  models::{audit::{AuditAction, NewAuditEvent}, user::NewUsernameChange},
+  state::config::ProfileConfig,
+  utils::error::Fault,
+};
+
+/// Fails if `name` has been released by another user of the tenant than `user` within the reservation period.
+/// Names taken by an existing user are rejected by the unique constraint of `users.username_canonical`.
+pub async fn ensure_username_available(connection: &mut AsyncPgConnection, tenant: Uuid, name: &str, user: Option<Uuid>) -> Result<(), Fault> {
+  if q_is_username_reserved(connection, tenant, name, user).await? {
+    return Err(Fault::UsernameReserved);
+  }
+
+  Ok(())
+}
+
+/// Keeps the previous name in the history, reserving it for `USERNAME_RESERVATION_DAYS`, and audits the change.
+/// Should be called inside the transaction that renames the user.
+pub async fn record_username_change(
+  connection: &mut AsyncPgConnection,
+  config: &ProfileConfig,
+  tenant: Uuid,
+  user: Uuid,
+  old_username: &str,
+  new_username: &str,
+  actor: Uuid,
+) -> Result<(), Fault> {
+  i_username_change(connection, NewUsernameChange {
+    user_id: user,
+    tenant_id: tenant,
+    old_username,
+    new_username,
+    changed_by: actor,
+    reserved_until: Utc::now() + Duration::days(config.username_reservation_days),
+  }).await?;
+
+  i_audit_event(connection, NewAuditEvent::new(
+    AuditAction::UsernameChanged,
+    Some(actor),
+    Some(user),
+    json!({ "oldUsername": old_username, "newUsername": new_username }),
+  )).await
+}
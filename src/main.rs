@@ -1,17 +1,31 @@
 use std::sync::Arc;
+use std::time::Duration;
 use rust_auth::api::system_setup::init_admin_user::setup;
+use rust_auth::api::system_setup::hash_legacy_otp::hash_legacy_codes;
+use rust_auth::api::system_setup::canonical_usernames::canonicalize_usernames;
 use rust_auth::state::postgres_wrapper::WrappedPostgres;
 use dotenv::dotenv;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use axum::{Router, middleware};
+use tower::Layer;
 use tower_http::trace::TraceLayer;
 use tower_http::cors::CorsLayer;
 
-use rust_auth::api::auth::auth::router as auth_router;
-use rust_auth::api::user::user::router as user_router;
-use rust_auth::api::otp::otp::router as otp_router;
+use rust_auth::api::auth::routes::router as auth_router;
+use rust_auth::api::user::routes::router as user_router;
+use rust_auth::api::otp::routes::router as otp_router;
+use rust_auth::api::role::routes::router as role_router;
+use rust_auth::api::group::routes::router as group_router;
+use rust_auth::api::tenant::routes::router as tenant_router;
+use rust_auth::middleware::tenant::resolve_tenant;
+use rust_auth::api::user::account::purge_deleted_accounts;
+use rust_auth::api::user::suspension::lift_expired_suspensions;
+use rust_auth::api::user::expiry::revoke_expired_accounts;
 
 use rust_auth::state::AppState;
+use rust_auth::state::config::AppConfig;
 use rust_auth::state::redis_wrapper::WrappedRedis;
+use rust_auth::state::mailer::WrappedMailer;
 
 #[tokio::main]
 async fn main() {
@@ -27,6 +41,8 @@ async fn main() {
         .init();
     // END TRACING SETUP
 
+    let config = AppConfig::new();
+
     // BEGIN Database Setup
     let pg_client = WrappedPostgres::new().await;
     
@@ -36,6 +52,18 @@ async fn main() {
         Ok(_) => println!("Fresh start. Initialized the provided adm-default user"),
         Err(_) => println!("An admin user did already exist, skipped setup of adm user")
     }
+
+    match hash_legacy_codes(&pg_client.postgres, &config.otp).await {
+        Ok(0) => (),
+        Ok(count) => println!("Hashed {} one-time-passwords that were stored in plaintext", count),
+        Err(_) => println!("Failed to hash one-time-passwords that are stored in plaintext")
+    }
+
+    match canonicalize_usernames(&pg_client.postgres).await {
+        Ok(0) => (),
+        Ok(count) => println!("Normalized the canonical form of {} usernames", count),
+        Err(_) => println!("Failed to normalize the canonical form of usernames")
+    }
     // END Database Setup
     // BEGIN REDIS SETUP
     let redis_client = WrappedRedis::new();
@@ -45,12 +73,51 @@ async fn main() {
     let state = AppState {
         pool: Arc::new(pg_client),
         redis: Arc::new(redis_client),
+        config: Arc::new(config),
+        mailer: Arc::new(WrappedMailer::new()),
     };
 
+    // BEGIN ACCOUNT MAINTENANCE
+    let maintenance_state = state.clone();
+    tokio::spawn(async move {
+        let account_config = &maintenance_state.config.account;
+        let mut interval = tokio::time::interval(Duration::from_secs(account_config.purge_interval_minutes.max(1) * 60));
+
+        loop {
+            interval.tick().await;
+
+            match purge_deleted_accounts(&maintenance_state.pool, account_config.deleted_retention_days).await {
+                Ok(0) => (),
+                Ok(count) => println!("Purged {} deleted accounts after their retention period", count),
+                Err(_) => println!("Failed to purge deleted accounts")
+            }
+
+            match lift_expired_suspensions(&maintenance_state.pool).await {
+                Ok(0) => (),
+                Ok(count) => println!("Unblocked {} users whose suspension ran out", count),
+                Err(_) => println!("Failed to lift expired suspensions")
+            }
+
+            match revoke_expired_accounts(&maintenance_state).await {
+                Ok(0) => (),
+                Ok(count) => println!("Revoked the sessions of {} expired accounts", count),
+                Err(_) => println!("Failed to revoke the sessions of expired accounts")
+            }
+        }
+    });
+    // END ACCOUNT MAINTENANCE
+
     let routes = auth_router(state.clone())
         .merge(user_router(state.clone()))
         .merge(otp_router(state.clone()))
-        .with_state(state)
+        .merge(role_router(state.clone()))
+        .merge(group_router(state.clone()))
+        .merge(tenant_router(state.clone()))
+        .with_state(state.clone());
+
+    // the tenant is resolved before routing, so a `/t/{slug}` prefix can be stripped from the path
+    let routes = Router::new()
+        .fallback_service(middleware::from_fn_with_state(state, resolve_tenant).layer(routes))
         .layer(CorsLayer::permissive())
         .layer(TraceLayer::new_for_http());
 
@@ -1,55 +1,106 @@
-use axum::{body::Body, extract::State, http::Request, middleware::Next, response::Response};
+use std::convert::Infallible;
 
-use crate::{utils::{parser::get_authorization_as_uuid, error::Fault}, state::AppState, api::auth::queries::q_get_user_by_id};
+use axum::{body::Body, extract::{FromRequestParts, RawPathParams, State}, http::{HeaderMap, Method, Request}, middleware::{self, Next}, response::Response, routing::Route};
+use tower::{Layer, Service};
+
+use crate::{utils::{parser::get_authorization_as_uuid, error::Fault}, state::AppState, api::{auth::queries::q_get_user_by_id, role::queries::q_permissions_of_user, tenant::scope::ensure_path_in_tenant}, models::{role::Permissions, tenant::Tenant, user::User}};
+
+/// Routes a user whose password was reset by an admin can still reach, everything else waits for a new password
+fn reachable_before_password_change(req: &Request<Body>) -> bool {
+  matches!(
+    (req.method(), req.uri().path()),
+    (&Method::GET, "/auth/self")
+      | (&Method::GET, "/auth/logout")
+      | (&Method::POST, "/auth/update-password-by-password")
+  )
+}
+
+/// The tenant `resolve_tenant` has attached to the request
+pub fn tenant_of(req: &Request<Body>) -> Result<Tenant, Fault> {
+  req.extensions().get::<Tenant>().cloned().ok_or(Fault::Unexpected)
+}
+
+/// The active user the access token of the request belongs to, tokens are only valid within the tenant of their user
+async fn authenticate(state: &AppState, headers: &HeaderMap, tenant: &Tenant) -> Result<User, Fault> {
+  let auth_token = get_authorization_as_uuid(headers).or(Err(Fault::NotLoggedIn))?;
+  let user_uuid = state.redis.get_user_for_access_token(&auth_token).await?;
+
+  let mut connection = state.pool.get_connection().await?;
+
+  let user = q_get_user_by_id(&mut connection.connection, user_uuid).await?;
+
+  if user.tenant_id != tenant.tenant_id {
+    return Err(Fault::NotLoggedIn);
+  }
+
+  user.ensure_active()?;
+
+  Ok(user)
+}
 
 pub async fn logged_in_guard(
   State(state): State<AppState>,
   mut req: Request<Body>,
   next: Next,
 ) -> Result<Response, Fault> {
-  let auth_token = get_authorization_as_uuid(&req.headers());
-  if let Ok(auth_token) = auth_token {
-    let user_uuid = state.redis.get_user_for_access_token(&auth_token).await?;
+  let tenant = tenant_of(&req)?;
+  let user = authenticate(&state, req.headers(), &tenant).await?;
 
-    let mut connection = state.pool.get_connection().await?;
+  if user.password_must_change && !reachable_before_password_change(&req) {
+    return Err(Fault::PasswordChangeRequired);
+  }
 
-    let user = q_get_user_by_id(&mut connection.connection, user_uuid).await?;
+  req.extensions_mut().insert(user);
+  Ok(next.run(req).await)
+}
 
-    if user.blocked.is_some_and(|b| b) {
-      return Err(Fault::UserBlocked);
-    }
+/// State of `permission_guard`, the permission a route requires
+#[derive(Clone)]
+pub struct Permit {
+  state: AppState,
+  permission: &'static str,
+}
 
-    req.extensions_mut().insert(user);
-    Ok(next.run(req).await)
-  } else {
-    Err(Fault::NotLoggedIn)
-  }
+/// Lets only users holding `permission` through one of their roles reach the route.
+/// The route receives the user and all of their `Permissions` as extensions.
+/// Users, groups, roles and codes named by the path have to belong to the tenant of the request, others are reported as not found.
+pub fn require_permission(
+  state: &AppState,
+  permission: &'static str,
+) -> impl Layer<
+  Route,
+  Service = impl Service<Request<Body>, Response = Response, Error = Infallible, Future = impl Send> + Clone + Send + Sync + 'static,
+> + Clone + Send + Sync + 'static {
+  middleware::from_fn_with_state(Permit { state: state.clone(), permission }, permission_guard)
 }
 
-pub async fn admin_guard(
-  State(state): State<AppState>,
-  mut req: Request<Body>,
+async fn permission_guard(
+  State(permit): State<Permit>,
+  req: Request<Body>,
   next: Next,
 ) -> Result<Response, Fault> {
-  let auth_token = get_authorization_as_uuid(&req.headers());
-  if let Ok(auth_token) = auth_token {
-    let user_uuid = state.redis.get_user_for_access_token(&auth_token).await?;
-
-    let mut connection = state.pool.get_connection().await?;
-
-    let user = q_get_user_by_id(&mut connection.connection, user_uuid).await?;
-
-    if let (Some(admin), Some(blocked)) = (user.admin, user.blocked) {
-      if blocked {
-        return Err(Fault::UserBlocked);
-      }
-      if admin {
-        req.extensions_mut().insert(user);
-        return Ok(next.run(req).await);
-      }
-    }
-    return Err(Fault::Unallowed)
-  } else {
-    Err(Fault::NotLoggedIn)
+  let tenant = tenant_of(&req)?;
+  let user = authenticate(&permit.state, req.headers(), &tenant).await?;
+
+  if user.password_must_change {
+    return Err(Fault::PasswordChangeRequired);
   }
-}
\ No newline at end of file
+
+  let (mut parts, body) = req.into_parts();
+  let params = RawPathParams::from_request_parts(&mut parts, &()).await.or(Err(Fault::Unexpected))?;
+
+  let permissions = {
+    let mut connection = permit.state.pool.get_connection().await?;
+    let permissions = Permissions(q_permissions_of_user(&mut connection.connection, user.user_id).await?.into_iter().collect());
+
+    permissions.ensure(permit.permission)?;
+    ensure_path_in_tenant(&mut connection.connection, &tenant, &params).await?;
+
+    permissions
+  };
+
+  let mut req = Request::from_parts(parts, body);
+  req.extensions_mut().insert(user);
+  req.extensions_mut().insert(permissions);
+  Ok(next.run(req).await)
+}
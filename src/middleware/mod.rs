@@ -1 +1,2 @@
-pub mod authorized;
\ No newline at end of file
+pub mod authorized;
+pub mod tenant;
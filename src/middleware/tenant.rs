@@ -0,0 +1,94 @@
+use axum::{body::Body, extract::State, http::{header::HOST, Request, Uri}, middleware::Next, response::Response};
+
+use crate::{api::tenant::queries::q_tenant_by_slug, models::tenant::DEFAULT_TENANT, state::AppState, utils::error::Fault};
+
+/// Splits `/t/{slug}/rest?query` into the slug and `/rest?query`
+fn strip_tenant_prefix(uri: &Uri) -> Option<(String, Uri)> {
+  let rest = uri.path().strip_prefix("/t/")?;
+  let (slug, path) = match rest.find('/') {
+    Some(at) => rest.split_at(at),
+    None => (rest, "/"),
+  };
+
+  let path_and_query = match uri.query() {
+    Some(query) => format!("{path}?{query}"),
+    None => path.to_string(),
+  };
+
+  let mut parts = uri.clone().into_parts();
+  parts.path_and_query = Some(path_and_query.parse().ok()?);
+
+  Some((slug.to_lowercase(), Uri::from_parts(parts).ok()?))
+}
+
+/// Resolves the tenant of a request and hands it to the routes as `Tenant` extension, unknown tenants are rejected.
+/// Has to wrap the router instead of being one of its layers, so the path prefix is gone before the request is routed.
+pub async fn resolve_tenant(
+  State(state): State<AppState>,
+  mut req: Request<Body>,
+  next: Next,
+) -> Result<Response, Fault> {
+  let config = &state.config.tenant;
+
+  let slug = match strip_tenant_prefix(req.uri()) {
+    Some((slug, uri)) => {
+      *req.uri_mut() = uri;
+      Some(slug)
+    },
+    None => req.headers().get(config.header.as_str())
+      .and_then(|value| value.to_str().ok())
+      .map(|value| value.trim().to_lowercase())
+      .filter(|value| !value.is_empty())
+      .or_else(|| {
+        let host = req.uri().host().or_else(|| req.headers().get(HOST).and_then(|value| value.to_str().ok()))?;
+        config.slug_of_host(host)
+      }),
+  };
+
+  // the connection is returned to the pool before the route runs
+  let tenant = {
+    let mut connection = state.pool.get_connection().await?;
+    q_tenant_by_slug(&mut connection.connection, slug.as_deref().unwrap_or(DEFAULT_TENANT)).await?
+  };
+
+  req.extensions_mut().insert(tenant);
+  Ok(next.run(req).await)
+}
+
+#[cfg(test)]
+mod tests {
+  use axum::http::Uri;
+
+  use super::strip_tenant_prefix;
+
+  fn strip(uri: &str) -> Option<(String, String)> {
+    strip_tenant_prefix(&uri.parse::<Uri>().unwrap()).map(|(slug, uri)| (slug, uri.to_string()))
+  }
+
+  #[test]
+  fn splits_slug_from_path() {
+    assert_eq!(strip("/t/acme/auth/login"), Some(("acme".to_string(), "/auth/login".to_string())));
+  }
+
+  #[test]
+  fn keeps_the_query() {
+    assert_eq!(strip("/t/acme/users?page=2&perPage=10"), Some(("acme".to_string(), "/users?page=2&perPage=10".to_string())));
+  }
+
+  #[test]
+  fn slug_without_path_points_to_root() {
+    assert_eq!(strip("/t/acme"), Some(("acme".to_string(), "/".to_string())));
+  }
+
+  #[test]
+  fn slug_is_lowercased() {
+    assert_eq!(strip("/t/ACME/users"), Some(("acme".to_string(), "/users".to_string())));
+  }
+
+  #[test]
+  fn paths_without_prefix_are_left_alone() {
+    assert_eq!(strip("/auth/login"), None);
+    assert_eq!(strip("/tenants/t/acme"), None);
+    assert_eq!(strip("/t"), None);
+  }
+}
@@ -0,0 +1,92 @@
+use std::fmt::Display;
+
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::schema::audit_events;
+
+/// Actions that are recorded in the audit log, stored by their `Display` name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditAction {
+  UsernameChanged,
+  AccountDeleted,
+  AccountRestored,
+  AccountPurged,
+  PasswordResetByAdmin,
+  SessionsRevoked,
+  RegistrationApproved,
+  RegistrationRejected,
+  AccountExpired,
+  RoleCreated,
+  RoleUpdated,
+  RoleDeleted,
+  RoleGranted,
+  RoleRevoked,
+  GroupCreated,
+  GroupUpdated,
+  GroupDeleted,
+  GroupMemberAdded,
+  GroupMemberRemoved,
+  TenantCreated,
+  TenantUpdated,
+  TenantDeleted,
+  TenantAdminInvited,
+}
+
+impl Display for AuditAction {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      AuditAction::UsernameChanged => write!(f, "USERNAME_CHANGED"),
+      AuditAction::AccountDeleted => write!(f, "ACCOUNT_DELETED"),
+      AuditAction::AccountRestored => write!(f, "ACCOUNT_RESTORED"),
+      AuditAction::AccountPurged => write!(f, "ACCOUNT_PURGED"),
+      AuditAction::PasswordResetByAdmin => write!(f, "PASSWORD_RESET_BY_ADMIN"),
+      AuditAction::SessionsRevoked => write!(f, "SESSIONS_REVOKED"),
+      AuditAction::RegistrationApproved => write!(f, "REGISTRATION_APPROVED"),
+      AuditAction::RegistrationRejected => write!(f, "REGISTRATION_REJECTED"),
+      AuditAction::AccountExpired => write!(f, "ACCOUNT_EXPIRED"),
+      AuditAction::RoleCreated => write!(f, "ROLE_CREATED"),
+      AuditAction::RoleUpdated => write!(f, "ROLE_UPDATED"),
+      AuditAction::RoleDeleted => write!(f, "ROLE_DELETED"),
+      AuditAction::RoleGranted => write!(f, "ROLE_GRANTED"),
+      AuditAction::RoleRevoked => write!(f, "ROLE_REVOKED"),
+      AuditAction::GroupCreated => write!(f, "GROUP_CREATED"),
+      AuditAction::GroupUpdated => write!(f, "GROUP_UPDATED"),
+      AuditAction::GroupDeleted => write!(f, "GROUP_DELETED"),
+      AuditAction::GroupMemberAdded => write!(f, "GROUP_MEMBER_ADDED"),
+      AuditAction::GroupMemberRemoved => write!(f, "GROUP_MEMBER_REMOVED"),
+      AuditAction::TenantCreated => write!(f, "TENANT_CREATED"),
+      AuditAction::TenantUpdated => write!(f, "TENANT_UPDATED"),
+      AuditAction::TenantDeleted => write!(f, "TENANT_DELETED"),
+      AuditAction::TenantAdminInvited => write!(f, "TENANT_ADMIN_INVITED"),
+    }
+  }
+}
+
+#[derive(Queryable, Selectable, serde::Serialize)]
+#[diesel(table_name = audit_events)]
+#[serde(rename_all(serialize="camelCase"))]
+pub struct AuditEvent {
+  pub id: i64,
+  pub actor: Option<Uuid>,
+  pub subject: Option<Uuid>,
+  pub action: String,
+  pub details: serde_json::Value,
+  pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = audit_events)]
+pub struct NewAuditEvent {
+  pub actor: Option<Uuid>,
+  pub subject: Option<Uuid>,
+  pub action: String,
+  pub details: serde_json::Value,
+}
+
+impl NewAuditEvent {
+  pub fn new(action: AuditAction, actor: Option<Uuid>, subject: Option<Uuid>, details: serde_json::Value) -> Self {
+    NewAuditEvent { actor, subject, action: action.to_string(), details }
+  }
+}
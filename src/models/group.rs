@@ -0,0 +1,105 @@
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::{schema::{groups, group_members, group_roles}, utils::patch::double_option};
+
+#[derive(serde::Serialize, Selectable, Queryable, Clone)]
+#[serde(rename_all(serialize="camelCase"))]
+pub struct Group {
+  pub group_id: Uuid,
+  pub name: String,
+  pub description: Option<String>,
+  /// Members of this group count as members of the parent group as well
+  pub parent_id: Option<Uuid>,
+  pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = groups)]
+pub struct NewGroup<'a> {
+  pub group_id: Uuid,
+  pub tenant_id: Uuid,
+  pub name: &'a str,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = groups)]
+pub struct NewNestedGroup<'a> {
+  pub group_id: Uuid,
+  pub tenant_id: Uuid,
+  pub name: &'a str,
+  pub description: Option<&'a str>,
+  pub parent_id: Option<Uuid>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct NewGroupRequest {
+  pub name: String,
+  pub description: Option<String>,
+  pub parent_id: Option<Uuid>,
+}
+
+/// Partial update of a group, a `null` parent moves the group to the top level
+#[derive(serde::Deserialize, Default)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct GroupPatch {
+  pub name: Option<String>,
+  #[serde(default, deserialize_with = "double_option")]
+  pub description: Option<Option<String>>,
+  #[serde(default, deserialize_with = "double_option")]
+  pub parent_id: Option<Option<Uuid>>,
+}
+
+#[derive(AsChangeset, Default)]
+#[diesel(table_name = groups)]
+pub struct GroupChangeset {
+  pub name: Option<String>,
+  pub description: Option<Option<String>>,
+  pub parent_id: Option<Option<Uuid>>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = group_members)]
+pub struct NewGroupMember {
+  pub group_id: Uuid,
+  pub user_id: Uuid,
+  pub added_by: Option<Uuid>,
+}
+
+/// A user in a group, either directly or as member of a group below it
+#[derive(serde::Serialize, Queryable)]
+#[serde(rename_all(serialize="camelCase"))]
+pub struct GroupMember {
+  pub user_id: Uuid,
+  pub username: String,
+  /// The group the user has been added to
+  pub via_group_id: Uuid,
+  pub added_by: Option<Uuid>,
+  pub added_at: DateTime<Utc>,
+}
+
+#[derive(serde::Deserialize, Default)]
+pub struct GroupMemberQuery {
+  /// Includes the members of all groups below
+  #[serde(default)]
+  pub nested: bool,
+}
+
+/// A group a user belongs to, `direct` is false for groups the user only belongs to through a group below
+#[derive(serde::Serialize)]
+#[serde(rename_all(serialize="camelCase"))]
+pub struct GroupMembership {
+  pub group_id: Uuid,
+  pub name: String,
+  pub direct: bool,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = group_roles)]
+pub struct NewGroupRole {
+  pub group_id: Uuid,
+  pub role_id: Uuid,
+  pub granted_by: Option<Uuid>,
+}
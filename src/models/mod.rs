@@ -1,3 +1,11 @@
 pub mod user;
 
 pub mod otp;
+
+pub mod group;
+
+pub mod audit;
+
+pub mod role;
+
+pub mod tenant;
@@ -1,15 +1,18 @@
-use std::{io::Write, str::FromStr};
+use std::{fmt::Display, io::Write, str::FromStr};
 
 use diesel::{backend::Backend, deserialize::FromSql, pg::Pg, prelude::*, serialize::{IsNull, ToSql}, AsExpression, FromSqlRow};
+use chrono::{DateTime, Utc};
 use serde::{Serialize, Deserialize};
 use uuid::Uuid;
-use crate::schema::{otp, sql_types::OtpType};
+use crate::schema::{otp, otp_batches, sql_types::OtpType};
 
 #[derive(Debug, PartialEq, FromSqlRow, AsExpression, Eq, Clone, Serialize, Deserialize)]
 #[diesel(sql_type = OtpType)]
 pub enum OtpEnum {
   REGISTER,
   PWRESET,
+  EMAILVERIFY,
+  UNLOCK,
 }
 
 impl ToSql<OtpType, Pg> for OtpEnum {
@@ -17,6 +20,8 @@ impl ToSql<OtpType, Pg> for OtpEnum {
     match *self {
       OtpEnum::PWRESET => out.write_all(b"PW_RESET")?,
       OtpEnum::REGISTER => out.write_all(b"REGISTER")?,
+      OtpEnum::EMAILVERIFY => out.write_all(b"EMAIL_VERIFY")?,
+      OtpEnum::UNLOCK => out.write_all(b"UNLOCK")?,
     }
     Ok(IsNull::No)
   }
@@ -27,6 +32,8 @@ impl FromSql<OtpType, Pg> for OtpEnum {
     match bytes.as_bytes() {
       b"PW_RESET" => Ok(OtpEnum::PWRESET),
       b"REGISTER" => Ok(OtpEnum::REGISTER),
+      b"EMAIL_VERIFY" => Ok(OtpEnum::EMAILVERIFY),
+      b"UNLOCK" => Ok(OtpEnum::UNLOCK),
       _ => Err("Unrecognized enum variant".into()),
     }
   }
@@ -38,16 +45,20 @@ impl FromStr for OtpEnum {
     match s {
       "REGISTER" => Ok(OtpEnum::REGISTER),
       "PW_RESET" => Ok(OtpEnum::PWRESET),
+      "EMAIL_VERIFY" => Ok(OtpEnum::EMAILVERIFY),
+      "UNLOCK" => Ok(OtpEnum::UNLOCK),
       _ => Err(()),
     }
   }
 }
 
-impl ToString for OtpEnum {
-  fn to_string(&self) -> String {
+impl Display for OtpEnum {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
       match self {
-        OtpEnum::PWRESET => "PW_RESET".into(),
-        OtpEnum::REGISTER => "REGISTER".into(),
+        OtpEnum::PWRESET => write!(f, "PW_RESET"),
+        OtpEnum::REGISTER => write!(f, "REGISTER"),
+        OtpEnum::EMAILVERIFY => write!(f, "EMAIL_VERIFY"),
+        OtpEnum::UNLOCK => write!(f, "UNLOCK"),
       }
   }
 }
@@ -66,35 +77,103 @@ impl ToString for OtpEnum {
 #[diesel(table_name = otp)]
 pub struct OtpInternal {
   pub id: i32,
-  pub code: String,
+  pub code_prefix: String,
   pub code_type: OtpEnum,
   pub user: Option<Uuid>,
+  pub grant_admin: bool,
+  pub restricted_to: Option<String>,
+  pub groups: Vec<Option<String>>,
+  pub account_expires_at: Option<DateTime<Utc>>,
+  pub email: Option<String>,
+  pub batch_id: Option<Uuid>,
+  pub expires_at: Option<DateTime<Utc>>,
+  pub created_by: Option<Uuid>,
+  pub created_at: DateTime<Utc>,
+  pub redeemed_by: Option<Uuid>,
+  pub redeemed_at: Option<DateTime<Utc>>,
 }
 
-impl From<OtpExternal> for OtpInternal {
-  fn from(value: OtpExternal) -> Self {
-      Self {
-        id: value.id,
-        code: value.code,
-        code_type: OtpEnum::from_str(value.code_type.as_str()).ok().unwrap(),
-        user: value.user,
-      }
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum OtpStatus {
+  Active,
+  Redeemed,
+  Expired,
+}
+
+impl OtpInternal {
+  pub fn status(&self) -> OtpStatus {
+    if self.redeemed_at.is_some() {
+      return OtpStatus::Redeemed;
+    }
+
+    match self.expires_at {
+      Some(expiry) if expiry <= Utc::now() => OtpStatus::Expired,
+      _ => OtpStatus::Active,
+    }
   }
+
+  /// The payload of a `REGISTER` code, `None` for all other code types
+  pub fn registration_payload(&self) -> Option<RegistrationPayload> {
+    if self.code_type != OtpEnum::REGISTER {
+      return None;
+    }
+
+    Some(RegistrationPayload {
+      admin: self.grant_admin,
+      restricted_to: self.restricted_to.clone(),
+      groups: self.groups.iter().flatten().cloned().collect(),
+      account_expires_at: self.account_expires_at,
+    })
+  }
+}
+
+/// Attributes a `REGISTER` code assigns to the account that is created with it
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RegistrationPayload {
+  pub admin: bool,
+  /// Username the code may exclusively be redeemed for
+  pub restricted_to: Option<String>,
+  /// Names of the groups the new user becomes a member of, missing groups are created
+  pub groups: Vec<String>,
+  pub account_expires_at: Option<DateTime<Utc>>,
 }
 
-#[derive(Serialize, Deserialize, Queryable)]
+#[derive(Serialize, Deserialize)]
 #[serde(rename_all(serialize="camelCase"))]
 pub struct OtpExternal {
   pub id: i32,
-  pub code: String,
+  /// Only the first few characters of the code, the full code is never stored
+  pub code_prefix: String,
   pub code_type: String,
   pub user: Option<Uuid>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub email: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub payload: Option<RegistrationPayload>,
+  pub batch_id: Option<Uuid>,
+  pub expires_at: Option<DateTime<Utc>>,
+  pub status: OtpStatus,
+  pub created_by: Option<Uuid>,
+  pub created_at: DateTime<Utc>,
+  pub redeemed_by: Option<Uuid>,
+  pub redeemed_at: Option<DateTime<Utc>>,
 }
 
 impl From<OtpInternal> for OtpExternal {
   fn from(value: OtpInternal) -> Self {
       Self {
-        code: value.code,
+        status: value.status(),
+        created_by: value.created_by,
+        created_at: value.created_at,
+        redeemed_by: value.redeemed_by,
+        redeemed_at: value.redeemed_at,
+        payload: value.registration_payload(),
+        email: value.email,
+        batch_id: value.batch_id,
+        expires_at: value.expires_at,
+        code_prefix: value.code_prefix,
         code_type: value.code_type.to_string(),
         id: value.id,
         user: value.user,
@@ -105,15 +184,113 @@ impl From<OtpInternal> for OtpExternal {
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct NewOtp {
+  /// Optional client-supplied code, only accepted when `OTP_ALLOW_CLIENT_CODES` is enabled
+  pub code: Option<String>,
+  pub user: Option<Uuid>,
+  /// Address an `EMAIL_VERIFY` code confirms
+  pub email: Option<String>,
+  /// Only supported for `REGISTER` codes
+  pub payload: Option<RegistrationPayload>,
+  pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Response for a freshly created OTP. This is the only time the full code is handed out.
+#[derive(Serialize)]
+#[serde(rename_all(serialize="camelCase"))]
+pub struct CreatedOtp {
+  pub id: i32,
   pub code: String,
+  pub code_type: String,
   pub user: Option<Uuid>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub email: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub payload: Option<RegistrationPayload>,
+  pub expires_at: Option<DateTime<Utc>>,
 }
 
-#[derive(Insertable, Deserialize)]
+impl CreatedOtp {
+  /// `code` is the code in its display format, as it should be handed to the recipient
+  pub fn from_internal(value: OtpInternal, code: String) -> Self {
+    Self {
+      payload: value.registration_payload(),
+      email: value.email,
+      expires_at: value.expires_at,
+      id: value.id,
+      code,
+      code_type: value.code_type.to_string(),
+      user: value.user,
+    }
+  }
+}
+
+#[derive(Insertable, Deserialize, Clone)]
 #[diesel(table_name = otp)]
 #[serde(rename_all = "camelCase")]
 pub struct InsertableOtp {
-  pub code: String,
+  pub code_hash: String,
+  pub code_prefix: String,
+  pub tenant_id: Uuid,
+  pub user: Option<Uuid>,
+  pub code_type: OtpEnum,
+  pub grant_admin: bool,
+  pub restricted_to: Option<String>,
+  pub groups: Vec<Option<String>>,
+  pub account_expires_at: Option<DateTime<Utc>>,
+  pub email: Option<String>,
+  pub batch_id: Option<Uuid>,
+  pub expires_at: Option<DateTime<Utc>>,
+  pub created_by: Option<Uuid>,
+}
+
+#[derive(Queryable, Selectable, Serialize, Clone)]
+#[diesel(table_name = otp_batches)]
+#[serde(rename_all(serialize="camelCase"))]
+pub struct OtpBatch {
+  pub batch_id: Uuid,
+  pub label: String,
+  pub created_by: Option<Uuid>,
+  pub created_at: DateTime<Utc>,
+  pub expires_at: Option<DateTime<Utc>>,
+  pub revoked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = otp_batches)]
+pub struct NewOtpBatch<'a> {
+  pub batch_id: Uuid,
+  pub tenant_id: Uuid,
+  pub label: &'a str,
+  pub created_by: Option<Uuid>,
+  pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewOtpBatchRequest {
+  pub label: String,
+  pub count: usize,
+  pub expires_at: Option<DateTime<Utc>>,
+  pub payload: Option<RegistrationPayload>,
+}
+
+/// Filters for listing OTPs, results are paginated by `cursor` (the id of the last OTP of the previous page)
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct OtpListQuery {
+  pub batch: Option<Uuid>,
+  #[serde(rename = "type")]
+  pub code_type: Option<String>,
   pub user: Option<Uuid>,
-  pub code_type: OtpEnum
+  pub status: Option<OtpStatus>,
+  pub cursor: Option<i32>,
+  pub limit: Option<i64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all(serialize="camelCase"))]
+pub struct OtpPage {
+  pub otps: Vec<OtpExternal>,
+  /// Cursor of the next page, `None` if this is the last page
+  pub next_cursor: Option<i32>,
 }
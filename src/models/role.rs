@@ -0,0 +1,140 @@
+use std::collections::BTreeSet;
+
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::{schema::{permissions, roles, role_permissions, user_roles}, utils::{error::Fault, patch::double_option}};
+
+/// Name of the system role that holds every permission, `users.admin` tells whether a user holds it
+pub const ADMIN_ROLE: &str = "admin";
+
+/// Name of the system role that additionally manages tenants, it is not tied to a tenant like all system roles
+pub const SUPERADMIN_ROLE: &str = "superadmin";
+
+/// An entry of the permission catalog, permissions are added by migrations only
+#[derive(serde::Serialize, Selectable, Queryable)]
+#[diesel(table_name = permissions)]
+pub struct Permission {
+  pub name: String,
+  pub description: String,
+}
+
+#[derive(Selectable, Queryable, Clone)]
+#[diesel(table_name = roles)]
+pub struct Role {
+  pub role_id: Uuid,
+  pub name: String,
+  pub description: Option<String>,
+  pub system: bool,
+  pub created_at: DateTime<Utc>,
+}
+
+/// A role together with the permissions it grants
+#[derive(serde::Serialize)]
+#[serde(rename_all(serialize="camelCase"))]
+pub struct RoleInfo {
+  pub role_id: Uuid,
+  pub name: String,
+  pub description: Option<String>,
+  /// System roles are maintained by migrations and cannot be changed
+  pub system: bool,
+  pub created_at: DateTime<Utc>,
+  pub permissions: Vec<String>,
+}
+
+impl RoleInfo {
+  pub fn new(role: Role, permissions: Vec<String>) -> Self {
+    RoleInfo {
+      role_id: role.role_id,
+      name: role.name,
+      description: role.description,
+      system: role.system,
+      created_at: role.created_at,
+      permissions,
+    }
+  }
+}
+
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NewRoleRequest {
+  pub name: String,
+  pub description: Option<String>,
+  #[serde(default)]
+  pub permissions: Vec<String>,
+}
+
+/// Partial update of a role, `permissions` replaces all permissions of the role
+#[derive(serde::Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct RolePatch {
+  pub name: Option<String>,
+  #[serde(default, deserialize_with = "double_option")]
+  pub description: Option<Option<String>>,
+  pub permissions: Option<Vec<String>>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = roles)]
+pub struct NewRole<'a> {
+  pub role_id: Uuid,
+  /// Custom roles always belong to the tenant they were created in
+  pub tenant_id: Uuid,
+  pub name: &'a str,
+  pub description: Option<&'a str>,
+}
+
+#[derive(AsChangeset, Default)]
+#[diesel(table_name = roles)]
+pub struct RoleChangeset {
+  pub name: Option<String>,
+  pub description: Option<Option<String>>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = role_permissions)]
+pub struct NewRolePermission<'a> {
+  pub role_id: Uuid,
+  pub permission: &'a str,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = user_roles)]
+pub struct NewUserRole {
+  pub user_id: Uuid,
+  pub role_id: Uuid,
+  pub granted_by: Option<Uuid>,
+}
+
+/// A role held by a user
+#[derive(serde::Serialize, Queryable)]
+#[serde(rename_all(serialize="camelCase"))]
+pub struct GrantedRole {
+  pub role_id: Uuid,
+  pub name: String,
+  pub granted_by: Option<Uuid>,
+  pub granted_at: DateTime<Utc>,
+}
+
+/// The permissions of the user making a request, handed to the routes by `require_permission`
+#[derive(Clone, Default)]
+pub struct Permissions(pub BTreeSet<String>);
+
+impl Permissions {
+  pub fn has(&self, permission: &str) -> bool {
+    self.0.contains(permission)
+  }
+
+  pub fn ensure(&self, permission: &str) -> Result<(), Fault> {
+    match self.has(permission) {
+      true => Ok(()),
+      false => Err(Fault::MissingPermission(permission.to_string())),
+    }
+  }
+
+  /// Nobody can hand out or take away permissions they do not hold themselves
+  pub fn ensure_all<'a>(&self, permissions: impl IntoIterator<Item = &'a String>) -> Result<(), Fault> {
+    permissions.into_iter().try_for_each(|permission| self.ensure(permission))
+  }
+}
@@ -0,0 +1,50 @@
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::schema::tenants;
+
+/// Slug of the tenant serving requests that do not name a tenant, it cannot be deleted
+pub const DEFAULT_TENANT: &str = "default";
+
+/// An organization with its own users, codes, groups and roles
+#[derive(serde::Serialize, Selectable, Queryable, Clone)]
+#[serde(rename_all(serialize="camelCase"))]
+pub struct Tenant {
+  pub tenant_id: Uuid,
+  /// Names the tenant in the path prefix, header or subdomain of a request, cannot be changed
+  pub slug: String,
+  pub name: String,
+  pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = tenants)]
+pub struct NewTenant<'a> {
+  pub tenant_id: Uuid,
+  pub slug: &'a str,
+  pub name: &'a str,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NewTenantRequest {
+  pub slug: String,
+  pub name: String,
+}
+
+#[derive(serde::Deserialize, AsChangeset, Default)]
+#[diesel(table_name = tenants)]
+#[serde(deny_unknown_fields)]
+pub struct TenantPatch {
+  pub name: Option<String>,
+}
+
+/// Registration code for the first admin of a tenant
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct AdminInvitationRequest {
+  /// The code can only be redeemed for this username
+  pub username: String,
+  pub expires_at: Option<DateTime<Utc>>,
+}
@@ -1,14 +1,133 @@
-use diesel::prelude::*;
+use std::{fmt::Display, io::Write};
+
+use chrono::{DateTime, Utc};
+use diesel::{backend::Backend, deserialize::FromSql, pg::Pg, prelude::*, serialize::{IsNull, ToSql}, AsExpression, FromSqlRow};
 use uuid::Uuid;
-use crate::{schema::users, utils::error::Fault, api::auth::password::hash_password};
+use crate::{schema::{users, username_history, sql_types::AccountStatusType}, utils::{error::Fault, patch::double_option}, api::auth::password::hash_password};
+
+/// Lifecycle of an account, only `ACTIVE` users can log in and use their sessions.
+/// `locked_*`, `deleted_at` and the `blocked_*` fields hold the details of the status they belong to.
+#[derive(Debug, PartialEq, FromSqlRow, AsExpression, Eq, Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[diesel(sql_type = AccountStatusType)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AccountStatus {
+  PendingApproval,
+  Active,
+  /// Blocked by an admin, possibly until `blocked_until`
+  Suspended,
+  /// Locked after too many failed logins, lifted by an unlock code or an admin
+  Locked,
+  /// Can be restored until the retention period has passed
+  Deleted,
+}
+
+impl AccountStatus {
+  pub const ALL: [AccountStatus; 5] = [
+    AccountStatus::PendingApproval,
+    AccountStatus::Active,
+    AccountStatus::Suspended,
+    AccountStatus::Locked,
+    AccountStatus::Deleted,
+  ];
+
+  /// Whether an account in this status may be moved to `next`.
+  /// A suspended account may be suspended again, which replaces the details of its suspension.
+  pub fn can_become(&self, next: AccountStatus) -> bool {
+    use AccountStatus::*;
+
+    matches!(
+      (self, next),
+      (PendingApproval, Active | Deleted)
+        | (Active, Suspended | Locked | Deleted)
+        | (Suspended, Active | Suspended | Deleted)
+        | (Locked, Active | Suspended | Deleted)
+        | (Deleted, Active)
+    )
+  }
+
+  /// All statuses an account may be moved to `next` from, to guard updates against concurrent changes
+  pub fn predecessors(next: AccountStatus) -> Vec<AccountStatus> {
+    AccountStatus::ALL.into_iter().filter(|status| status.can_become(next)).collect()
+  }
+
+  pub fn ensure_can_become(&self, next: AccountStatus) -> Result<(), Fault> {
+    match self.can_become(next) {
+      true => Ok(()),
+      false => Err(Fault::InvalidStatusTransition { from: *self, to: next }),
+    }
+  }
+}
+
+impl ToSql<AccountStatusType, Pg> for AccountStatus {
+  fn to_sql<'b>(&'b self, out: &mut diesel::serialize::Output<'b, '_, Pg>) -> diesel::serialize::Result {
+    out.write_all(self.to_string().as_bytes())?;
+    Ok(IsNull::No)
+  }
+}
+
+impl FromSql<AccountStatusType, Pg> for AccountStatus {
+  fn from_sql(bytes: <Pg as Backend>::RawValue<'_>) -> diesel::deserialize::Result<Self> {
+    match bytes.as_bytes() {
+      b"PENDING_APPROVAL" => Ok(AccountStatus::PendingApproval),
+      b"ACTIVE" => Ok(AccountStatus::Active),
+      b"SUSPENDED" => Ok(AccountStatus::Suspended),
+      b"LOCKED" => Ok(AccountStatus::Locked),
+      b"DELETED" => Ok(AccountStatus::Deleted),
+      _ => Err("Unrecognized enum variant".into()),
+    }
+  }
+}
+
+impl Display for AccountStatus {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      AccountStatus::PendingApproval => write!(f, "PENDING_APPROVAL"),
+      AccountStatus::Active => write!(f, "ACTIVE"),
+      AccountStatus::Suspended => write!(f, "SUSPENDED"),
+      AccountStatus::Locked => write!(f, "LOCKED"),
+      AccountStatus::Deleted => write!(f, "DELETED"),
+    }
+  }
+}
 
 #[derive(serde::Serialize, serde::Deserialize, Selectable, Queryable, Clone)]
 pub struct User {
   pub user_id: Uuid,
   pub username: String,
   pub password: String,
-  pub admin: Option<bool>,
-  pub blocked: Option<bool>
+  pub admin: bool,
+  pub expires_at: Option<DateTime<Utc>>,
+  pub email: Option<String>,
+  pub email_verified_at: Option<DateTime<Utc>>,
+  pub failed_login_attempts: i32,
+  /// Set when the account got locked due to too many failed login attempts
+  pub locked_at: Option<DateTime<Utc>>,
+  pub created_at: DateTime<Utc>,
+  pub display_name: Option<String>,
+  pub locale: Option<String>,
+  /// Application specific attributes, always a JSON object
+  pub attributes: serde_json::Value,
+  /// Set while the account is deleted and can still be restored
+  pub deleted_at: Option<DateTime<Utc>>,
+  /// Shown to the user while they are blocked
+  pub blocked_reason: Option<String>,
+  /// End of a temporary suspension, `None` blocks until an admin unblocks the user
+  pub blocked_until: Option<DateTime<Utc>>,
+  pub blocked_by: Option<Uuid>,
+  /// Last change of the row, maintained by the `set_updated_at` trigger
+  pub updated_at: DateTime<Utc>,
+  pub last_login_at: Option<DateTime<Utc>>,
+  /// Last login or token refresh
+  pub last_active_at: Option<DateTime<Utc>>,
+  /// Set by an admin handing out a temporary password, the user can do nothing but change it
+  pub password_must_change: bool,
+  pub status: AccountStatus,
+  /// Set once the sessions of the user have been revoked after `expires_at` passed
+  pub expiry_processed_at: Option<DateTime<Utc>>,
+  /// The user can only log in and use their tokens within this tenant
+  pub tenant_id: Uuid,
+  /// End of a lockout due to failed logins
+  pub locked_until: Option<DateTime<Utc>>,
 }
 
 #[derive(serde::Serialize)]
@@ -16,17 +135,104 @@ pub struct User {
 pub struct UserInfo {
   pub user_id: Uuid,
   pub username: String,
-  pub admin: Option<bool>,
-  pub blocked: Option<bool>
+  pub admin: bool,
+  /// Status the user is in right now, a suspension or lockout that has run out counts as `ACTIVE`
+  pub status: AccountStatus,
+  pub blocked: bool,
+  /// The user loses access once this has passed
+  pub expires_at: Option<DateTime<Utc>>,
+  pub expired: bool,
+  pub email: Option<String>,
+  pub email_verified: bool,
+  pub locked: bool,
+  pub locked_until: Option<DateTime<Utc>>,
+  pub created_at: DateTime<Utc>,
+  pub display_name: Option<String>,
+  pub locale: Option<String>,
+  pub attributes: serde_json::Value,
+  pub deleted_at: Option<DateTime<Utc>>,
+  pub blocked_reason: Option<String>,
+  pub blocked_until: Option<DateTime<Utc>>,
+  pub blocked_by: Option<Uuid>,
+  pub updated_at: DateTime<Utc>,
+  pub last_login_at: Option<DateTime<Utc>>,
+  pub last_active_at: Option<DateTime<Utc>>,
+  pub password_must_change: bool,
+}
+
+#[derive(serde::Serialize)]
+pub struct UserResponse {
+  pub user: UserInfo
 }
 
 impl From<User> for UserInfo {
   fn from(user: User) -> Self {
-    UserInfo { user_id: user.user_id, username: user.username, admin: user.admin, blocked: user.blocked }
+    UserInfo {
+      status: user.effective_status(),
+      blocked: user.is_blocked(),
+      expired: user.is_expired(),
+      locked: user.is_locked(),
+      locked_until: user.is_locked().then_some(user.locked_until).flatten(),
+      user_id: user.user_id,
+      username: user.username,
+      admin: user.admin,
+      expires_at: user.expires_at,
+      email: user.email,
+      email_verified: user.email_verified_at.is_some(),
+      created_at: user.created_at,
+      display_name: user.display_name,
+      locale: user.locale,
+      attributes: user.attributes,
+      deleted_at: user.deleted_at,
+      blocked_reason: user.blocked_reason,
+      blocked_until: user.blocked_until,
+      blocked_by: user.blocked_by,
+      updated_at: user.updated_at,
+      last_login_at: user.last_login_at,
+      last_active_at: user.last_active_at,
+      password_must_change: user.password_must_change,
+    }
   }
 }
 
 impl User {
+  /// Whether an admin block is in place, a suspension ends by itself once `blocked_until` has passed
+  pub fn is_blocked(&self) -> bool {
+    self.status == AccountStatus::Suspended && self.blocked_until.is_none_or(|until| until > Utc::now())
+  }
+
+  /// Whether a lockout due to failed logins is in place, it ends by itself once `locked_until` has passed
+  pub fn is_locked(&self) -> bool {
+    self.status == AccountStatus::Locked && self.locked_until.is_none_or(|until| until > Utc::now())
+  }
+
+  /// The stored status, except for a suspension or lockout that has run out before it got lifted
+  pub fn effective_status(&self) -> AccountStatus {
+    match self.status {
+      AccountStatus::Suspended if !self.is_blocked() => AccountStatus::Active,
+      AccountStatus::Locked if !self.is_locked() => AccountStatus::Active,
+      status => status,
+    }
+  }
+
+  pub fn is_expired(&self) -> bool {
+    self.expires_at.is_some_and(|expiry| expiry <= Utc::now())
+  }
+
+  /// The one check deciding whether the user may log in and use their sessions
+  pub fn ensure_active(&self) -> Result<(), Fault> {
+    match self.effective_status() {
+      AccountStatus::Active => match self.expires_at {
+        Some(expiry) if expiry <= Utc::now() => Err(Fault::AccountExpired(expiry)),
+        _ => Ok(()),
+      },
+      AccountStatus::PendingApproval => Err(Fault::PendingApproval),
+      AccountStatus::Suspended => Err(Fault::UserBlocked { reason: self.blocked_reason.clone(), until: self.blocked_until }),
+      AccountStatus::Locked => Err(Fault::UserLocked),
+      AccountStatus::Deleted => Err(Fault::NotFound(String::from("User"))),
+    }
+  }
+
   pub fn verify_password(&self, password: String) -> Result<(), Fault> {
     let success = bcrypt::verify(password, &self.password);
     match success {
@@ -38,7 +244,7 @@ impl User {
 
   pub fn set_password(&mut self, password: String) -> Result<(), Fault> {
     let hashed = hash_password(password)
-      .or_else(|_| Err(Fault::Unexpected))?;
+      .map_err(|_| Fault::Unexpected)?;
 
     self.password = hashed;
     Ok(())
@@ -49,15 +255,352 @@ impl User {
 #[diesel(table_name = users)]
 pub struct NewUser<'a> {
   pub user_id: &'a Uuid,
+  pub tenant_id: Uuid,
   pub username: &'a str,
+  pub username_canonical: &'a str,
   pub password: &'a str,
+  pub admin: bool,
+  pub expires_at: Option<DateTime<Utc>>,
+  pub email: Option<&'a str>,
+  pub status: AccountStatus,
 }
 
 #[derive(Insertable)]
 #[diesel(table_name = users)]
 pub struct NewAdmUser {
   pub user_id: Uuid,
+  pub tenant_id: Uuid,
   pub username: String,
+  pub username_canonical: String,
   pub password: String,
+  pub admin: bool,
+}
+#[derive(serde::Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum UserSort {
+  #[default]
+  Username,
+  CreatedAt,
+  LastLoginAt,
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+  #[default]
+  Asc,
+  Desc,
+}
+
+/// Filters for listing users, results are paginated by `page` (starting at 1) and `perPage`
+#[derive(serde::Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct UserListQuery {
+  /// Case insensitive substring of the username
+  pub search: Option<String>,
+  /// Case insensitive start of the username
+  pub prefix: Option<String>,
   pub admin: Option<bool>,
-}
\ No newline at end of file
+  /// Suspended users whose suspension has not run out yet
+  pub blocked: Option<bool>,
+  /// Users in this status as stored, takes precedence over `deleted`
+  pub status: Option<AccountStatus>,
+  pub created_after: Option<DateTime<Utc>>,
+  pub created_before: Option<DateTime<Utc>>,
+  /// Users that have not logged in since then, including users that never logged in
+  pub last_login_before: Option<DateTime<Utc>>,
+  /// Lists deleted users instead of active ones
+  #[serde(default)]
+  pub deleted: bool,
+  #[serde(default)]
+  pub sort: UserSort,
+  #[serde(default)]
+  pub order: SortOrder,
+  pub page: Option<i64>,
+  pub per_page: Option<i64>,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all(serialize="camelCase"))]
+pub struct UserPage {
+  pub users: Vec<UserInfo>,
+  pub page: i64,
+  pub per_page: i64,
+  /// Amount of users matching the filters, across all pages
+  pub total: i64,
+  pub total_pages: i64,
+}
+
+/// Profile fields that can be opened up for users to change on their own
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProfileField {
+  Username,
+  Email,
+  DisplayName,
+  Locale,
+  Attributes,
+}
+
+impl ProfileField {
+  pub fn from_env_str(value: &str) -> Option<Self> {
+    match value {
+      "username" => Some(ProfileField::Username),
+      "email" => Some(ProfileField::Email),
+      "displayName" => Some(ProfileField::DisplayName),
+      "locale" => Some(ProfileField::Locale),
+      "attributes" => Some(ProfileField::Attributes),
+      _ => None,
+    }
+  }
+}
+
+/// Partial update of a user. Missing fields stay untouched, `null` clears nullable fields.
+/// `attributes` replaces the whole attribute object.
+#[derive(serde::Deserialize, Default)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct UserPatch {
+  pub username: Option<String>,
+  #[serde(default, deserialize_with = "double_option")]
+  pub email: Option<Option<String>>,
+  #[serde(default, deserialize_with = "double_option")]
+  pub display_name: Option<Option<String>>,
+  #[serde(default, deserialize_with = "double_option")]
+  pub locale: Option<Option<String>>,
+  pub attributes: Option<serde_json::Value>,
+  /// Only admins may change the fields below
+  pub admin: Option<bool>,
+  pub blocked: Option<bool>,
+  #[serde(default, deserialize_with = "double_option")]
+  pub blocked_reason: Option<Option<String>>,
+  #[serde(default, deserialize_with = "double_option")]
+  pub blocked_until: Option<Option<DateTime<Utc>>>,
+  #[serde(default, deserialize_with = "double_option")]
+  pub expires_at: Option<Option<DateTime<Utc>>>,
+}
+
+#[derive(AsChangeset, Default)]
+#[diesel(table_name = users)]
+pub struct UserChangeset {
+  pub username: Option<String>,
+  pub username_canonical: Option<String>,
+  pub email: Option<Option<String>>,
+  pub email_verified_at: Option<Option<DateTime<Utc>>>,
+  pub display_name: Option<Option<String>>,
+  pub locale: Option<Option<String>>,
+  pub attributes: Option<serde_json::Value>,
+  pub admin: Option<bool>,
+  pub status: Option<AccountStatus>,
+  pub failed_login_attempts: Option<i32>,
+  pub locked_at: Option<Option<DateTime<Utc>>>,
+  pub locked_until: Option<Option<DateTime<Utc>>>,
+  pub blocked_reason: Option<Option<String>>,
+  pub blocked_until: Option<Option<DateTime<Utc>>>,
+  pub blocked_by: Option<Option<Uuid>>,
+  pub expires_at: Option<Option<DateTime<Utc>>>,
+  pub expiry_processed_at: Option<Option<DateTime<Utc>>>,
+}
+
+/// Query of admin actions that need to be confirmed when admins apply them to themselves
+#[derive(serde::Deserialize, Default)]
+pub struct Confirmation {
+  #[serde(default)]
+  pub confirm: bool,
+}
+
+/// Blocks a user, either until `until` or until an admin unblocks them
+#[derive(serde::Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Suspension {
+  /// Shown to the user when they are turned away
+  pub reason: Option<String>,
+  pub until: Option<DateTime<Utc>>,
+}
+
+#[derive(serde::Serialize, Selectable, Queryable)]
+#[diesel(table_name = username_history)]
+#[serde(rename_all(serialize="camelCase"))]
+pub struct UsernameChange {
+  pub id: i32,
+  pub user_id: Option<Uuid>,
+  pub old_username: String,
+  pub new_username: String,
+  pub changed_by: Option<Uuid>,
+  pub changed_at: DateTime<Utc>,
+  /// Until then, no other user can take `old_username`
+  pub reserved_until: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = username_history)]
+pub struct NewUsernameChange<'a> {
+  pub user_id: Uuid,
+  /// Reservations only apply within the tenant of the user
+  pub tenant_id: Uuid,
+  pub old_username: &'a str,
+  pub new_username: &'a str,
+  pub changed_by: Uuid,
+  pub reserved_until: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+  use chrono::Duration;
+
+  use super::*;
+
+  fn user_with_status(status: AccountStatus) -> User {
+    let now = Utc::now();
+    User {
+      user_id: Uuid::new_v4(),
+      username: "alice".to_string(),
+      password: String::new(),
+      admin: false,
+      expires_at: None,
+      email: None,
+      email_verified_at: None,
+      failed_login_attempts: 0,
+      locked_at: None,
+      created_at: now,
+      display_name: None,
+      locale: None,
+      attributes: serde_json::json!({}),
+      deleted_at: None,
+      blocked_reason: None,
+      blocked_until: None,
+      blocked_by: None,
+      updated_at: now,
+      last_login_at: None,
+      last_active_at: None,
+      password_must_change: false,
+      status,
+      expiry_processed_at: None,
+      tenant_id: Uuid::new_v4(),
+      locked_until: None,
+    }
+  }
+
+  #[test]
+  fn allowed_transitions() {
+    use AccountStatus::*;
+
+    for (from, to) in [
+      (PendingApproval, Active),
+      (PendingApproval, Deleted),
+      (Active, Suspended),
+      (Active, Locked),
+      (Active, Deleted),
+      (Suspended, Active),
+      (Suspended, Suspended),
+      (Suspended, Deleted),
+      (Locked, Active),
+      (Locked, Suspended),
+      (Locked, Deleted),
+      (Deleted, Active),
+    ] {
+      assert!(from.can_become(to), "{from} should be able to become {to}");
+      assert!(from.ensure_can_become(to).is_ok());
+    }
+  }
+
+  #[test]
+  fn forbidden_transitions() {
+    use AccountStatus::*;
+
+    for (from, to) in [
+      (PendingApproval, PendingApproval),
+      (PendingApproval, Suspended),
+      (PendingApproval, Locked),
+      (Active, Active),
+      (Active, PendingApproval),
+      (Suspended, Locked),
+      (Suspended, PendingApproval),
+      (Locked, Locked),
+      (Deleted, Deleted),
+      (Deleted, Suspended),
+      (Deleted, Locked),
+    ] {
+      assert!(!from.can_become(to), "{from} should not be able to become {to}");
+      assert!(matches!(from.ensure_can_become(to), Err(Fault::InvalidStatusTransition { .. })));
+    }
+  }
+
+  #[test]
+  fn predecessors_match_transitions() {
+    assert_eq!(AccountStatus::predecessors(AccountStatus::Locked), vec![AccountStatus::Active]);
+    assert_eq!(
+      AccountStatus::predecessors(AccountStatus::Active),
+      vec![AccountStatus::PendingApproval, AccountStatus::Suspended, AccountStatus::Locked, AccountStatus::Deleted],
+    );
+  }
+
+  #[test]
+  fn active_user_is_active() {
+    let user = user_with_status(AccountStatus::Active);
+
+    assert_eq!(user.effective_status(), AccountStatus::Active);
+    assert!(user.ensure_active().is_ok());
+  }
+
+  #[test]
+  fn expired_account_is_not_active() {
+    let mut user = user_with_status(AccountStatus::Active);
+    user.expires_at = Some(Utc::now() - Duration::minutes(1));
+
+    assert!(user.is_expired());
+    assert!(matches!(user.ensure_active(), Err(Fault::AccountExpired(_))));
+  }
+
+  #[test]
+  fn running_suspension_blocks() {
+    let mut user = user_with_status(AccountStatus::Suspended);
+    user.blocked_reason = Some("spam".to_string());
+    user.blocked_until = Some(Utc::now() + Duration::hours(1));
+
+    assert!(user.is_blocked());
+    assert_eq!(user.effective_status(), AccountStatus::Suspended);
+    assert!(matches!(user.ensure_active(), Err(Fault::UserBlocked { reason: Some(_), until: Some(_) })));
+  }
+
+  #[test]
+  fn suspension_without_end_blocks() {
+    let user = user_with_status(AccountStatus::Suspended);
+
+    assert!(user.is_blocked());
+    assert!(matches!(user.ensure_active(), Err(Fault::UserBlocked { until: None, .. })));
+  }
+
+  #[test]
+  fn expired_suspension_counts_as_active() {
+    let mut user = user_with_status(AccountStatus::Suspended);
+    user.blocked_until = Some(Utc::now() - Duration::minutes(1));
+
+    assert!(!user.is_blocked());
+    assert_eq!(user.effective_status(), AccountStatus::Active);
+    assert!(user.ensure_active().is_ok());
+  }
+
+  #[test]
+  fn running_lockout_locks() {
+    let mut user = user_with_status(AccountStatus::Locked);
+    user.locked_until = Some(Utc::now() + Duration::minutes(15));
+
+    assert!(user.is_locked());
+    assert!(matches!(user.ensure_active(), Err(Fault::UserLocked)));
+  }
+
+  #[test]
+  fn expired_lockout_counts_as_active() {
+    let mut user = user_with_status(AccountStatus::Locked);
+    user.locked_until = Some(Utc::now() - Duration::minutes(1));
+
+    assert!(!user.is_locked());
+    assert_eq!(user.effective_status(), AccountStatus::Active);
+    assert!(user.ensure_active().is_ok());
+  }
+
+  #[test]
+  fn pending_and_deleted_users_are_not_active() {
+    assert!(matches!(user_with_status(AccountStatus::PendingApproval).ensure_active(), Err(Fault::PendingApproval)));
+    assert!(matches!(user_with_status(AccountStatus::Deleted).ensure_active(), Err(Fault::NotFound(_))));
+  }
+}
@@ -4,14 +4,173 @@
 ///
 /// (Automatically generated by Diesel.)
 pub mod sql_types {
+    /// The `account_status` SQL type
+    ///
+    /// (Automatically generated by Diesel.)
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "account_status"))]
+    pub struct AccountStatusType;
+
     /// The `otp_type` SQL type
     ///
     /// (Automatically generated by Diesel.)
-    #[derive(diesel::sql_types::SqlType)]
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "otp_type"))]
     pub struct OtpType;
 }
 
+diesel::table! {
+    /// Representation of the `audit_events` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    audit_events (id) {
+        /// The `id` column of the `audit_events` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int8,
+        /// The `actor` column of the `audit_events` table.
+        ///
+        /// Its SQL type is `Nullable<Uuid>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        actor -> Nullable<Uuid>,
+        /// The `subject` column of the `audit_events` table.
+        ///
+        /// Its SQL type is `Nullable<Uuid>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        subject -> Nullable<Uuid>,
+        /// The `action` column of the `audit_events` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        action -> Varchar,
+        /// The `details` column of the `audit_events` table.
+        ///
+        /// Its SQL type is `Jsonb`.
+        ///
+        /// (Automatically generated by Diesel.)
+        details -> Jsonb,
+        /// The `created_at` column of the `audit_events` table.
+        ///
+        /// Its SQL type is `Timestamptz`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    /// Representation of the `group_members` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    group_members (group_id, user_id) {
+        /// The `group_id` column of the `group_members` table.
+        ///
+        /// Its SQL type is `Uuid`.
+        ///
+        /// (Automatically generated by Diesel.)
+        group_id -> Uuid,
+        /// The `user_id` column of the `group_members` table.
+        ///
+        /// Its SQL type is `Uuid`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Uuid,
+        /// The `added_by` column of the `group_members` table.
+        ///
+        /// Its SQL type is `Nullable<Uuid>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        added_by -> Nullable<Uuid>,
+        /// The `added_at` column of the `group_members` table.
+        ///
+        /// Its SQL type is `Timestamptz`.
+        ///
+        /// (Automatically generated by Diesel.)
+        added_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    /// Representation of the `group_roles` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    group_roles (group_id, role_id) {
+        /// The `group_id` column of the `group_roles` table.
+        ///
+        /// Its SQL type is `Uuid`.
+        ///
+        /// (Automatically generated by Diesel.)
+        group_id -> Uuid,
+        /// The `role_id` column of the `group_roles` table.
+        ///
+        /// Its SQL type is `Uuid`.
+        ///
+        /// (Automatically generated by Diesel.)
+        role_id -> Uuid,
+        /// The `granted_by` column of the `group_roles` table.
+        ///
+        /// Its SQL type is `Nullable<Uuid>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        granted_by -> Nullable<Uuid>,
+        /// The `granted_at` column of the `group_roles` table.
+        ///
+        /// Its SQL type is `Timestamptz`.
+        ///
+        /// (Automatically generated by Diesel.)
+        granted_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    /// Representation of the `groups` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    groups (group_id) {
+        /// The `group_id` column of the `groups` table.
+        ///
+        /// Its SQL type is `Uuid`.
+        ///
+        /// (Automatically generated by Diesel.)
+        group_id -> Uuid,
+        /// The `name` column of the `groups` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        name -> Varchar,
+        /// The `description` column of the `groups` table.
+        ///
+        /// Its SQL type is `Nullable<Text>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        description -> Nullable<Text>,
+        /// The `parent_id` column of the `groups` table.
+        ///
+        /// Its SQL type is `Nullable<Uuid>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        parent_id -> Nullable<Uuid>,
+        /// The `created_at` column of the `groups` table.
+        ///
+        /// Its SQL type is `Timestamptz`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamptz,
+        /// The `tenant_id` column of the `groups` table.
+        ///
+        /// Its SQL type is `Uuid`.
+        ///
+        /// (Automatically generated by Diesel.)
+        tenant_id -> Uuid,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use super::sql_types::OtpType;
@@ -26,12 +185,12 @@ diesel::table! {
         ///
         /// (Automatically generated by Diesel.)
         id -> Int4,
-        /// The `code` column of the `otp` table.
+        /// The `legacy_code` column of the `otp` table.
         ///
-        /// Its SQL type is `Varchar`.
+        /// Its SQL type is `Nullable<Varchar>`.
         ///
         /// (Automatically generated by Diesel.)
-        code -> Varchar,
+        legacy_code -> Nullable<Varchar>,
         /// The `code_type` column of the `otp` table.
         ///
         /// Its SQL type is `OtpType`.
@@ -44,10 +203,351 @@ diesel::table! {
         ///
         /// (Automatically generated by Diesel.)
         user -> Nullable<Uuid>,
+        /// The `code_hash` column of the `otp` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        code_hash -> Nullable<Varchar>,
+        /// The `code_prefix` column of the `otp` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        code_prefix -> Varchar,
+        /// The `grant_admin` column of the `otp` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        grant_admin -> Bool,
+        /// The `restricted_to` column of the `otp` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        restricted_to -> Nullable<Varchar>,
+        /// The `groups` column of the `otp` table.
+        ///
+        /// Its SQL type is `Array<Nullable<Text>>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        groups -> Array<Nullable<Text>>,
+        /// The `account_expires_at` column of the `otp` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamptz>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        account_expires_at -> Nullable<Timestamptz>,
+        /// The `email` column of the `otp` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        email -> Nullable<Varchar>,
+        /// The `batch_id` column of the `otp` table.
+        ///
+        /// Its SQL type is `Nullable<Uuid>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        batch_id -> Nullable<Uuid>,
+        /// The `expires_at` column of the `otp` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamptz>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        expires_at -> Nullable<Timestamptz>,
+        /// The `created_by` column of the `otp` table.
+        ///
+        /// Its SQL type is `Nullable<Uuid>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_by -> Nullable<Uuid>,
+        /// The `created_at` column of the `otp` table.
+        ///
+        /// Its SQL type is `Timestamptz`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamptz,
+        /// The `redeemed_by` column of the `otp` table.
+        ///
+        /// Its SQL type is `Nullable<Uuid>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        redeemed_by -> Nullable<Uuid>,
+        /// The `redeemed_at` column of the `otp` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamptz>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        redeemed_at -> Nullable<Timestamptz>,
+        /// The `tenant_id` column of the `otp` table.
+        ///
+        /// Its SQL type is `Uuid`.
+        ///
+        /// (Automatically generated by Diesel.)
+        tenant_id -> Uuid,
+    }
+}
+
+diesel::table! {
+    /// Representation of the `otp_batches` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    otp_batches (batch_id) {
+        /// The `batch_id` column of the `otp_batches` table.
+        ///
+        /// Its SQL type is `Uuid`.
+        ///
+        /// (Automatically generated by Diesel.)
+        batch_id -> Uuid,
+        /// The `label` column of the `otp_batches` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        label -> Varchar,
+        /// The `created_by` column of the `otp_batches` table.
+        ///
+        /// Its SQL type is `Nullable<Uuid>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_by -> Nullable<Uuid>,
+        /// The `created_at` column of the `otp_batches` table.
+        ///
+        /// Its SQL type is `Timestamptz`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamptz,
+        /// The `expires_at` column of the `otp_batches` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamptz>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        expires_at -> Nullable<Timestamptz>,
+        /// The `revoked_at` column of the `otp_batches` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamptz>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        revoked_at -> Nullable<Timestamptz>,
+        /// The `tenant_id` column of the `otp_batches` table.
+        ///
+        /// Its SQL type is `Uuid`.
+        ///
+        /// (Automatically generated by Diesel.)
+        tenant_id -> Uuid,
+    }
+}
+
+diesel::table! {
+    /// Representation of the `permissions` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    permissions (name) {
+        /// The `name` column of the `permissions` table.
+        ///
+        /// Its SQL type is `Text`.
+        ///
+        /// (Automatically generated by Diesel.)
+        name -> Text,
+        /// The `description` column of the `permissions` table.
+        ///
+        /// Its SQL type is `Text`.
+        ///
+        /// (Automatically generated by Diesel.)
+        description -> Text,
+    }
+}
+
+diesel::table! {
+    /// Representation of the `role_permissions` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    role_permissions (role_id, permission) {
+        /// The `role_id` column of the `role_permissions` table.
+        ///
+        /// Its SQL type is `Uuid`.
+        ///
+        /// (Automatically generated by Diesel.)
+        role_id -> Uuid,
+        /// The `permission` column of the `role_permissions` table.
+        ///
+        /// Its SQL type is `Text`.
+        ///
+        /// (Automatically generated by Diesel.)
+        permission -> Text,
+    }
+}
+
+diesel::table! {
+    /// Representation of the `roles` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    roles (role_id) {
+        /// The `role_id` column of the `roles` table.
+        ///
+        /// Its SQL type is `Uuid`.
+        ///
+        /// (Automatically generated by Diesel.)
+        role_id -> Uuid,
+        /// The `name` column of the `roles` table.
+        ///
+        /// Its SQL type is `Text`.
+        ///
+        /// (Automatically generated by Diesel.)
+        name -> Text,
+        /// The `description` column of the `roles` table.
+        ///
+        /// Its SQL type is `Nullable<Text>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        description -> Nullable<Text>,
+        /// The `system` column of the `roles` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        system -> Bool,
+        /// The `created_at` column of the `roles` table.
+        ///
+        /// Its SQL type is `Timestamptz`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamptz,
+        /// The `tenant_id` column of the `roles` table.
+        ///
+        /// Its SQL type is `Nullable<Uuid>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        tenant_id -> Nullable<Uuid>,
+    }
+}
+
+diesel::table! {
+    /// Representation of the `tenants` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    tenants (tenant_id) {
+        /// The `tenant_id` column of the `tenants` table.
+        ///
+        /// Its SQL type is `Uuid`.
+        ///
+        /// (Automatically generated by Diesel.)
+        tenant_id -> Uuid,
+        /// The `slug` column of the `tenants` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        slug -> Varchar,
+        /// The `name` column of the `tenants` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        name -> Varchar,
+        /// The `created_at` column of the `tenants` table.
+        ///
+        /// Its SQL type is `Timestamptz`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    /// Representation of the `user_roles` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    user_roles (user_id, role_id) {
+        /// The `user_id` column of the `user_roles` table.
+        ///
+        /// Its SQL type is `Uuid`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Uuid,
+        /// The `role_id` column of the `user_roles` table.
+        ///
+        /// Its SQL type is `Uuid`.
+        ///
+        /// (Automatically generated by Diesel.)
+        role_id -> Uuid,
+        /// The `granted_by` column of the `user_roles` table.
+        ///
+        /// Its SQL type is `Nullable<Uuid>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        granted_by -> Nullable<Uuid>,
+        /// The `granted_at` column of the `user_roles` table.
+        ///
+        /// Its SQL type is `Timestamptz`.
+        ///
+        /// (Automatically generated by Diesel.)
+        granted_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    /// Representation of the `username_history` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    username_history (id) {
+        /// The `id` column of the `username_history` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// The `user_id` column of the `username_history` table.
+        ///
+        /// Its SQL type is `Nullable<Uuid>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        user_id -> Nullable<Uuid>,
+        /// The `old_username` column of the `username_history` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        old_username -> Varchar,
+        /// The `new_username` column of the `username_history` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        new_username -> Varchar,
+        /// The `changed_by` column of the `username_history` table.
+        ///
+        /// Its SQL type is `Nullable<Uuid>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        changed_by -> Nullable<Uuid>,
+        /// The `changed_at` column of the `username_history` table.
+        ///
+        /// Its SQL type is `Timestamptz`.
+        ///
+        /// (Automatically generated by Diesel.)
+        changed_at -> Timestamptz,
+        /// The `reserved_until` column of the `username_history` table.
+        ///
+        /// Its SQL type is `Timestamptz`.
+        ///
+        /// (Automatically generated by Diesel.)
+        reserved_until -> Timestamptz,
+        /// The `tenant_id` column of the `username_history` table.
+        ///
+        /// Its SQL type is `Uuid`.
+        ///
+        /// (Automatically generated by Diesel.)
+        tenant_id -> Uuid,
     }
 }
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::AccountStatusType;
+
     /// Representation of the `users` table.
     ///
     /// (Automatically generated by Diesel.)
@@ -72,22 +572,172 @@ diesel::table! {
         password -> Varchar,
         /// The `admin` column of the `users` table.
         ///
-        /// Its SQL type is `Nullable<Bool>`.
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        admin -> Bool,
+        /// The `expires_at` column of the `users` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamptz>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        expires_at -> Nullable<Timestamptz>,
+        /// The `email` column of the `users` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        email -> Nullable<Varchar>,
+        /// The `email_verified_at` column of the `users` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamptz>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        email_verified_at -> Nullable<Timestamptz>,
+        /// The `failed_login_attempts` column of the `users` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        failed_login_attempts -> Int4,
+        /// The `locked_at` column of the `users` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamptz>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        locked_at -> Nullable<Timestamptz>,
+        /// The `created_at` column of the `users` table.
+        ///
+        /// Its SQL type is `Timestamptz`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamptz,
+        /// The `display_name` column of the `users` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        display_name -> Nullable<Varchar>,
+        /// The `locale` column of the `users` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        locale -> Nullable<Varchar>,
+        /// The `attributes` column of the `users` table.
+        ///
+        /// Its SQL type is `Jsonb`.
+        ///
+        /// (Automatically generated by Diesel.)
+        attributes -> Jsonb,
+        /// The `deleted_at` column of the `users` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamptz>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        deleted_at -> Nullable<Timestamptz>,
+        /// The `blocked_reason` column of the `users` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        blocked_reason -> Nullable<Varchar>,
+        /// The `blocked_until` column of the `users` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamptz>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        blocked_until -> Nullable<Timestamptz>,
+        /// The `blocked_by` column of the `users` table.
+        ///
+        /// Its SQL type is `Nullable<Uuid>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        blocked_by -> Nullable<Uuid>,
+        /// The `username_canonical` column of the `users` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        username_canonical -> Varchar,
+        /// The `updated_at` column of the `users` table.
+        ///
+        /// Its SQL type is `Timestamptz`.
+        ///
+        /// (Automatically generated by Diesel.)
+        updated_at -> Timestamptz,
+        /// The `last_login_at` column of the `users` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamptz>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        last_login_at -> Nullable<Timestamptz>,
+        /// The `last_active_at` column of the `users` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamptz>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        last_active_at -> Nullable<Timestamptz>,
+        /// The `password_must_change` column of the `users` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        password_must_change -> Bool,
+        /// The `status` column of the `users` table.
+        ///
+        /// Its SQL type is `AccountStatusType`.
+        ///
+        /// (Automatically generated by Diesel.)
+        status -> AccountStatusType,
+        /// The `expiry_processed_at` column of the `users` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamptz>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        expiry_processed_at -> Nullable<Timestamptz>,
+        /// The `tenant_id` column of the `users` table.
+        ///
+        /// Its SQL type is `Uuid`.
         ///
         /// (Automatically generated by Diesel.)
-        admin -> Nullable<Bool>,
-        /// The `blocked` column of the `users` table.
+        tenant_id -> Uuid,
+        /// The `locked_until` column of the `users` table.
         ///
-        /// Its SQL type is `Nullable<Bool>`.
+        /// Its SQL type is `Nullable<Timestamptz>`.
         ///
         /// (Automatically generated by Diesel.)
-        blocked -> Nullable<Bool>,
+        locked_until -> Nullable<Timestamptz>,
     }
 }
 
-diesel::joinable!(otp -> users (user));
+diesel::joinable!(group_members -> groups (group_id));
+diesel::joinable!(group_roles -> groups (group_id));
+diesel::joinable!(group_roles -> roles (role_id));
+diesel::joinable!(groups -> tenants (tenant_id));
+diesel::joinable!(otp -> otp_batches (batch_id));
+diesel::joinable!(otp -> tenants (tenant_id));
+diesel::joinable!(otp_batches -> tenants (tenant_id));
+diesel::joinable!(otp_batches -> users (created_by));
+diesel::joinable!(role_permissions -> permissions (permission));
+diesel::joinable!(role_permissions -> roles (role_id));
+diesel::joinable!(roles -> tenants (tenant_id));
+diesel::joinable!(user_roles -> roles (role_id));
+diesel::joinable!(username_history -> tenants (tenant_id));
+diesel::joinable!(users -> tenants (tenant_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    audit_events,
+    group_members,
+    group_roles,
+    groups,
     otp,
+    otp_batches,
+    permissions,
+    role_permissions,
+    roles,
+    tenants,
+    user_roles,
+    username_history,
     users,
 );
@@ -0,0 +1,291 @@
+use jsonschema::Validator;
+use regex::Regex;
+
+use crate::{models::user::ProfileField, utils::{otp_code::{hash_code, OtpCodeFormat}, username::canonical_username, validation::MAX_USERNAME_LENGTH}};
+
+/// Runtime configuration, read once from the environment on startup
+pub struct AppConfig {
+  pub otp: OtpConfig,
+  pub auth: AuthConfig,
+  pub profile: ProfileConfig,
+  pub email: EmailConfig,
+  pub account: AccountConfig,
+  pub registration: RegistrationConfig,
+  pub tenant: TenantConfig,
+}
+
+/// Where the tenant of a request is taken from. A path prefix `/t/{slug}` wins over the header, the header over the subdomain.
+/// Requests naming no tenant are served by the default tenant.
+pub struct TenantConfig {
+  /// Header holding the slug of the tenant
+  pub header: String,
+  /// Lowercase domain the subdomains of which name tenants, `None` disables the lookup by subdomain
+  pub base_domain: Option<String>,
+}
+
+impl TenantConfig {
+  fn from_env() -> Self {
+    TenantConfig {
+      header: std::env::var("TENANT_HEADER").ok()
+        .filter(|header| !header.is_empty())
+        .unwrap_or_else(|| "X-Tenant".to_string()),
+      base_domain: std::env::var("TENANT_BASE_DOMAIN").ok()
+        .map(|domain| domain.trim().trim_start_matches('.').to_lowercase())
+        .filter(|domain| !domain.is_empty()),
+    }
+  }
+
+  /// The slug in front of `base_domain` in `host`, only a single label counts
+  pub fn slug_of_host(&self, host: &str) -> Option<String> {
+    let base_domain = self.base_domain.as_deref()?;
+    let host = host.split(':').next()?.to_lowercase();
+
+    host.strip_suffix(base_domain)?
+      .strip_suffix('.')
+      .filter(|slug| !slug.is_empty() && !slug.contains('.'))
+      .map(str::to_string)
+  }
+}
+
+/// Who may register without a registration code. A valid code is accepted in every mode and skips the approval
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RegistrationMode {
+  /// Only with a registration code
+  Invite,
+  /// Everybody
+  Open,
+  /// Everybody with an email address of one of `REGISTRATION_EMAIL_DOMAINS`, new users are activated by verifying it
+  Domain,
+  /// Everybody, new users wait for the approval of an admin before they can log in
+  Approval,
+}
+
+pub struct RegistrationConfig {
+  pub mode: RegistrationMode,
+  /// Lowercase domains email addresses have to belong to in `Domain` mode
+  pub email_domains: Vec<String>,
+}
+
+impl RegistrationConfig {
+  fn from_env() -> Self {
+    let mode = match std::env::var("REGISTRATION_MODE").unwrap_or_default().to_lowercase().as_str() {
+      "" | "invite" => RegistrationMode::Invite,
+      "open" => RegistrationMode::Open,
+      "domain" => RegistrationMode::Domain,
+      "approval" => RegistrationMode::Approval,
+      _ => panic!("env var 'REGISTRATION_MODE' should be one of 'invite', 'open', 'domain' or 'approval'"),
+    };
+
+    let email_domains: Vec<String> = std::env::var("REGISTRATION_EMAIL_DOMAINS")
+      .unwrap_or_default()
+      .split(',')
+      .map(|domain| domain.trim().trim_start_matches('@').to_lowercase())
+      .filter(|domain| !domain.is_empty())
+      .collect();
+
+    if mode == RegistrationMode::Domain && email_domains.is_empty() {
+      panic!("env var 'REGISTRATION_EMAIL_DOMAINS' should list at least one domain if 'REGISTRATION_MODE' is 'domain'");
+    }
+
+    RegistrationConfig { mode, email_domains }
+  }
+
+  /// Whether the domain of `email` is one of `email_domains`, subdomains do not count
+  pub fn is_allowed_email(&self, email: &str) -> bool {
+    email
+      .rsplit_once('@')
+      .is_some_and(|(_, domain)| self.email_domains.contains(&domain.to_lowercase()))
+  }
+}
+
+/// What a user cannot do before their email address is verified
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EmailVerificationRequirement {
+  /// Verification is optional
+  Off,
+  /// Sensitive actions like changing the password require a verified address
+  Sensitive,
+  /// Like `Sensitive`, additionally users cannot log in and have to provide an address when registering. Admins are exempt from the login check
+  Login,
+}
+
+pub struct EmailConfig {
+  pub requirement: EmailVerificationRequirement,
+  /// Minutes a verification code stays valid
+  pub verify_code_ttl: i64,
+  /// Link sent along with a verification code, `{code}` is replaced by the code
+  pub verify_link: Option<String>,
+}
+
+impl EmailConfig {
+  fn from_env() -> Self {
+    let requirement = match std::env::var("REQUIRE_VERIFIED_EMAIL").unwrap_or_default().to_lowercase().as_str() {
+      "" | "off" => EmailVerificationRequirement::Off,
+      "sensitive" => EmailVerificationRequirement::Sensitive,
+      "login" => EmailVerificationRequirement::Login,
+      _ => panic!("env var 'REQUIRE_VERIFIED_EMAIL' should be one of 'off', 'sensitive' or 'login'"),
+    };
+
+    EmailConfig {
+      requirement,
+      verify_code_ttl: read_number_env("EMAIL_VERIFY_CODE_TTL_MINUTES").unwrap_or(24 * 60),
+      verify_link: std::env::var("EMAIL_VERIFY_LINK").ok().filter(|link| !link.is_empty()),
+    }
+  }
+}
+
+pub struct ProfileConfig {
+  /// Fields users may change on their own profile, admins may change all fields
+  pub self_editable: Vec<ProfileField>,
+  /// JSON schema the attributes of a user have to satisfy, `None` accepts any JSON object
+  pub attributes_schema: Option<Validator>,
+  /// Days a released username stays reserved for its previous owner
+  pub username_reservation_days: i64,
+  pub username: UsernameRules,
+}
+
+/// What new usernames have to look like, existing names are not checked again
+pub struct UsernameRules {
+  /// Length in characters, the maximum cannot exceed the width of `users.username`
+  pub min_length: usize,
+  pub max_length: usize,
+  /// Pattern the whole name has to match
+  pub pattern: Regex,
+  /// Canonical forms of names only admins may hand out
+  pub blocked: Vec<String>,
+}
+
+impl UsernameRules {
+  fn from_env() -> Self {
+    let min_length = read_number_env("USERNAME_MIN_LENGTH").unwrap_or(3);
+    let max_length = read_number_env("USERNAME_MAX_LENGTH").unwrap_or(MAX_USERNAME_LENGTH);
+    if min_length < 1 || min_length > max_length || max_length > MAX_USERNAME_LENGTH {
+      panic!("env vars 'USERNAME_MIN_LENGTH' and 'USERNAME_MAX_LENGTH' should satisfy 1 <= min <= max <= {MAX_USERNAME_LENGTH}");
+    }
+
+    let pattern = std::env::var("USERNAME_PATTERN").ok()
+      .filter(|pattern| !pattern.is_empty())
+      .unwrap_or_else(|| r"^[\p{L}\p{N}._-]+$".to_string());
+    let pattern = Regex::new(&pattern)
+      .unwrap_or_else(|e| panic!("env var 'USERNAME_PATTERN' should be a valid regular expression: {e}"));
+
+    let blocked = std::env::var("BLOCKED_USERNAMES")
+      .unwrap_or_else(|_| "admin,administrator,root,system,support".to_string())
+      .split(',')
+      .map(str::trim)
+      .filter(|name| !name.is_empty())
+      .map(canonical_username)
+      .collect();
+
+    UsernameRules { min_length, max_length, pattern, blocked }
+  }
+}
+
+impl ProfileConfig {
+  fn from_env() -> Self {
+    let self_editable = std::env::var("SELF_EDITABLE_PROFILE_FIELDS")
+      .unwrap_or_else(|_| "username,email,displayName,locale,attributes".to_string())
+      .split(',')
+      .map(str::trim)
+      .filter(|field| !field.is_empty())
+      .map(|field| ProfileField::from_env_str(field)
+        .unwrap_or_else(|| panic!("env var 'SELF_EDITABLE_PROFILE_FIELDS' contains the unknown field '{field}'")))
+      .collect();
+
+    let attributes_schema = std::env::var("USER_ATTRIBUTES_SCHEMA").ok()
+      .filter(|path| !path.is_empty())
+      .map(|path| {
+        let raw = std::fs::read_to_string(&path)
+          .unwrap_or_else(|_| panic!("env var 'USER_ATTRIBUTES_SCHEMA' should point to a readable file, got '{path}'"));
+        let schema: serde_json::Value = serde_json::from_str(&raw)
+          .unwrap_or_else(|e| panic!("'{path}' should contain a JSON schema: {e}"));
+        jsonschema::validator_for(&schema)
+          .unwrap_or_else(|e| panic!("'{path}' should contain a valid JSON schema: {e}"))
+      });
+
+    ProfileConfig {
+      self_editable,
+      attributes_schema,
+      username_reservation_days: read_number_env("USERNAME_RESERVATION_DAYS").unwrap_or(30),
+      username: UsernameRules::from_env(),
+    }
+  }
+}
+
+pub struct AccountConfig {
+  /// Days a deleted account can be restored before it gets purged
+  pub deleted_retention_days: i64,
+  /// Minutes between two runs of the job purging deleted accounts, lifting expired suspensions and revoking the sessions of expired accounts
+  pub purge_interval_minutes: u64,
+}
+
+pub struct AuthConfig {
+  /// Failed logins in a row after which an account gets locked, 0 disables the lockout
+  pub max_failed_logins: i32,
+  /// Minutes a lockout lasts, also the lifetime of the unlock code mailed on a lockout and the window unlock attempts are counted in
+  pub lockout_minutes: i64,
+  /// Minutes a password reset code issued by an admin stays valid
+  pub password_reset_code_ttl: i64,
+  /// Link handed out along with a password reset code, `{code}` is replaced by the code
+  pub password_reset_link: Option<String>,
+}
+
+pub struct OtpConfig {
+  pub format: OtpCodeFormat,
+  /// Whether admins may pick the code of an OTP themselves instead of having it generated
+  pub allow_client_codes: bool,
+  /// Key for the HMAC codes are stored with
+  pub hash_secret: Vec<u8>,
+}
+
+impl OtpConfig {
+  pub fn hash_code(&self, code: &str) -> String {
+    hash_code(&self.hash_secret, code)
+  }
+}
+
+impl AppConfig {
+  pub fn new() -> Self {
+    AppConfig {
+      otp: OtpConfig {
+        format: OtpCodeFormat::from_env(),
+        allow_client_codes: read_bool_env("OTP_ALLOW_CLIENT_CODES"),
+        hash_secret: std::env::var("OTP_HASH_SECRET")
+          .expect("env var 'OTP_HASH_SECRET' should contain a secret used to hash one-time-passwords")
+          .into_bytes(),
+      },
+      auth: AuthConfig {
+        max_failed_logins: read_number_env("MAX_FAILED_LOGINS").unwrap_or(5),
+        lockout_minutes: read_number_env("LOCKOUT_MINUTES").unwrap_or(15),
+        password_reset_code_ttl: read_number_env("PASSWORD_RESET_CODE_TTL_MINUTES").unwrap_or(24 * 60),
+        password_reset_link: std::env::var("PASSWORD_RESET_LINK").ok().filter(|link| !link.is_empty()),
+      },
+      profile: ProfileConfig::from_env(),
+      email: EmailConfig::from_env(),
+      account: AccountConfig {
+        deleted_retention_days: read_number_env("DELETED_ACCOUNT_RETENTION_DAYS").unwrap_or(30),
+        purge_interval_minutes: read_number_env("ACCOUNT_PURGE_INTERVAL_MINUTES").unwrap_or(60),
+      },
+      registration: RegistrationConfig::from_env(),
+      tenant: TenantConfig::from_env(),
+    }
+  }
+}
+
+impl Default for AppConfig {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+fn read_bool_env(name: &str) -> bool {
+  std::env::var(name)
+    .map(|v| matches!(v.to_lowercase().as_str(), "1" | "true" | "yes"))
+    .unwrap_or(false)
+}
+
+fn read_number_env<T: std::str::FromStr>(name: &str) -> Option<T> {
+  std::env::var(name)
+    .ok()
+    .map(|v| v.parse::<T>().unwrap_or_else(|_| panic!("env var '{name}' should be a number")))
+}
@@ -0,0 +1,83 @@
+use lettre::{
+  message::{header::ContentType, Mailbox},
+  transport::smtp::authentication::Credentials,
+  AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+
+use crate::utils::error::Fault;
+
+type SmtpTransport = AsyncSmtpTransport<Tokio1Executor>;
+
+/// Sends mails via SMTP. Without `SMTP_HOST`, sending fails,
+/// unless `MAIL_PRINT_TO_STDOUT` is set, which prints mails (including the codes in them) for development.
+pub struct WrappedMailer {
+  transport: Option<SmtpTransport>,
+  from: Mailbox,
+  print_to_stdout: bool,
+}
+
+impl WrappedMailer {
+  pub fn new() -> Self {
+    let from = std::env::var("MAIL_FROM")
+      .unwrap_or_else(|_| "rust-auth <noreply@localhost>".to_string())
+      .parse::<Mailbox>()
+      .expect("env var 'MAIL_FROM' should contain a valid mailbox like 'Name <mail@example.com>'");
+
+    let transport = std::env::var("SMTP_HOST").ok()
+      .filter(|host| !host.is_empty())
+      .map(|host| {
+        let tls = std::env::var("SMTP_TLS").unwrap_or_else(|_| "starttls".to_string());
+        let mut builder = match tls.to_lowercase().as_str() {
+          "tls" => SmtpTransport::relay(&host).expect("SMTP_HOST should be a valid hostname"),
+          "starttls" => SmtpTransport::starttls_relay(&host).expect("SMTP_HOST should be a valid hostname"),
+          "none" => SmtpTransport::builder_dangerous(&host),
+          _ => panic!("env var 'SMTP_TLS' should be one of 'tls', 'starttls' or 'none'"),
+        };
+
+        if let Some(port) = std::env::var("SMTP_PORT").ok().filter(|port| !port.is_empty()) {
+          builder = builder.port(port.parse().expect("env var 'SMTP_PORT' should be a port number"));
+        }
+
+        if let (Ok(username), Ok(password)) = (std::env::var("SMTP_USERNAME"), std::env::var("SMTP_PASSWORD")) {
+          builder = builder.credentials(Credentials::new(username, password));
+        }
+
+        builder.build()
+      });
+
+    let print_to_stdout = std::env::var("MAIL_PRINT_TO_STDOUT")
+      .map(|v| matches!(v.to_lowercase().as_str(), "1" | "true" | "yes"))
+      .unwrap_or(false);
+
+    WrappedMailer { transport, from, print_to_stdout }
+  }
+
+  pub async fn send(&self, to: &str, subject: &str, body: String) -> Result<(), Fault> {
+    let Some(transport) = &self.transport else {
+      if !self.print_to_stdout {
+        return Err(Fault::MailDelivery);
+      }
+      println!("Mail to {to} (SMTP_HOST is not set, the mail has not been sent)\nSubject: {subject}\n\n{body}");
+      return Ok(());
+    };
+
+    let recipient = to.parse::<Mailbox>().map_err(|_| Fault::MailDelivery)?;
+    let message = Message::builder()
+      .from(self.from.clone())
+      .to(recipient)
+      .subject(subject)
+      .header(ContentType::TEXT_PLAIN)
+      .body(body)
+      .map_err(|_| Fault::MailDelivery)?;
+
+    transport.send(message).await
+      .map_err(|_| Fault::MailDelivery)
+      .map(|_| ())
+  }
+}
+
+impl Default for WrappedMailer {
+  fn default() -> Self {
+    Self::new()
+  }
+}
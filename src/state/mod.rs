@@ -2,16 +2,22 @@ use std::sync::Arc;
 
 use redis::Client;
 
+use self::config::AppConfig;
 use self::postgres_wrapper::WrappedPostgres;
 use self::redis_wrapper::WrappedRedis;
+use self::mailer::WrappedMailer;
 
 type RedisClient = Client;
 
 pub mod redis_wrapper;
 pub mod postgres_wrapper;
+pub mod config;
+pub mod mailer;
 
 #[derive(Clone)]
 pub struct AppState {
   pub pool: Arc<WrappedPostgres>,
   pub redis: Arc<WrappedRedis>,
+  pub config: Arc<AppConfig>,
+  pub mailer: Arc<WrappedMailer>,
 }
\ No newline at end of file
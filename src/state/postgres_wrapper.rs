@@ -22,16 +22,15 @@ impl WrappedPostgres {
     WrappedPostgres { postgres: Arc::new(pool) }
   }
 
-  pub async fn get_connection(&self) -> Result<Box<WrappedPooledConnection>, Fault> {
+  pub async fn get_connection(&self) -> Result<Box<WrappedPooledConnection<'_>>, Fault> {
     self.postgres.get().await
-      .or_else(|_| Err(Fault::DatabaseConnection))
-      .and_then(|c| Ok(Box::new(WrappedPooledConnection { connection: c })))
+      .map_err(|_| Fault::DatabaseConnection)
+      .map(|c| Box::new(WrappedPooledConnection { connection: c }))
   }
 
   // TODO: Need to do more research on lifetimes as this does not seem to work as I want it to
   #[allow(dead_code)]
   pub async fn with_connection<
-    'a,
     F,
     Fut,
     U
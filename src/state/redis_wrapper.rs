@@ -1,4 +1,6 @@
+use chrono::{DateTime, Duration, Utc};
 use redis::{aio::MultiplexedConnection, AsyncCommands, Cmd};
+use serde::Serialize;
 use uuid::Uuid;
 
 use crate::{api::auth::session::TokenPair, utils::error::Fault};
@@ -10,6 +12,12 @@ pub struct WrappedRedis {
   redis: RedisClient
 }
 
+impl Default for WrappedRedis {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
 impl WrappedRedis {
   pub fn new() -> Self {
     let env_redis = std::env::var("REDIS_HOST").expect("env var 'REDIS_HOST' should contain name of redis host system");
@@ -21,7 +29,7 @@ impl WrappedRedis {
   }
 
   pub async fn get_connection(&self) -> Result<MultiplexedConnection, Fault> {
-    self.redis.get_multiplexed_tokio_connection().await.or_else(|_| Err(Fault::DatabaseConnection))
+    self.redis.get_multiplexed_tokio_connection().await.map_err(|_| Fault::DatabaseConnection)
   }
 
   pub async fn save_token_pair_for_user(&self, pair: &TokenPair) -> Result<(), Fault> {
@@ -37,10 +45,11 @@ impl WrappedRedis {
         format!("REFRESH:{}", pair.get_refresh_token_string()),
         format!("{}:{}", pair.get_id_string(), pair.get_access_token_string()),
         pair.refresh_token.duration,
-      ))
-      .query_async::<()>(&mut con).await.or_else(|_| {
-        Err(Fault::DatabaseConnection)
-      })?;
+      )).ignore()
+      // index of the sessions of a user, it lives as long as the newest refresh token
+      .sadd(format!("SESSIONS:{}", pair.get_id_string()), pair.get_refresh_token_string()).ignore()
+      .expire(format!("SESSIONS:{}", pair.get_id_string()), pair.refresh_token.duration).ignore()
+      .query_async::<()>(&mut con).await.map_err(|_| Fault::DatabaseConnection)?;
 
     Ok(())
   }
@@ -48,11 +57,11 @@ impl WrappedRedis {
   async fn get_user_for_token(&self, token: &String) -> Result<Uuid, Fault> {
     let mut con = self.get_connection().await?;
 
-    let result: String = con.get(token).await.or_else(|_| Err(Fault::NotLoggedIn))?;
+    let result: String = con.get(token).await.map_err(|_| Fault::NotLoggedIn)?;
 
-    let splits: Vec<&str> = result.split(":").into_iter().collect();
+    let splits: Vec<&str> = result.split(":").collect();
 
-    let parsed = Uuid::parse_str(&splits[0]).or_else(|_| Err(Fault::UuidConversion))?;
+    let parsed = Uuid::parse_str(splits[0]).map_err(|_| Fault::UuidConversion)?;
 
     Ok(parsed)
   }
@@ -68,7 +77,7 @@ impl WrappedRedis {
   pub async fn clear_token(&self, token: &String) -> Result<(), Fault> {
     let mut con = self.get_connection().await?;
     
-    con.del::<_, ()>(format!("ACCESS:{}", token)).await.or_else(|_| Err(Fault::Unexpected))?;
+    con.del::<_, ()>(format!("ACCESS:{}", token)).await.map_err(|_| Fault::Unexpected)?;
 
     Ok(())
   }
@@ -76,9 +85,9 @@ impl WrappedRedis {
   pub async fn invalidate_refresh_token_and_get_result(&self, token: Uuid) -> Result<(String, String), Fault> {
     let mut con = self.get_connection().await?;
 
-    let result: String = con.get_del(format!("REFRESH:{}", token.to_string())).await.or_else(|_| Err(Fault::NotLoggedIn))?;
+    let result: String = con.get_del(format!("REFRESH:{}", token)).await.map_err(|_| Fault::NotLoggedIn)?;
 
-    let splits: Vec<&str> = result.split(":").into_iter().collect();
+    let splits: Vec<&str> = result.split(":").collect();
 
     Ok((splits[0].to_string(), splits[1].to_string()))
   }
@@ -86,14 +95,95 @@ impl WrappedRedis {
   pub async fn invalidate_session_by_access_token(&self, token: Uuid) -> Result<(), Fault> {
     let mut con = self.get_connection().await?;
 
-    let result: String = con.get_del(format!("ACCESS:{}", token.to_string())).await.or_else(|_| Err(Fault::NotLoggedIn))?;
+    let result: String = con.get_del(format!("ACCESS:{}", token)).await.map_err(|_| Fault::NotLoggedIn)?;
 
-    let refresh_token: String = result.split(":").into_iter().last().unwrap().to_string();
+    let refresh_token: String = result.split(":").last().unwrap().to_string();
+    let user_id: String = result.split(":").next().unwrap().to_string();
 
-    con.del::<_, ()>(format!("REFRESH:{}", refresh_token)).await.or_else(|_| Err(Fault::Unexpected))?;
+    con.del::<_, ()>(format!("REFRESH:{}", refresh_token)).await.map_err(|_| Fault::Unexpected)?;
+    con.srem::<_, _, ()>(format!("SESSIONS:{}", user_id), refresh_token).await.map_err(|_| Fault::Unexpected)?;
 
     Ok(())
   }
+
+  /// Lists the sessions of a user that are still alive, entries of sessions that ended are cleaned up on the way
+  pub async fn get_sessions_of_user(&self, user: Uuid) -> Result<Vec<SessionInfo>, Fault> {
+    let mut con = self.get_connection().await?;
+    let index = format!("SESSIONS:{}", user);
+
+    let refresh_tokens: Vec<String> = con.smembers(&index).await.map_err(|_| Fault::Unexpected)?;
+    let mut sessions = Vec::new();
+
+    for refresh_token in refresh_tokens {
+      let refresh_key = format!("REFRESH:{}", refresh_token);
+      let value: Option<String> = con.get(&refresh_key).await.map_err(|_| Fault::Unexpected)?;
+
+      let Some(access_token) = value.as_ref().and_then(|v| v.split(":").nth(1)) else {
+        con.srem::<_, _, ()>(&index, &refresh_token).await.map_err(|_| Fault::Unexpected)?;
+        continue;
+      };
+
+      let refresh_ttl: i64 = con.ttl(&refresh_key).await.map_err(|_| Fault::Unexpected)?;
+      let access_ttl: i64 = con.ttl(format!("ACCESS:{}", access_token)).await.map_err(|_| Fault::Unexpected)?;
+      let now = Utc::now();
+
+      sessions.push(SessionInfo {
+        current: false,
+        access_token_expires_at: (access_ttl > 0).then(|| now + Duration::seconds(access_ttl)),
+        refresh_token_expires_at: (refresh_ttl > 0).then(|| now + Duration::seconds(refresh_ttl)),
+        access_token: access_token.to_string(),
+      });
+    }
+
+    Ok(sessions)
+  }
+
+  /// Ends all sessions of a user, returns the amount of ended sessions
+  pub async fn invalidate_sessions_of_user(&self, user: Uuid) -> Result<usize, Fault> {
+    let mut con = self.get_connection().await?;
+    let index = format!("SESSIONS:{}", user);
+
+    let refresh_tokens: Vec<String> = con.smembers(&index).await.map_err(|_| Fault::Unexpected)?;
+    let mut ended = 0;
+
+    for refresh_token in refresh_tokens {
+      let value: Option<String> = con.get_del(format!("REFRESH:{}", refresh_token)).await.map_err(|_| Fault::Unexpected)?;
+
+      if let Some(access_token) = value.as_ref().and_then(|v| v.split(":").nth(1)) {
+        con.del::<_, ()>(format!("ACCESS:{}", access_token)).await.map_err(|_| Fault::Unexpected)?;
+        ended += 1;
+      }
+    }
+
+    con.del::<_, ()>(&index).await.map_err(|_| Fault::Unexpected)?;
+
+    Ok(ended)
+  }
+
+  /// Counts an attempt under `key`, the count starts over `window` seconds after the first attempt.
+  /// Returns the attempts within the current window, including this one.
+  pub async fn count_attempt(&self, key: &str, window: i64) -> Result<i64, Fault> {
+    let mut con = self.get_connection().await?;
+
+    let attempts: i64 = con.incr(key, 1).await.map_err(|_| Fault::DatabaseConnection)?;
+    if attempts == 1 {
+      con.expire::<_, ()>(key, window).await.map_err(|_| Fault::DatabaseConnection)?;
+    }
+
+    Ok(attempts)
+  }
+}
+
+/// A session of a user, without its tokens
+#[derive(Serialize)]
+#[serde(rename_all(serialize="camelCase"))]
+pub struct SessionInfo {
+  #[serde(skip)]
+  pub access_token: String,
+  /// Whether this is the session the request has been made with
+  pub current: bool,
+  pub access_token_expires_at: Option<DateTime<Utc>>,
+  pub refresh_token_expires_at: Option<DateTime<Utc>>,
 }
 
 fn build_set_ex_cmd(key: String, value: String, duration: i64) -> Cmd {
@@ -1,5 +1,7 @@
 use axum::{response::IntoResponse, http::StatusCode, Json};
+use chrono::{DateTime, Utc};
 use serde_json::json;
+use crate::models::user::AccountStatus;
 
 #[derive(Clone)]
 pub enum Fault {
@@ -13,14 +15,57 @@ pub enum Fault {
   Unallowed,
   MalformedAuthorization,
   NotImplementedYet,
-  UserBlocked,
+  /// Carries the reason and the end of a suspension, both are handed to the user
+  UserBlocked { reason: Option<String>, until: Option<DateTime<Utc>> },
+  UserLocked,
+  AccountExpired(DateTime<Utc>),
   RegistrationCodeInvalid,
   PasswordCodeInvalid,
+  EmailCodeInvalid,
+  UnlockCodeInvalid,
+  TooManyAttempts,
   MissingUserIdOtp,
+  MissingEmailOtp,
+  CustomOtpCodeDisabled,
+  MalformedOtpCode,
+  OtpPayloadUnsupported,
+  Validation(String),
+  FieldNotEditable(String),
+  AttributesInvalid(String),
+  EmailNotVerified,
+  EmailRequired,
+  MailDelivery,
+  UsernameReserved,
+  LastAdmin,
+  ConfirmationRequired,
+  PasswordChangeRequired,
+  PendingApproval,
+  NotPendingApproval,
+  RegistrationCodeRequired,
+  EmailDomainNotAllowed,
+  InvalidStatusTransition { from: AccountStatus, to: AccountStatus },
+  MissingPermission(String),
+  /// The target user holds a permission the acting user lacks
+  TargetOutranks(String),
+  SystemRole,
+  GroupHasSubgroups,
+  TenantNotEmpty,
+  DefaultTenant,
+}
+
+impl From<diesel::result::Error> for Fault {
+  fn from(_: diesel::result::Error) -> Self {
+    Fault::Diesel
+  }
 }
 
 impl IntoResponse for Fault {
   fn into_response(self) -> axum::response::Response {
+      let suspension = match &self {
+        Fault::UserBlocked { reason, until } => Some(json!({ "reason": reason, "blockedUntil": until })),
+        _ => None,
+      };
+
       let (status, error_message) = match self {
         Fault::DatabaseConnection => (StatusCode::INTERNAL_SERVER_ERROR, "Unknown error".to_string()),
         Fault::NotLoggedIn => (StatusCode::UNAUTHORIZED, "Please log into the application in order to execute this function".to_string()),
@@ -32,16 +77,51 @@ impl IntoResponse for Fault {
         Fault::Unallowed => (StatusCode::FORBIDDEN, "Insufficient permissions".to_string()),
         Fault::MalformedAuthorization => (StatusCode::BAD_REQUEST, "Authorization header must be in form of `'TOKEN {{auth_token}}'`".to_string()),
         Fault::NotImplementedYet => (StatusCode::NOT_IMPLEMENTED, "Functionality is a To-Do".to_string()),
-        Fault::UserBlocked => (StatusCode::UNAUTHORIZED, "Your account has been blocked by an admin. Please reach out to an admin to regain access to this app!".to_string()),
+        Fault::UserBlocked { until: Some(until), .. } => (StatusCode::UNAUTHORIZED, format!("Your account has been suspended by an admin until {}", until.to_rfc3339())),
+        Fault::UserBlocked { until: None, .. } => (StatusCode::UNAUTHORIZED, "Your account has been blocked by an admin. Please reach out to an admin to regain access to this app!".to_string()),
+        Fault::AccountExpired(expiry) => (StatusCode::UNAUTHORIZED, format!("Your account expired at {}. Please reach out to an admin to regain access to this app!", expiry.to_rfc3339())),
+        Fault::UserLocked => (StatusCode::UNAUTHORIZED, "Your account has been locked for a while after too many failed login attempts. Try again later, use the unlock code sent to your email address or reach out to an admin!".to_string()),
         Fault::RegistrationCodeInvalid => (StatusCode::BAD_REQUEST, "The entered registration code does not exist".to_string()),
         Fault::PasswordCodeInvalid => (StatusCode::BAD_REQUEST, "The entered password code does not exist".to_string()),
-        Fault::MissingUserIdOtp => (StatusCode::BAD_REQUEST, "To create a password reset code, please specify a user that is bound to the code".to_string())
+        Fault::EmailCodeInvalid => (StatusCode::BAD_REQUEST, "The entered email verification code does not exist".to_string()),
+        Fault::UnlockCodeInvalid => (StatusCode::BAD_REQUEST, "The entered unlock code does not exist".to_string()),
+        Fault::TooManyAttempts => (StatusCode::TOO_MANY_REQUESTS, "Too many attempts, please try again later".to_string()),
+        Fault::MissingUserIdOtp => (StatusCode::BAD_REQUEST, "To create this kind of code, please specify a user that is bound to the code".to_string()),
+        Fault::MissingEmailOtp => (StatusCode::BAD_REQUEST, "To create an email verification code, please specify a valid email address".to_string()),
+        Fault::CustomOtpCodeDisabled => (StatusCode::BAD_REQUEST, "Codes are generated by the server, supplying a custom code is disabled".to_string()),
+        Fault::MalformedOtpCode => (StatusCode::BAD_REQUEST, "A custom code must consist of 1 to 128 letters or digits".to_string()),
+        Fault::OtpPayloadUnsupported => (StatusCode::BAD_REQUEST, "A payload can only be attached to registration codes".to_string()),
+        Fault::Validation(reason) => (StatusCode::BAD_REQUEST, reason),
+        Fault::FieldNotEditable(field) => (StatusCode::FORBIDDEN, format!("The field '{field}' can only be changed by an admin")),
+        Fault::AttributesInvalid(reason) => (StatusCode::BAD_REQUEST, format!("The attributes do not match the attribute schema: {reason}")),
+        Fault::EmailNotVerified => (StatusCode::FORBIDDEN, "Please verify your email address first".to_string()),
+        Fault::EmailRequired => (StatusCode::BAD_REQUEST, "Please provide a valid email address".to_string()),
+        Fault::MailDelivery => (StatusCode::BAD_GATEWAY, "The email could not be sent".to_string()),
+        Fault::UsernameReserved => (StatusCode::CONFLICT, "The username has been in use recently and is still reserved".to_string()),
+        Fault::LastAdmin => (StatusCode::CONFLICT, "At least one active admin has to remain, promote another user first".to_string()),
+        Fault::RegistrationCodeRequired => (StatusCode::FORBIDDEN, "Registration requires a registration code".to_string()),
+        Fault::EmailDomainNotAllowed => (StatusCode::FORBIDDEN, "Registration requires an email address of an allowed domain".to_string()),
+        Fault::PendingApproval => (StatusCode::FORBIDDEN, "Your account is waiting for the approval of an admin".to_string()),
+        Fault::NotPendingApproval => (StatusCode::CONFLICT, "The user is not waiting for approval".to_string()),
+        Fault::InvalidStatusTransition { from, to } => (StatusCode::CONFLICT, format!("A {from} account cannot become {to}")),
+        Fault::MissingPermission(permission) => (StatusCode::FORBIDDEN, format!("This requires the permission '{permission}'")),
+        Fault::TargetOutranks(permission) => (StatusCode::FORBIDDEN, format!("The user holds the permission '{permission}', which you lack")),
+        Fault::SystemRole => (StatusCode::CONFLICT, "System roles cannot be changed".to_string()),
+        Fault::GroupHasSubgroups => (StatusCode::CONFLICT, "The group still contains groups, move or delete them first".to_string()),
+        Fault::TenantNotEmpty => (StatusCode::CONFLICT, "The tenant still has users, delete them and wait for their purge first".to_string()),
+        Fault::DefaultTenant => (StatusCode::CONFLICT, "The default tenant cannot be deleted".to_string()),
+        Fault::PasswordChangeRequired => (StatusCode::FORBIDDEN, "Your password has been reset by an admin, please change it first".to_string()),
+        Fault::ConfirmationRequired => (StatusCode::PRECONDITION_REQUIRED, "This action affects your own account, repeat it with `confirm=true` to proceed".to_string()),
       };
 
-      let body = Json(json!({
+      let mut body = json!({
         "error": error_message
-      }));
+      });
+
+      if let Some(suspension) = suspension {
+        body["suspension"] = suspension;
+      }
 
-      (status, body).into_response()
+      (status, Json(body)).into_response()
   }
 }
\ No newline at end of file
@@ -1,3 +1,15 @@
 pub mod parser;
 
-pub mod error;
\ No newline at end of file
+pub mod error;
+
+pub mod otp_code;
+
+pub mod otp_words;
+
+pub mod validation;
+
+pub mod patch;
+
+pub mod sql;
+
+pub mod username;
@@ -0,0 +1,169 @@
+use hmac::{Hmac, Mac};
+use rand::{seq::IndexedRandom, Rng};
+use sha2::Sha256;
+
+use super::{error::Fault, otp_words::WORDS};
+
+/// Maximum length of a normalized code
+pub const MAX_CODE_LENGTH: usize = 128;
+
+/// Amount of leading characters of a normalized code that are kept in plaintext to tell codes apart.
+/// They are not part of the secret, only the remainder of the code is hashed.
+pub const CODE_PREFIX_LENGTH: usize = 2;
+
+const DIGITS: &[u8] = b"0123456789";
+const CROCKFORD: &[u8] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OtpAlphabet {
+  Digits,
+  Crockford,
+  Words,
+}
+
+impl OtpAlphabet {
+  fn from_env_str(value: &str) -> Option<Self> {
+    match value.to_lowercase().as_str() {
+      "digits" => Some(OtpAlphabet::Digits),
+      "crockford" | "base32" => Some(OtpAlphabet::Crockford),
+      "words" => Some(OtpAlphabet::Words),
+      _ => None,
+    }
+  }
+}
+
+/// Describes how server-generated OTP codes look like.
+///
+/// `length` is the amount of characters (or words for `OtpAlphabet::Words`),
+/// `group_size` splits character based codes into dash separated groups for readability.
+/// A `group_size` of 0 disables grouping.
+#[derive(Clone, Debug)]
+pub struct OtpCodeFormat {
+  pub alphabet: OtpAlphabet,
+  pub length: usize,
+  pub group_size: usize,
+}
+
+impl OtpCodeFormat {
+  pub fn from_env() -> Self {
+    let alphabet = std::env::var("OTP_CODE_ALPHABET")
+      .map(|a| OtpAlphabet::from_env_str(&a).expect("env var 'OTP_CODE_ALPHABET' should be one of 'digits', 'crockford' or 'words'"))
+      .unwrap_or(OtpAlphabet::Digits);
+
+    let default_length = match alphabet {
+      OtpAlphabet::Words => 4,
+      _ => 8,
+    };
+    let length = read_usize_env("OTP_CODE_LENGTH").unwrap_or(default_length);
+    let group_size = read_usize_env("OTP_CODE_GROUP_SIZE").unwrap_or(4);
+
+    let format = OtpCodeFormat { alphabet, length, group_size };
+    assert!(length > 0, "env var 'OTP_CODE_LENGTH' should be greater than 0");
+    assert!(format.max_stored_length() <= MAX_CODE_LENGTH, "env var 'OTP_CODE_LENGTH' results in codes longer than {MAX_CODE_LENGTH} characters");
+
+    format
+  }
+
+  /// Generates a new random code, formatted for display.
+  ///
+  /// The code starts with a prefix of `CODE_PREFIX_LENGTH` characters that comes on top of `length`,
+  /// so showing the prefix does not weaken the secret.
+  pub fn generate(&self) -> String {
+    let mut rng = rand::rng();
+    let charset = if self.alphabet == OtpAlphabet::Digits { DIGITS } else { CROCKFORD };
+    let prefix = random_chars(charset, CODE_PREFIX_LENGTH);
+
+    match self.alphabet {
+      OtpAlphabet::Words => {
+        let words = (0..self.length)
+          .map(|_| *WORDS.choose(&mut rng).unwrap())
+          .collect::<Vec<&str>>();
+        format!("{prefix}-{}", words.join("-"))
+      }
+      OtpAlphabet::Digits | OtpAlphabet::Crockford => {
+        let secret = self.group(&random_chars(charset, self.length));
+        match self.group_size {
+          0 => format!("{prefix}{secret}"),
+          _ => format!("{prefix}-{secret}"),
+        }
+      }
+    }
+  }
+
+  fn group(&self, raw: &str) -> String {
+    if self.group_size == 0 {
+      return raw.to_string();
+    }
+
+    raw.chars()
+      .collect::<Vec<char>>()
+      .chunks(self.group_size)
+      .map(|chunk| chunk.iter().collect::<String>())
+      .collect::<Vec<String>>()
+      .join("-")
+  }
+
+  fn max_stored_length(&self) -> usize {
+    let secret_length = match self.alphabet {
+      OtpAlphabet::Words => self.length * WORDS.iter().map(|w| w.len()).max().unwrap_or(0),
+      _ => self.length,
+    };
+
+    CODE_PREFIX_LENGTH + secret_length
+  }
+}
+
+/// Brings a code into the form it is stored in.
+///
+/// Separators, whitespace and casing are irrelevant, so `abcd-1234`, `ABCD 1234` and `ABCD1234` are the same code.
+pub fn normalize_code(code: &str) -> String {
+  code.chars()
+    .filter(|c| c.is_ascii_alphanumeric())
+    .map(|c| c.to_ascii_uppercase())
+    .collect()
+}
+
+/// Computes the keyed hash (HMAC-SHA256, hex encoded) a code is stored and looked up by.
+/// The plaintext prefix is left out, only the secret part of the code is hashed.
+pub fn hash_code(secret: &[u8], code: &str) -> String {
+  let code_secret: String = normalize_code(code).chars().skip(CODE_PREFIX_LENGTH).collect();
+
+  let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+  mac.update(code_secret.as_bytes());
+
+  mac.finalize()
+    .into_bytes()
+    .iter()
+    .map(|b| format!("{b:02x}"))
+    .collect()
+}
+
+/// Short plaintext prefix of a code, used to tell codes apart in listings
+pub fn code_prefix(code: &str) -> String {
+  normalize_code(code).chars().take(CODE_PREFIX_LENGTH).collect()
+}
+
+/// Validates a code that has been supplied by a client instead of being generated
+pub fn validate_custom_code(code: &str) -> Result<(), Fault> {
+  let normalized = normalize_code(code);
+
+  if normalized.len() <= CODE_PREFIX_LENGTH || normalized.len() > MAX_CODE_LENGTH {
+    return Err(Fault::MalformedOtpCode);
+  }
+
+  Ok(())
+}
+
+fn random_chars(charset: &[u8], amount: usize) -> String {
+  let mut rng = rand::rng();
+
+  (0..amount)
+    .map(|_| charset[rng.random_range(0..charset.len())] as char)
+    .collect()
+}
+
+fn read_usize_env(name: &str) -> Option<usize> {
+  std::env::var(name)
+    .ok()
+    .map(|v| v.parse::<usize>().unwrap_or_else(|_| panic!("env var '{name}' should be a positive number")))
+}
@@ -0,0 +1,31 @@
+/// Word list used for the `words` OTP alphabet. Its length is a power of two so
+/// every word carries exactly eight bits of entropy.
+pub const WORDS: [&str; 256] = [
+  "acid", "acorn", "acre", "actor", "adapt", "aged", "agent", "aging", "aide", "aim",
+  "ajar", "alarm", "album", "alert", "alike", "alive", "alley", "alpha", "amber",
+  "ample", "angel", "anger", "angle", "ankle", "apple", "april", "apron", "arena",
+  "argue", "armor", "army", "aroma", "arrow", "art", "ashen", "aside", "atlas", "atom",
+  "attic", "audio", "aunt", "avoid", "awake", "award", "axis", "bacon", "badge", "bagel",
+  "baker", "balmy", "bamboo", "banjo", "barn", "basil", "basin", "batch", "bath",
+  "beach", "beam", "bean", "bench", "berry", "bike", "bingo", "birch", "bison", "blade",
+  "blend", "bliss", "bloom", "blue", "blunt", "board", "boat", "body", "bolt", "bonus",
+  "book", "boost", "boots", "brave", "bread", "brick", "brim", "broom", "brush", "bud",
+  "buggy", "bulb", "bunny", "cabin", "cable", "cactus", "cadet", "camel", "candy",
+  "canoe", "cargo", "carol", "carpet", "cedar", "chair", "chalk", "charm", "chef",
+  "chess", "chip", "cider", "cinch", "civic", "clamp", "claw", "clay", "cliff", "clock",
+  "cloud", "clove", "coast", "cobra", "cocoa", "comet", "coral", "cork", "couch", "cove",
+  "crane", "crate", "crisp", "crumb", "cubic", "cupid", "curry", "cycle", "daisy",
+  "dance", "darts", "dawn", "decaf", "delta", "denim", "depot", "diary", "dice", "digit",
+  "dime", "diner", "disco", "dock", "dolphin", "dome", "donut", "dove", "dozen", "drift",
+  "drum", "dune", "eagle", "earth", "easel", "ebony", "echo", "elbow", "elder", "elm",
+  "ember", "emoji", "envoy", "epic", "equal", "ether", "fable", "fancy", "fern", "ferry",
+  "fiber", "fiddle", "field", "finch", "flame", "flask", "fleet", "flint", "flute",
+  "foam", "focus", "foggy", "forge", "fossil", "fox", "frost", "fruit", "gecko", "genie",
+  "giant", "ginger", "glade", "globe", "glove", "goose", "gourd", "grain", "grape",
+  "gravy", "grid", "grove", "guava", "gull", "habit", "hammer", "happy", "harbor",
+  "hazel", "heron", "hiker", "hippo", "honey", "hotel", "husky", "igloo", "image",
+  "index", "ivory", "jacket", "jade", "jelly", "jewel", "jigsaw", "jolly", "judge",
+  "juice", "jumbo", "karma", "kayak", "kettle", "kiosk", "kite", "koala", "ladle",
+  "lagoon", "lemon", "lilac", "linen", "llama", "lobby", "lotus", "lucky", "lunar",
+  "magma", "mango", "maple", "marsh", "medal", "melon", "mint", "mocha", "moose",
+];
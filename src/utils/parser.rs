@@ -7,7 +7,7 @@ pub fn get_authorization_as_uuid(headers: &HeaderMap) -> Result<String, Fault> {
     .get(header::AUTHORIZATION);
 
   if let Some(st) = auth_string {
-    let x = st.to_str().or_else(|_| Err(Fault::UuidConversion))?;
+    let x = st.to_str().map_err(|_| Fault::UuidConversion)?;
 
     let y = x.split(' ').nth(1);
     if let Some(token) = y {
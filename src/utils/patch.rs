@@ -0,0 +1,11 @@
+use serde::{Deserialize, Deserializer};
+
+/// Deserializes a field of a partial update, so a missing field (`None`) can be told apart from an explicit `null` (`Some(None)`).
+/// Has to be combined with `#[serde(default)]`.
+pub fn double_option<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+  T: Deserialize<'de>,
+  D: Deserializer<'de>,
+{
+  Option::<T>::deserialize(deserializer).map(Some)
+}
@@ -0,0 +1,8 @@
+use diesel::{define_sql_function, sql_types::{Nullable, Text}};
+
+define_sql_function!(fn lower(x: Text) -> Text);
+
+define_sql_function! {
+  #[sql_name = "lower"]
+  fn lower_nullable(x: Nullable<Text>) -> Nullable<Text>;
+}
@@ -0,0 +1,67 @@
+use unicode_normalization::UnicodeNormalization;
+
+use crate::{state::config::UsernameRules, utils::{error::Fault, validation::MAX_USERNAME_LENGTH}};
+
+/// Form usernames are compared in: NFKC normalized and case folded, so `Alice`, `alice` and `ａｌｉｃｅ` are the same name
+pub fn canonical_username(name: &str) -> String {
+  let normalized: String = name.nfkc().collect();
+  caseless::default_case_fold_str(&normalized).nfkc().collect()
+}
+
+/// Checks a new username against `USERNAME_MIN_LENGTH`, `USERNAME_MAX_LENGTH` and `USERNAME_PATTERN`
+pub fn validate_username(name: &str, rules: &UsernameRules) -> Result<(), Fault> {
+  let length = name.chars().count();
+  let canonical = canonical_username(name);
+
+  if length < rules.min_length || length > rules.max_length || canonical.chars().count() > MAX_USERNAME_LENGTH {
+    return Err(Fault::Validation(format!("A username must consist of {} to {} characters", rules.min_length, rules.max_length)));
+  }
+
+  if !rules.pattern.is_match(name) {
+    return Err(Fault::Validation("The username contains characters that are not allowed".to_string()));
+  }
+
+  Ok(())
+}
+
+/// Rejects names on `BLOCKED_USERNAMES` in any spelling, admins may still hand them out
+pub fn ensure_username_not_blocked(name: &str, rules: &UsernameRules) -> Result<(), Fault> {
+  if rules.blocked.contains(&canonical_username(name)) {
+    return Err(Fault::Validation("This username is not available".to_string()));
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::canonical_username;
+
+  #[test]
+  fn casing_is_irrelevant() {
+    assert_eq!(canonical_username("Alice"), "alice");
+    assert_eq!(canonical_username("ALICE"), canonical_username("alice"));
+  }
+
+  #[test]
+  fn compatibility_forms_are_normalized() {
+    assert_eq!(canonical_username("ａｌｉｃｅ"), "alice");
+    assert_eq!(canonical_username("ﬁle"), "file");
+  }
+
+  #[test]
+  fn composed_and_decomposed_forms_are_equal() {
+    assert_eq!(canonical_username("Ame\u{301}lie"), canonical_username("Amélie"));
+  }
+
+  #[test]
+  fn case_folding_goes_beyond_lowercase() {
+    assert_eq!(canonical_username("Straße"), canonical_username("STRASSE"));
+  }
+
+  #[test]
+  fn distinct_names_stay_distinct() {
+    assert_ne!(canonical_username("alice"), canonical_username("alicia"));
+    assert_ne!(canonical_username("alice"), canonical_username("alice "));
+  }
+}
@@ -0,0 +1,59 @@
+/// Maximum length of an email address, mirrors the width of `users.email`
+pub const MAX_EMAIL_LENGTH: usize = 254;
+
+/// Maximum lengths of the remaining profile fields, mirror the widths of their columns in `users`
+pub const MAX_USERNAME_LENGTH: usize = 64;
+pub const MAX_DISPLAY_NAME_LENGTH: usize = 128;
+pub const MAX_LOCALE_LENGTH: usize = 35;
+
+/// Maximum length of the reason shown to a blocked user
+pub const MAX_BLOCKED_REASON_LENGTH: usize = 500;
+
+/// Maximum length of the reason mailed to a user whose registration has been rejected
+pub const MAX_REJECTION_REASON_LENGTH: usize = 500;
+
+/// Maximum lengths of the name and the description of a role
+pub const MAX_ROLE_NAME_LENGTH: usize = 64;
+pub const MAX_ROLE_DESCRIPTION_LENGTH: usize = 500;
+
+/// Maximum lengths of the name and the description of a group, the name mirrors the width of `groups.name`
+pub const MAX_GROUP_NAME_LENGTH: usize = 64;
+pub const MAX_GROUP_DESCRIPTION_LENGTH: usize = 500;
+
+/// Maximum length of the slug of a tenant, the limit of a DNS label so every slug can serve as subdomain
+pub const MAX_TENANT_SLUG_LENGTH: usize = 63;
+/// Maximum length of the name of a tenant, mirrors the width of `tenants.name`
+pub const MAX_TENANT_NAME_LENGTH: usize = 128;
+
+/// Plausibility check of an email address. Ownership is proven by an `EMAIL_VERIFY` code, not by this check.
+pub fn is_valid_email(email: &str) -> bool {
+  if email.len() > MAX_EMAIL_LENGTH || email.chars().any(|c| c.is_whitespace()) {
+    return false;
+  }
+
+  match email.split_once('@') {
+    Some((local, domain)) => !local.is_empty() && !domain.contains('@') && domain.contains('.') && !domain.starts_with('.') && !domain.ends_with('.'),
+    None => false,
+  }
+}
+
+/// Checks the shape of a BCP 47 language tag like `en` or `de-AT`, without checking the subtags against the registry
+pub fn is_valid_locale(locale: &str) -> bool {
+  if locale.len() > MAX_LOCALE_LENGTH {
+    return false;
+  }
+
+  let mut subtags = locale.split('-');
+  let language_valid = subtags.next()
+    .is_some_and(|language| (2..=8).contains(&language.len()) && language.chars().all(|c| c.is_ascii_alphabetic()));
+
+  language_valid && subtags.all(|subtag| (1..=8).contains(&subtag.len()) && subtag.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+/// Whether `slug` is a lowercase DNS label, so it works in a path, a header and as subdomain alike
+pub fn is_valid_tenant_slug(slug: &str) -> bool {
+  (1..=MAX_TENANT_SLUG_LENGTH).contains(&slug.len())
+    && slug.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+    && !slug.starts_with('-')
+    && !slug.ends_with('-')
+}